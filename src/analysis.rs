@@ -0,0 +1,3 @@
+pub(crate) mod sitemap;
+pub(crate) mod params;
+pub(crate) mod diff;
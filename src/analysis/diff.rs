@@ -0,0 +1,202 @@
+use bstr::ByteSlice;
+use hyper::HeaderMap;
+use similar::{ChangeTag, TextDiff};
+
+use crate::cruster_proxy::request_response::{HyperRequestWrapper, HyperResponseWrapper};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum DiffMode {
+    Lines,
+    Words,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum DiffTag {
+    Equal,
+    Removed,
+    Added,
+}
+
+impl From<ChangeTag> for DiffTag {
+    fn from(tag: ChangeTag) -> Self {
+        match tag {
+            ChangeTag::Equal => DiffTag::Equal,
+            ChangeTag::Delete => DiffTag::Removed,
+            ChangeTag::Insert => DiffTag::Added,
+        }
+    }
+}
+
+/// Request or response split into parts which are compared separately
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DiffSubject {
+    pub(crate) first_line: String,
+    pub(crate) headers: Vec<String>,
+    pub(crate) body: String,
+}
+
+fn headers_to_lines(headers: &HeaderMap) -> Vec<String> {
+    let mut lines: Vec<String> = headers
+        .iter()
+        .map(|(k, v)| format!("{}: {}", k.as_str(), v.as_bytes().to_str_lossy()))
+        .collect();
+
+    // Order of headers is not a difference worth showing
+    lines.sort();
+    return lines;
+}
+
+impl From<&HyperRequestWrapper> for DiffSubject {
+    fn from(request: &HyperRequestWrapper) -> Self {
+        DiffSubject {
+            first_line: format!("{} {} {}", &request.method, &request.uri, &request.version),
+            headers: headers_to_lines(&request.headers),
+            body: request.body.to_str_lossy().to_string(),
+        }
+    }
+}
+
+impl From<&HyperResponseWrapper> for DiffSubject {
+    fn from(response: &HyperResponseWrapper) -> Self {
+        DiffSubject {
+            first_line: format!("{} {}", &response.version, &response.status),
+            headers: headers_to_lines(&response.headers),
+            body: response.body.to_str_lossy().to_string(),
+        }
+    }
+}
+
+impl DiffSubject {
+    /// Parses request or response in text form, i.e. the one shown in repeater
+    pub(crate) fn from_text(text: &str) -> Self {
+        let text = text.replace("\r\n", "\n");
+        let (head, body) = match text.split_once("\n\n") {
+            Some((head, body)) => (head, body),
+            None => (text.as_str(), "")
+        };
+
+        let mut head_lines = head.lines();
+        let first_line = head_lines.next().unwrap_or_default().to_string();
+        let mut headers: Vec<String> = head_lines
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string())
+            .collect();
+        headers.sort();
+
+        DiffSubject {
+            first_line,
+            headers,
+            body: body.to_string(),
+        }
+    }
+
+    fn headers_text(&self) -> String {
+        let mut text = self.headers.join("\n");
+        if !text.is_empty() {
+            text.push('\n');
+        }
+
+        return text;
+    }
+
+    fn full_text(&self) -> String {
+        return format!("{}\n{}\n{}", &self.first_line, self.headers_text(), &self.body);
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct DiffSection {
+    pub(crate) name: &'static str,
+    // Lines or words depending on mode, line chunks keep their line breaks
+    pub(crate) chunks: Vec<(DiffTag, String)>,
+}
+
+impl DiffSection {
+    pub(crate) fn has_changes(&self) -> bool {
+        return self.chunks.iter().any(|(tag, _)| *tag != DiffTag::Equal);
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct DiffStats {
+    pub(crate) left_length: usize,
+    pub(crate) right_length: usize,
+    pub(crate) removed_lines: usize,
+    pub(crate) added_lines: usize,
+    // From 0.0 for completely different to 1.0 for equal
+    pub(crate) similarity: f32,
+}
+
+impl DiffStats {
+    /// Difference of body lengths, positive if the right one is longer
+    pub(crate) fn length_delta(&self) -> i64 {
+        return self.right_length as i64 - self.left_length as i64;
+    }
+
+    pub(crate) fn summary(&self) -> String {
+        return format!(
+            "similarity: {:.1}%, body length: {} -> {} ({:+}), lines: -{} +{}",
+            self.similarity * 100.0,
+            self.left_length,
+            self.right_length,
+            self.length_delta(),
+            self.removed_lines,
+            self.added_lines
+        );
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Comparison {
+    pub(crate) sections: Vec<DiffSection>,
+    pub(crate) stats: DiffStats,
+}
+
+fn make_chunks(left: &str, right: &str, mode: DiffMode) -> Vec<(DiffTag, String)> {
+    let diff = match mode {
+        DiffMode::Lines => TextDiff::from_lines(left, right),
+        DiffMode::Words => TextDiff::from_words(left, right),
+    };
+
+    return diff
+        .iter_all_changes()
+        .map(|change| (DiffTag::from(change.tag()), change.value().to_string()))
+        .collect();
+}
+
+pub(crate) fn compare(left: &DiffSubject, right: &DiffSubject, mode: DiffMode) -> Comparison {
+    let sections = vec![
+        DiffSection {
+            name: "first line",
+            chunks: make_chunks(&format!("{}\n", &left.first_line), &format!("{}\n", &right.first_line), mode),
+        },
+        DiffSection {
+            name: "headers",
+            chunks: make_chunks(&left.headers_text(), &right.headers_text(), mode),
+        },
+        DiffSection {
+            name: "body",
+            chunks: make_chunks(&left.body, &right.body, mode),
+        },
+    ];
+
+    // Metrics are always line-based, so they do not depend on chosen mode
+    let (left_text, right_text) = (left.full_text(), right.full_text());
+    let line_diff = TextDiff::from_lines(&left_text, &right_text);
+    let mut stats = DiffStats {
+        left_length: left.body.len(),
+        right_length: right.body.len(),
+        similarity: line_diff.ratio(),
+        ..DiffStats::default()
+    };
+
+    for change in line_diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Delete => stats.removed_lines += 1,
+            ChangeTag::Insert => stats.added_lines += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+
+    return Comparison { sections, stats };
+}
@@ -0,0 +1,298 @@
+use std::fmt::Display;
+use std::collections::BTreeMap;
+
+use bstr::ByteSlice;
+use hyper::HeaderMap;
+use serde::{Serialize, Serializer};
+use serde_json as json;
+use url::form_urlencoded;
+
+use crate::http_storage::RequestResponsePair;
+
+// How many different example values are kept for one parameter
+const MAX_EXAMPLES: usize = 3;
+// Long values (tokens, blobs) are cut to keep the output readable
+const MAX_EXAMPLE_LENGTH: usize = 64;
+// Headers which describe transport rather than application, they are not worth listing
+const SKIPPED_HEADERS: [&str; 7] = [
+    "host",
+    "content-length",
+    "connection",
+    "proxy-connection",
+    "keep-alive",
+    "transfer-encoding",
+    "cookie",
+];
+
+fn values_as_list<K, V: Serialize, S: Serializer>(map: &BTreeMap<K, V>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(map.values())
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ParamLocation {
+    Query,
+    Form,
+    Json,
+    Cookie,
+    Header,
+    // Keys of JSON in responses, ones missing in requests are mass-assignment candidates
+    ResponseJson,
+}
+
+impl Display for ParamLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ParamLocation::Query => "query",
+            ParamLocation::Form => "form",
+            ParamLocation::Json => "json",
+            ParamLocation::Cookie => "cookie",
+            ParamLocation::Header => "header",
+            ParamLocation::ResponseJson => "response_json",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct Parameter {
+    pub(crate) name: String,
+    pub(crate) location: ParamLocation,
+    pub(crate) count: usize,
+    pub(crate) first_seen: usize,
+    pub(crate) examples: Vec<String>,
+}
+
+impl Parameter {
+    fn account(&mut self, value: &str) {
+        self.count += 1;
+        if self.examples.len() >= MAX_EXAMPLES {
+            return;
+        }
+
+        let value: String = if value.chars().count() > MAX_EXAMPLE_LENGTH {
+            let mut cut: String = value.chars().take(MAX_EXAMPLE_LENGTH).collect();
+            cut.push_str("...");
+            cut
+        }
+        else {
+            value.to_string()
+        };
+
+        if !self.examples.contains(&value) {
+            self.examples.push(value);
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct Endpoint {
+    pub(crate) method: String,
+    // URL without query, i.e. 'https://example.com/api/login'
+    pub(crate) url: String,
+    pub(crate) count: usize,
+    pub(crate) first_seen: usize,
+    #[serde(serialize_with = "values_as_list")]
+    pub(crate) params: BTreeMap<(ParamLocation, String), Parameter>,
+}
+
+impl Endpoint {
+    fn add_param(&mut self, location: ParamLocation, name: &str, value: &str, index: usize) {
+        self.params
+            .entry((location, name.to_string()))
+            .or_insert_with(|| {
+                Parameter {
+                    name: name.to_string(),
+                    location,
+                    count: 0,
+                    first_seen: index,
+                    examples: Vec::with_capacity(MAX_EXAMPLES),
+                }
+            })
+            .account(value);
+    }
+
+    fn add_json_params(&mut self, location: ParamLocation, prefix: &str, value: &json::Value, index: usize) {
+        match value {
+            json::Value::Object(map) if !map.is_empty() => {
+                for (key, nested) in map {
+                    let name = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                    self.add_json_params(location, &name, nested, index);
+                }
+            },
+            json::Value::Array(list) if !list.is_empty() => {
+                let name = format!("{}[]", prefix);
+                for nested in list {
+                    self.add_json_params(location, &name, nested, index);
+                }
+            },
+            json::Value::String(s) => {
+                self.add_param(location, prefix, s, index);
+            },
+            _ => {
+                // Top-level scalars have no name to list them by
+                if !prefix.is_empty() {
+                    self.add_param(location, prefix, &value.to_string(), index);
+                }
+            }
+        }
+    }
+
+    /// Path of the endpoint with its method, i.e. 'POST https://example.com/api/login'
+    pub(crate) fn title(&self) -> String {
+        return format!("{} {}", &self.method, &self.url);
+    }
+}
+
+fn is_content_type(headers: &HeaderMap, needle: &str) -> bool {
+    return headers
+        .get_all("content-type")
+        .iter()
+        .any(|ct| ct.as_bytes().to_str_lossy().to_lowercase().contains(needle));
+}
+
+fn parse_json_body(headers: &HeaderMap, body: &[u8]) -> Option<json::Value> {
+    let trimmed = body.trim_start();
+    let looks_like_json = trimmed.starts_with(b"{") || trimmed.starts_with(b"[");
+    if !is_content_type(headers, "json") && !looks_like_json {
+        return None;
+    }
+
+    return json::from_slice(body).ok();
+}
+
+#[derive(Serialize, Default, Debug)]
+pub(crate) struct ParamsInventory {
+    #[serde(serialize_with = "values_as_list")]
+    pub(crate) endpoints: BTreeMap<(String, String), Endpoint>,
+}
+
+impl ParamsInventory {
+    pub(crate) fn from_pairs<'a, T>(pairs: T) -> Self where T: IntoIterator<Item = &'a RequestResponsePair> {
+        let mut inventory = ParamsInventory::default();
+        for pair in pairs {
+            inventory.add_pair(pair);
+        }
+
+        return inventory;
+    }
+
+    pub(crate) fn add_pair(&mut self, pair: &RequestResponsePair) {
+        let request = match pair.request.as_ref() {
+            Some(request) => request,
+            None => return
+        };
+
+        if request.method == "CONNECT" {
+            return;
+        }
+
+        let path = match request.get_request_path_without_query() {
+            Ok(path) => path,
+            Err(_) => request.get_request_path()
+        };
+
+        let url = format!("{}{}{}", request.get_scheme(), request.get_hostname(), path);
+        let index = pair.index;
+        let endpoint = self.endpoints
+            .entry((url.clone(), request.method.clone()))
+            .or_insert_with(|| {
+                Endpoint {
+                    method: request.method.clone(),
+                    url,
+                    count: 0,
+                    first_seen: index,
+                    params: BTreeMap::default(),
+                }
+            });
+        endpoint.count += 1;
+
+        if let Some(query) = request.get_query() {
+            let query = query.trim_start_matches('?');
+            let query = query.split('#').next().unwrap_or_default();
+            for (name, value) in form_urlencoded::parse(query.as_bytes()) {
+                endpoint.add_param(ParamLocation::Query, &name, &value, index);
+            }
+        }
+
+        if !request.body.is_empty() {
+            if is_content_type(&request.headers, "application/x-www-form-urlencoded") {
+                for (name, value) in form_urlencoded::parse(&request.body) {
+                    endpoint.add_param(ParamLocation::Form, &name, &value, index);
+                }
+            }
+            else if let Some(body) = parse_json_body(&request.headers, &request.body) {
+                endpoint.add_json_params(ParamLocation::Json, "", &body, index);
+            }
+        }
+
+        for cookie_header in request.headers.get_all("cookie") {
+            let cookies = cookie_header.as_bytes().to_str_lossy();
+            for cookie in cookies.split(';').map(|c| c.trim()).filter(|c| !c.is_empty()) {
+                let (name, value) = cookie.split_once('=').unwrap_or((cookie, ""));
+                endpoint.add_param(ParamLocation::Cookie, name, value, index);
+            }
+        }
+
+        for (name, value) in request.headers.iter() {
+            if SKIPPED_HEADERS.contains(&name.as_str()) {
+                continue;
+            }
+
+            endpoint.add_param(ParamLocation::Header, name.as_str(), &value.as_bytes().to_str_lossy(), index);
+        }
+
+        if let Some(response) = pair.response.as_ref() {
+            if is_content_type(&response.headers, "json") {
+                if let Some(body) = parse_json_body(&response.headers, &response.body) {
+                    endpoint.add_json_params(ParamLocation::ResponseJson, "", &body, index);
+                }
+            }
+        }
+    }
+
+    /// Whole inventory as text table, endpoint title is followed by its parameters
+    pub(crate) fn to_table_lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::with_capacity(self.endpoints.len() * 8);
+        for endpoint in self.endpoints.values() {
+            lines.extend(ParamsInventory::endpoint_to_lines(endpoint));
+            lines.push(String::default());
+        }
+
+        return lines;
+    }
+
+    pub(crate) fn endpoint_to_lines(endpoint: &Endpoint) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::with_capacity(endpoint.params.len() + 2);
+        lines.push(format!("{} (requests: {}, first seen: #{})", endpoint.title(), endpoint.count, endpoint.first_seen));
+
+        if endpoint.params.is_empty() {
+            lines.push("    <NO PARAMETERS>".to_string());
+            return lines;
+        }
+
+        let name_width = endpoint.params
+            .values()
+            .map(|p| p.name.chars().count())
+            .max()
+            .unwrap_or_default()
+            .min(40);
+
+        for param in endpoint.params.values() {
+            lines.push(
+                format!(
+                    "    {:<13} {:<name_width$} {:>5} {:>7}  {}",
+                    param.location.to_string(),
+                    &param.name,
+                    param.count,
+                    format!("#{}", param.first_seen),
+                    param.examples.join(" | "),
+                    name_width = name_width
+                )
+            );
+        }
+
+        return lines;
+    }
+}
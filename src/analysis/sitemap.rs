@@ -0,0 +1,170 @@
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::http_storage::RequestResponsePair;
+
+fn children_as_list<S: Serializer>(children: &BTreeMap<String, SiteMapNode>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(children.values())
+}
+
+#[derive(Serialize, Default, Debug)]
+pub(crate) struct SiteMapNode {
+    pub(crate) name: String,
+    // URL prefix, which identifies the node, i.e. 'https://example.com/api'
+    pub(crate) prefix: String,
+    pub(crate) count: usize,
+    pub(crate) statuses: BTreeMap<String, usize>,
+    pub(crate) params: BTreeSet<String>,
+    #[serde(serialize_with = "children_as_list")]
+    pub(crate) children: BTreeMap<String, SiteMapNode>,
+}
+
+impl SiteMapNode {
+    fn new(name: &str, prefix: String) -> Self {
+        SiteMapNode {
+            name: name.to_string(),
+            prefix,
+            ..SiteMapNode::default()
+        }
+    }
+
+    fn account(&mut self, status: Option<&str>) {
+        self.count += 1;
+        if let Some(status) = status {
+            *self.statuses.entry(status.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    pub(crate) fn statuses_summary(&self) -> String {
+        return self.statuses
+            .iter()
+            .map(|(status, count)| format!("{}:{}", status, count))
+            .collect::<Vec<String>>()
+            .join(" ");
+    }
+
+    /// Short one-line description of node without its name
+    pub(crate) fn summary(&self) -> String {
+        let mut result = format!("({})", self.count);
+        if !self.statuses.is_empty() {
+            result.push_str(&format!(" [{}]", self.statuses_summary()));
+        }
+
+        if !self.params.is_empty() {
+            let params = self.params
+                .iter()
+                .map(|p| p.as_str())
+                .collect::<Vec<&str>>()
+                .join(", ");
+            result.push_str(&format!(" ?{}", params));
+        }
+
+        return result;
+    }
+
+    pub(crate) fn find(&self, prefix: &str) -> Option<&SiteMapNode> {
+        if self.prefix == prefix {
+            return Some(self);
+        }
+
+        for child in self.children.values() {
+            if prefix.starts_with(&child.prefix) {
+                if let Some(found) = child.find(prefix) {
+                    return Some(found);
+                }
+            }
+        }
+
+        return None;
+    }
+}
+
+#[derive(Serialize, Default, Debug)]
+pub(crate) struct SiteMap {
+    #[serde(serialize_with = "children_as_list")]
+    pub(crate) hosts: BTreeMap<String, SiteMapNode>,
+}
+
+impl SiteMap {
+    pub(crate) fn from_pairs<'a, T>(pairs: T) -> Self where T: IntoIterator<Item = &'a RequestResponsePair> {
+        let mut sitemap = SiteMap::default();
+        for pair in pairs {
+            sitemap.add_pair(pair);
+        }
+
+        return sitemap;
+    }
+
+    pub(crate) fn add_pair(&mut self, pair: &RequestResponsePair) {
+        let request = match pair.request.as_ref() {
+            Some(request) => request,
+            None => return
+        };
+
+        // Tunnels have no path to place them at
+        if request.method == "CONNECT" {
+            return;
+        }
+
+        let status = pair.response
+            .as_ref()
+            .map(|rsp| rsp.status.split(' ').next().unwrap_or_default().to_string());
+
+        let origin = format!("{}{}", request.get_scheme(), request.get_hostname());
+        let path = match request.get_request_path_without_query() {
+            Ok(path) => path,
+            Err(_) => request.get_request_path()
+        };
+
+        let mut node = self.hosts
+            .entry(origin.clone())
+            .or_insert_with(|| SiteMapNode::new(&origin, origin.clone()));
+        node.account(status.as_deref());
+
+        for segment in path.split('/').filter(|s| !s.is_empty()) {
+            let prefix = format!("{}/{}", &node.prefix, segment);
+            node = node.children
+                .entry(segment.to_string())
+                .or_insert_with(|| SiteMapNode::new(&format!("/{}", segment), prefix));
+            node.account(status.as_deref());
+        }
+
+        if let Some(query) = request.get_query() {
+            let query = query.trim_start_matches('?');
+            let query = query.split('#').next().unwrap_or_default();
+            for param in query.split('&').filter(|p| !p.is_empty()) {
+                let name = param.split('=').next().unwrap_or_default();
+                node.params.insert(name.to_string());
+            }
+        }
+    }
+
+    pub(crate) fn find(&self, prefix: &str) -> Option<&SiteMapNode> {
+        for host in self.hosts.values() {
+            if prefix.starts_with(&host.prefix) {
+                if let Some(found) = host.find(prefix) {
+                    return Some(found);
+                }
+            }
+        }
+
+        return None;
+    }
+
+    fn node_to_lines(node: &SiteMapNode, depth: usize, lines: &mut Vec<String>) {
+        lines.push(format!("{}{} {}", "  ".repeat(depth), &node.name, node.summary()));
+        for child in node.children.values() {
+            SiteMap::node_to_lines(child, depth + 1, lines);
+        }
+    }
+
+    /// Whole map as indented tree, one node per line
+    pub(crate) fn to_tree_lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::with_capacity(self.hosts.len() * 10);
+        for host in self.hosts.values() {
+            SiteMap::node_to_lines(host, 0, &mut lines);
+        }
+
+        return lines;
+    }
+}
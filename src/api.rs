@@ -0,0 +1,428 @@
+use std::convert::Infallible;
+use std::net::{SocketAddr, TcpListener};
+use std::path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use crossbeam_channel::Sender;
+use hyper::{
+    Body,
+    Method,
+    Request,
+    Response,
+    Server,
+    StatusCode,
+    header,
+    service::{make_service_fn, service_fn}
+};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::Serialize;
+use serde_json as json;
+use tokio::sync::broadcast;
+
+use crate::{
+    cli::repeater::{RepeaterIterator, exec::send_saved},
+    config::{Api, Config, Scope},
+    http_storage::{RequestResponsePair, serializable::SerializableProxyData},
+    project::{self, files::RecordReader},
+    query::Query,
+    scope::ScopeMatcher,
+    session::Sessions,
+    utils::CrusterError
+};
+
+/// How many events are kept for slow subscriber before it misses them
+const EVENTS_CAPACITY: usize = 1024;
+const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+/// Changes made with API which are applied by dump loop
+pub(crate) enum ApiCommand {
+    SetScope(Scope),
+}
+
+/// Ends of channels which connect API with dump loop
+pub(crate) struct ApiChannels {
+    pub(crate) commands: crossbeam_channel::Receiver<ApiCommand>,
+    pub(crate) events: broadcast::Sender<String>,
+}
+
+struct ApiState {
+    token: Option<String>,
+    // Address API is bound to, requests with other Host are rejected against DNS rebinding
+    address: SocketAddr,
+    mode: &'static str,
+    proxy_address: String,
+    project: Option<String>,
+    scope: Mutex<Option<Scope>>,
+    commands: Sender<ApiCommand>,
+    events: broadcast::Sender<String>,
+    sessions: Arc<Sessions>,
+}
+
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl ToString) -> Self {
+        return ApiError { status, message: message.to_string() };
+    }
+
+    fn bad_request(message: impl ToString) -> Self {
+        return ApiError::new(StatusCode::BAD_REQUEST, message);
+    }
+
+    fn not_found(message: impl ToString) -> Self {
+        return ApiError::new(StatusCode::NOT_FOUND, message);
+    }
+
+    fn internal(message: impl ToString) -> Self {
+        return ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, message);
+    }
+}
+
+type ApiResult = Result<Response<Body>, ApiError>;
+
+/// Length of token made when none is set
+const GENERATED_TOKEN_LENGTH: usize = 32;
+
+/// Sets random token if API has none. Returns it, so it can be shown to user
+pub(crate) fn generate_token_if_missing(api: &mut Api) -> Option<String> {
+    if api.token.is_some() {
+        return None;
+    }
+
+    let token: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(GENERATED_TOKEN_LENGTH)
+        .map(char::from)
+        .collect();
+
+    api.token = Some(token.clone());
+    return Some(token);
+}
+
+/// Listener is bound by caller, so busy port is reported before proxy is started
+pub(crate) fn bind(address: &str) -> Result<TcpListener, CrusterError> {
+    let listener = TcpListener::bind(address)?;
+    listener.set_nonblocking(true)?;
+    return Ok(listener);
+}
+
+/// Serves API until process exits. Returns channels to be handled by dump loop
+pub(crate) fn serve(listener: TcpListener, config: &Config, mode: &'static str, sessions: Arc<Sessions>) -> Result<ApiChannels, CrusterError> {
+    let (commands_tx, commands_rx) = crossbeam_channel::unbounded();
+    let (events_tx, _) = broadcast::channel(EVENTS_CAPACITY);
+
+    let state = Arc::new(
+        ApiState {
+            token: config.api.as_ref().and_then(|api| api.token.clone()),
+            address: listener.local_addr()?,
+            mode,
+            proxy_address: format!("{}:{}", config.address, config.port),
+            project: config.project.clone(),
+            scope: Mutex::new(config.scope.clone()),
+            commands: commands_tx,
+            events: events_tx.clone(),
+            sessions,
+        }
+    );
+
+    let server = Server::from_tcp(listener)?;
+    let make_service = make_service_fn(move |_| {
+        let state = state.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| handle(state.clone(), req)))
+        }
+    });
+
+    tokio::task::spawn(
+        async move {
+            if let Err(err) = server.serve(make_service).await {
+                eprintln!("API error: {}", err);
+            }
+        }
+    );
+
+    return Ok(ApiChannels { commands: commands_rx, events: events_tx });
+}
+
+async fn handle(state: Arc<ApiState>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let result = if let Err(err) = check_origin(&state, &req) {
+        Err(err)
+    }
+    else if is_authorized(&state, &req) {
+        route(state, req).await
+    }
+    else {
+        Err(ApiError::new(StatusCode::UNAUTHORIZED, "Token is required as 'Authorization: Bearer <token>'"))
+    };
+
+    return Ok(result.unwrap_or_else(|err| json_response(err.status, &json::json!({ "error": err.message }))));
+}
+
+/// Browsers send 'Origin' with cross-origin requests, so web pages cannot use API. Host must be the address
+/// of API, so page of rebound domain cannot read it either
+fn check_origin(state: &ApiState, req: &Request<Body>) -> Result<(), ApiError> {
+    if req.headers().contains_key(header::ORIGIN) {
+        return Err(ApiError::new(StatusCode::FORBIDDEN, "Requests from browsers are not accepted"));
+    }
+
+    let host = req.headers()
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    let port = state.address.port();
+    let allowed = host == state.address.to_string()
+        || (state.address.ip().is_loopback() && host == format!("localhost:{}", port))
+        // Address is not known for unspecified one, token is required there
+        || (state.address.ip().is_unspecified() && host.rsplit_once(':').is_some_and(|(_, p)| p == port.to_string()));
+
+    if !allowed {
+        return Err(ApiError::new(StatusCode::FORBIDDEN, format!("Host '{}' is not the address of API", host)));
+    }
+
+    return Ok(());
+}
+
+fn is_authorized(state: &ApiState, req: &Request<Body>) -> bool {
+    // Token is always set when API is served, see 'generate_token_if_missing'
+    let token = match state.token.as_ref() {
+        Some(token) => token,
+        None => return false
+    };
+
+    return req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|value| value.trim() == token);
+}
+
+async fn route(state: Arc<ApiState>, req: Request<Body>) -> ApiResult {
+    let path = req.uri().path().trim_end_matches('/').to_string();
+    let segments: Vec<&str> = path.split('/').skip(1).collect();
+
+    return match (req.method(), segments.as_slice()) {
+        (&Method::GET, ["status"]) => status(&state),
+        (&Method::GET, ["http"]) => history(&state, req.uri().query().unwrap_or_default()),
+        (&Method::GET, ["http", id]) => pair_by_id(&state, id),
+        (&Method::GET, ["scope"]) => get_scope(&state),
+        (&Method::PUT, ["scope"]) => put_scope(&state, req).await,
+        (&Method::GET, ["repeaters"]) => list_repeaters(&state),
+        (&Method::POST, ["repeaters", mark, "send"]) => {
+            let mark = mark.to_string();
+            send_repeater(&state, &mark).await
+        },
+        (&Method::GET, ["events"]) => subscribe(&state),
+        _ => Err(ApiError::not_found(format!("No such endpoint: {} {}", req.method(), path)))
+    };
+}
+
+fn json_response<T: Serialize>(status: StatusCode, value: &T) -> Response<Body> {
+    let body = json::to_string(value).unwrap_or_default();
+    return Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap();
+}
+
+fn project_file(state: &ApiState, name: &str) -> Result<String, ApiError> {
+    return match state.project.as_ref() {
+        Some(project) => Ok(format!("{}/{}", project, name)),
+        None => Err(ApiError::not_found("No project is set, so nothing is stored"))
+    };
+}
+
+fn status(state: &ApiState) -> ApiResult {
+    let status = json::json!({
+        "mode": state.mode,
+        "proxy": state.proxy_address,
+        "project": state.project,
+        "pid": std::process::id(),
+        "subscribers": state.events.receiver_count(),
+    });
+
+    return Ok(json_response(StatusCode::OK, &status));
+}
+
+/// Stored pairs are read from project, so history is the same as CLI shows
+fn read_pairs(state: &ApiState, mut keep: impl FnMut(&RequestResponsePair) -> bool) -> Result<Vec<SerializableProxyData>, ApiError> {
+    let http_path = project_file(state, "http.jsonl")?;
+    let mut pairs: Vec<SerializableProxyData> = Vec::default();
+    if !path::Path::new(&http_path).is_file() {
+        return Ok(pairs);
+    }
+
+    for record in RecordReader::open(&http_path).map_err(ApiError::internal)? {
+        let record = record.map_err(ApiError::internal)?;
+        let serializable: SerializableProxyData = json::from_str(&record).map_err(ApiError::internal)?;
+        let pair: RequestResponsePair = serializable.try_into().map_err(ApiError::internal)?;
+        if keep(&pair) {
+            pairs.push(SerializableProxyData::try_from(&pair).map_err(ApiError::internal)?);
+        }
+    }
+
+    return Ok(pairs);
+}
+
+fn history(state: &ApiState, query_string: &str) -> ApiResult {
+    let mut query: Option<Query> = None;
+    let mut limit = DEFAULT_HISTORY_LIMIT;
+
+    for (key, value) in url::form_urlencoded::parse(query_string.as_bytes()) {
+        match key.as_ref() {
+            "query" => {
+                let parsed = Query::from_str(&value).map_err(|e| ApiError::bad_request(e.explain(&value)))?;
+                query = Some(parsed);
+            },
+            "limit" => {
+                limit = value.parse().map_err(|_| ApiError::bad_request(format!("Invalid limit '{}'", value)))?;
+            },
+            _ => return Err(ApiError::bad_request(format!("Unknown parameter '{}', possible: query, limit", key)))
+        }
+    }
+
+    let matcher = ScopeMatcher::from_config(state.scope.lock().unwrap().as_ref());
+    let in_scope = |uri: &str| matcher.fits(uri);
+    let mut pairs = read_pairs(state, |pair| {
+        query.as_ref().is_none_or(|query| query.matches(pair, &in_scope))
+    })?;
+
+    // The latest pairs are returned
+    let skip = pairs.len().saturating_sub(limit);
+    let pairs: Vec<SerializableProxyData> = pairs.drain(skip ..).collect();
+
+    return Ok(json_response(StatusCode::OK, &pairs));
+}
+
+fn pair_by_id(state: &ApiState, id: &str) -> ApiResult {
+    let id: usize = id.parse().map_err(|_| ApiError::bad_request(format!("Invalid ID '{}'", id)))?;
+    let mut pairs = read_pairs(state, |pair| pair.index == id)?;
+
+    return match pairs.pop() {
+        Some(pair) => Ok(json_response(StatusCode::OK, &pair)),
+        None => Err(ApiError::not_found(format!("No pair with ID {}", id)))
+    };
+}
+
+fn get_scope(state: &ApiState) -> ApiResult {
+    let scope = state.scope.lock().unwrap().clone().unwrap_or_default();
+    return Ok(json_response(StatusCode::OK, &scope));
+}
+
+/// Scope is replaced, saved in project as TUI does and applied to traffic coming next
+async fn put_scope(state: &ApiState, req: Request<Body>) -> ApiResult {
+    let body = hyper::body::to_bytes(req.into_body()).await.map_err(ApiError::bad_request)?;
+    let scope: Scope = json::from_slice(&body).map_err(|e| ApiError::bad_request(format!("Invalid scope: {}", e)))?;
+    ScopeMatcher::try_from(&scope).map_err(ApiError::bad_request)?;
+
+    if let Some(project) = state.project.as_ref() {
+        project::scope::store(project, &scope).map_err(ApiError::internal)?;
+    }
+
+    *state.scope.lock().unwrap() = Some(scope.clone());
+    state.commands.send(ApiCommand::SetScope(scope.clone())).map_err(ApiError::internal)?;
+
+    return Ok(json_response(StatusCode::OK, &scope));
+}
+
+#[derive(Serialize)]
+struct RepeaterInfo {
+    number: usize,
+    name: String,
+    address: String,
+    https: bool,
+    request: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    redirects_exceeded: bool,
+}
+
+fn repeaters_path(state: &ApiState) -> Result<String, ApiError> {
+    let repeaters_path = project_file(state, "repeater.jsonl")?;
+    if !path::Path::new(&repeaters_path).is_file() {
+        return Err(ApiError::not_found("No repeaters are saved in project"));
+    }
+
+    return Ok(repeaters_path);
+}
+
+fn list_repeaters(state: &ApiState) -> ApiResult {
+    let repeaters: Vec<RepeaterInfo> = RepeaterIterator::new(&repeaters_path(state)?)
+        .map_err(ApiError::internal)?
+        .enumerate()
+        .map(|(i, repeater)| {
+            let repeater = repeater.map_err(ApiError::internal)?;
+            let response = repeater.response.get_content().source().to_string();
+            Ok(RepeaterInfo {
+                number: i + 1,
+                name: repeater.name,
+                address: repeater.parameters.address,
+                https: repeater.parameters.https,
+                request: repeater.request,
+                response: if response.is_empty() { None } else { Some(response) },
+                redirects_exceeded: false,
+            })
+        })
+        .collect::<Result<Vec<RepeaterInfo>, ApiError>>()?;
+
+    return Ok(json_response(StatusCode::OK, &repeaters));
+}
+
+async fn send_repeater(state: &ApiState, mark: &str) -> ApiResult {
+    let repeaters_path = repeaters_path(state)?;
+    let (number, repeater, redirects_exceeded) = send_saved(&repeaters_path, mark, &state.sessions)
+        .await
+        .map_err(|e| {
+            let message: String = e.into();
+            ApiError::new(StatusCode::BAD_GATEWAY, message)
+        })?;
+
+    let info = RepeaterInfo {
+        number,
+        name: repeater.name,
+        address: repeater.parameters.address,
+        https: repeater.parameters.https,
+        request: repeater.request,
+        response: Some(repeater.response.get_content().source().to_string()),
+        redirects_exceeded,
+    };
+
+    return Ok(json_response(StatusCode::OK, &info));
+}
+
+/// Events are streamed as NDJSON, the same objects as `cruster dump --format json` prints
+fn subscribe(state: &ApiState) -> ApiResult {
+    let mut events = state.events.subscribe();
+    let (mut sender, body) = Body::channel();
+
+    tokio::task::spawn(
+        async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        if sender.send_data(event.into()).await.is_err() {
+                            break;
+                        }
+                    },
+                    // Slow subscriber misses some events, but stays subscribed
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break
+                }
+            }
+        }
+    );
+
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/x-ndjson")
+        .body(body)
+        .map_err(ApiError::internal)?;
+
+    return Ok(response);
+}
@@ -1,9 +1,12 @@
 mod http;
-mod repeater;
+pub(crate) mod repeater;
+mod project;
+mod scope;
 
 use clap::{self, ArgMatches};
 
 use crate::config;
+use crate::session::Sessions;
 use std::process::exit;
 
 #[derive(Debug)]
@@ -44,8 +47,9 @@ pub(crate) async fn launch(command: ArgMatches, config: config::Config) -> Resul
                     let str_range = args.get_one::<String>("INDEX").unwrap();
                     let range = http::show::parse_range(str_range)?;
                     let settings = http::show::parse_settings(args)?;
+                    let in_scope = crate::scope::make_checker(config.scope.as_ref());
 
-                    if let Err(err) = http::show::execute(range, &http_data_path, settings) {
+                    if let Err(err) = http::show::execute(range, &http_data_path, settings, &in_scope) {
                         let err_msg: String = err.into();
                         eprintln!("Error occured while http::show executed: {}", err_msg);
                         exit(1);
@@ -53,11 +57,45 @@ pub(crate) async fn launch(command: ArgMatches, config: config::Config) -> Resul
                 },
                 Some(("follow", args)) => {
                     let settings = http::follow::HttpFollowSettings::try_from(args)?;
-                    if let Err(err) = http::follow::exec(&settings, &http_data_path) {
+                    let in_scope = crate::scope::make_checker(config.scope.as_ref());
+                    if let Err(err) = http::follow::exec(&settings, &http_data_path, &in_scope) {
                         let err_msg: String = err.into();
                         eprintln!("Error occured while http::follow executed: {}", err_msg);
                         exit(8);
                     }
+                },
+                Some(("export-code", args)) => {
+                    let settings = http::export_code::ExportCodeSettings::try_from(args)?;
+                    if let Err(err) = http::export_code::execute(&settings, &http_data_path) {
+                        let err_msg: String = err.into();
+                        eprintln!("Error occured while http::export_code executed: {}", err_msg);
+                        exit(9);
+                    }
+                },
+                Some(("sitemap", args)) => {
+                    let settings = http::sitemap::SiteMapSettings::try_from(args)?;
+                    if let Err(err) = http::sitemap::execute(&settings, &http_data_path) {
+                        let err_msg: String = err.into();
+                        eprintln!("Error occured while http::sitemap executed: {}", err_msg);
+                        exit(10);
+                    }
+                },
+                Some(("params", args)) => {
+                    let settings = http::params::ParamsSettings::try_from(args)?;
+                    if let Err(err) = http::params::execute(&settings, &http_data_path) {
+                        let err_msg: String = err.into();
+                        eprintln!("Error occured while http::params executed: {}", err_msg);
+                        exit(11);
+                    }
+                },
+                Some(("diff", args)) => {
+                    let settings = http::diff::DiffSettings::try_from(args)?;
+                    let repeater_state_path = format!("{}/repeater.jsonl", &project);
+                    if let Err(err) = http::diff::execute(&settings, &http_data_path, &repeater_state_path) {
+                        let err_msg: String = err.into();
+                        eprintln!("Error occured while http::diff executed: {}", err_msg);
+                        exit(12);
+                    }
                 }
                 _ => {}
             }
@@ -89,7 +127,8 @@ pub(crate) async fn launch(command: ArgMatches, config: config::Config) -> Resul
 
                     let settings = repeater::exec::RepeaterExecSettings::try_from(args)?;
                     let editor = config.editor.as_ref().unwrap();
-                    if let Err(err) = repeater::exec::execute(&settings, &repeater_state_path, editor).await {
+                    let sessions = Sessions::from(&config);
+                    if let Err(err) = repeater::exec::execute(&settings, &repeater_state_path, editor, &sessions).await {
                         let err_str: String = err.into();
                         eprintln!("Error occured while repeater::exec executed: {}", err_str);
                         exit(5);
@@ -122,3 +161,114 @@ pub(crate) async fn launch(command: ArgMatches, config: config::Config) -> Resul
 
     Ok(())
 }
+
+pub(crate) fn launch_project(command: ArgMatches, config: config::Config) -> Result<(), CrusterCLIError> {
+    let project = match config.project.as_ref() {
+        Some(path) => {
+            path.to_string()
+        },
+        None => {
+            return Err(
+                CrusterCLIError::from("Project must be specified with '-P' option or in config")
+            )
+        }
+    };
+
+    match command.subcommand() {
+        Some(("init", args)) => {
+            let settings = project::init::ProjectInitSettings::try_from(args)?;
+            if let Err(err) = project::init::execute(&settings, &project) {
+                let err_str: String = err.into();
+                eprintln!("Error occured while project::init executed: {}", err_str);
+                exit(13);
+            }
+        },
+        Some(("info", args)) => {
+            let settings = project::info::ProjectInfoSettings::try_from(args)?;
+            if let Err(err) = project::info::execute(&settings, &project) {
+                let err_str: String = err.into();
+                eprintln!("Error occured while project::info executed: {}", err_str);
+                exit(14);
+            }
+        },
+        Some(("merge", args)) => {
+            let settings = project::merge::ProjectMergeSettings::try_from(args)?;
+            if let Err(err) = project::merge::execute(&settings, &project) {
+                let err_str: String = err.into();
+                eprintln!("Error occured while project::merge executed: {}", err_str);
+                exit(15);
+            }
+        },
+        Some(("prune", args)) => {
+            let settings = project::prune::ProjectPruneSettings::try_from(args)?;
+            if let Err(err) = project::prune::execute(settings, &project, &config) {
+                let err_str: String = err.into();
+                eprintln!("Error occured while project::prune executed: {}", err_str);
+                exit(16);
+            }
+        },
+        Some(("compact", args)) => {
+            let settings = project::compact::ProjectCompactSettings::try_from(args)?;
+            if let Err(err) = project::compact::execute(&settings, &project) {
+                let err_str: String = err.into();
+                eprintln!("Error occured while project::compact executed: {}", err_str);
+                exit(17);
+            }
+        },
+        Some(("encrypt", _)) => {
+            if let Err(err) = project::crypt::encrypt(&project) {
+                let err_str: String = err.into();
+                eprintln!("Error occured while project::encrypt executed: {}", err_str);
+                exit(18);
+            }
+        },
+        Some(("decrypt", _)) => {
+            if let Err(err) = project::crypt::decrypt(&project) {
+                let err_str: String = err.into();
+                eprintln!("Error occured while project::decrypt executed: {}", err_str);
+                exit(19);
+            }
+        },
+        Some(("compress", _)) => {
+            if let Err(err) = project::compress::compress(&project) {
+                let err_str: String = err.into();
+                eprintln!("Error occured while project::compress executed: {}", err_str);
+                exit(20);
+            }
+        },
+        Some(("decompress", _)) => {
+            if let Err(err) = project::compress::decompress(&project) {
+                let err_str: String = err.into();
+                eprintln!("Error occured while project::decompress executed: {}", err_str);
+                exit(21);
+            }
+        },
+        Some(("view", args)) => {
+            let settings = project::view::ProjectViewSettings::try_from(args)?;
+            if let Err(err) = project::view::execute(&settings, &project) {
+                let err_str: String = err.into();
+                eprintln!("Error occured while project::view executed: {}", err_str);
+                exit(22);
+            }
+        },
+        _ => unreachable!()
+    }
+
+    Ok(())
+}
+
+pub(crate) fn launch_scope(command: ArgMatches, config: config::Config) -> Result<(), CrusterCLIError> {
+    match command.subcommand() {
+        Some(("test", args)) => {
+            let url = args.get_one::<String>("URL").unwrap();
+            if let Err(err) = scope::test(url, config.scope.as_ref()) {
+                let err_str: String = err.into();
+                eprintln!("Error occured while scope::test executed: {}", err_str);
+                exit(23);
+            }
+        },
+        _ => unreachable!()
+    }
+
+    Ok(())
+}
@@ -3,6 +3,10 @@ use std::cmp::min;
 
 pub(super) mod show;
 pub(super) mod follow;
+pub(super) mod export_code;
+pub(super) mod sitemap;
+pub(super) mod params;
+pub(super) mod diff;
 
 pub(crate) fn print_briefly(pair: &http_storage::RequestResponsePair, with_header: bool) {
     let idx = pair.index;
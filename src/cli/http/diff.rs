@@ -0,0 +1,208 @@
+use clap::ArgMatches;
+use colored::Colorize;
+use serde_json as json;
+
+use crate::http_storage;
+use crate::project::files::RecordReader;
+use crate::cli::CrusterCLIError;
+use crate::cli::repeater::RepeaterIterator;
+use crate::analysis::diff::{self, DiffMode, DiffSubject, DiffTag, Comparison};
+
+// Equal lines around changes which are printed in lines mode, the rest are folded
+const CONTEXT_LINES: usize = 3;
+
+pub(crate) enum DiffOperand {
+    Pair(usize),
+    Repeater(usize),
+}
+
+impl TryFrom<&str> for DiffOperand {
+    type Error = CrusterCLIError;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        if let Some(number) = value.strip_prefix("r:") {
+            return Ok(DiffOperand::Repeater(number.parse()?));
+        }
+
+        return Ok(DiffOperand::Pair(value.parse()?));
+    }
+}
+
+pub(crate) struct DiffSettings {
+    pub(crate) left: DiffOperand,
+    pub(crate) right: DiffOperand,
+    pub(crate) requests: bool,
+    pub(crate) mode: DiffMode,
+    pub(crate) color: bool,
+}
+
+impl TryFrom<&ArgMatches> for DiffSettings {
+    type Error = CrusterCLIError;
+    fn try_from(args: &ArgMatches) -> Result<Self, Self::Error> {
+        let left = DiffOperand::try_from(args.get_one::<String>("LEFT").unwrap().as_str())?;
+        let right = DiffOperand::try_from(args.get_one::<String>("RIGHT").unwrap().as_str())?;
+        let mode = if args.get_flag("words") { DiffMode::Words } else { DiffMode::Lines };
+
+        let settings = DiffSettings {
+            left,
+            right,
+            requests: args.get_flag("requests"),
+            mode,
+            color: !args.get_flag("no-color"),
+        };
+
+        return Ok(settings);
+    }
+}
+
+fn find_pair(http_path: &str, index: usize) -> Result<http_storage::RequestResponsePair, CrusterCLIError> {
+    for raw_line in RecordReader::open(http_path)? {
+        let line = &raw_line?;
+
+        let pair_ser: http_storage::serializable::SerializableProxyData = json::from_str(line)?;
+        let pair: http_storage::RequestResponsePair = pair_ser.try_into()?;
+        if pair.index == index {
+            return Ok(pair);
+        }
+    }
+
+    Err(
+        CrusterCLIError::from(format!("Could not find record with index {}", index))
+    )
+}
+
+fn load_subject(operand: &DiffOperand, requests: bool, http_path: &str, repeater_path: &str) -> Result<DiffSubject, CrusterCLIError> {
+    match operand {
+        DiffOperand::Pair(index) => {
+            let pair = find_pair(http_path, *index)?;
+            let subject = if requests {
+                pair.request.as_ref().map(DiffSubject::from)
+            }
+            else {
+                pair.response.as_ref().map(DiffSubject::from)
+            };
+
+            subject.ok_or_else(|| CrusterCLIError::from(format!("Record with index {} has nothing to compare", index)))
+        },
+        DiffOperand::Repeater(number) => {
+            if !std::path::Path::new(repeater_path).is_file() {
+                return Err(CrusterCLIError::from("There are no saved repeaters in project"));
+            }
+
+            let repeater = match RepeaterIterator::new(repeater_path)?.nth(*number).transpose()? {
+                Some(repeater) => repeater,
+                None => return Err(CrusterCLIError::from(format!("Could not find repeater #{}", number)))
+            };
+
+            if requests {
+                return Ok(DiffSubject::from_text(&repeater.request));
+            }
+
+            let response = repeater.response.get_content();
+            if response.source().is_empty() {
+                return Err(CrusterCLIError::from(format!("Repeater #{} has no response yet", number)));
+            }
+
+            Ok(DiffSubject::from_text(response.source()))
+        }
+    }
+}
+
+fn print_line(tag: DiffTag, line: &str, color: bool) {
+    let line = line.trim_end_matches(['\r', '\n']);
+    match (tag, color) {
+        (DiffTag::Equal, _) => println!(" {}", line),
+        (DiffTag::Removed, true) => println!("{}", format!("-{}", line).red()),
+        (DiffTag::Added, true) => println!("{}", format!("+{}", line).green()),
+        (DiffTag::Removed, false) => println!("-{}", line),
+        (DiffTag::Added, false) => println!("+{}", line),
+    }
+}
+
+fn is_near_change(chunks: &[(DiffTag, String)], i: usize) -> bool {
+    return chunks
+        .iter()
+        .skip(i.saturating_sub(CONTEXT_LINES))
+        .take(CONTEXT_LINES * 2 + 1)
+        .any(|(tag, _)| *tag != DiffTag::Equal);
+}
+
+fn print_lines(chunks: &[(DiffTag, String)], color: bool) {
+    let mut i: usize = 0;
+    while i < chunks.len() {
+        let (tag, line) = &chunks[i];
+        if is_near_change(chunks, i) {
+            print_line(*tag, line, color);
+            i += 1;
+            continue;
+        }
+
+        let folded = chunks[i..]
+            .iter()
+            .enumerate()
+            .take_while(|(j, _)| !is_near_change(chunks, i + j))
+            .count();
+
+        let message = format!("... {} equal lines ...", folded);
+        if color {
+            println!("{}", message.dimmed());
+        }
+        else {
+            println!("{}", message);
+        }
+
+        i += folded;
+    }
+}
+
+fn print_words(chunks: &[(DiffTag, String)], color: bool) {
+    let mut text = String::default();
+    for (tag, word) in chunks {
+        let word = match (tag, color) {
+            (DiffTag::Equal, _) => word.clone(),
+            (DiffTag::Removed, true) => word.red().strikethrough().to_string(),
+            (DiffTag::Added, true) => word.green().to_string(),
+            (DiffTag::Removed, false) => format!("[-{}-]", word),
+            (DiffTag::Added, false) => format!("{{+{}+}}", word),
+        };
+
+        text.push_str(&word);
+    }
+
+    println!("{}", text.trim_end_matches(['\r', '\n']));
+}
+
+fn print_comparison(comparison: &Comparison, mode: DiffMode, color: bool) {
+    for section in comparison.sections.iter() {
+        let title = format!("@@ {} @@", section.name);
+        if color {
+            println!("{}", title.cyan());
+        }
+        else {
+            println!("{}", title);
+        }
+
+        // Unchanged first line is still printed to show what is compared
+        if !section.has_changes() && section.name != "first line" {
+            println!("<EQUAL>");
+            continue;
+        }
+
+        match mode {
+            DiffMode::Lines => print_lines(&section.chunks, color),
+            DiffMode::Words => print_words(&section.chunks, color),
+        }
+    }
+
+    println!();
+    println!("{}", comparison.stats.summary());
+}
+
+pub(crate) fn execute(settings: &DiffSettings, http_path: &str, repeater_path: &str) -> Result<(), CrusterCLIError> {
+    let left = load_subject(&settings.left, settings.requests, http_path, repeater_path)?;
+    let right = load_subject(&settings.right, settings.requests, http_path, repeater_path)?;
+
+    let comparison = diff::compare(&left, &right, settings.mode);
+    print_comparison(&comparison, settings.mode, settings.color);
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use clap::ArgMatches;
+use serde_json as json;
+use std::io::Write;
+
+use crate::http_storage;
+use crate::project::files::RecordReader;
+use crate::cli::CrusterCLIError;
+use crate::export_code::{self, CodeLang};
+
+pub(crate) struct ExportCodeSettings {
+    pub(crate) index: usize,
+    pub(crate) lang: CodeLang,
+}
+
+impl TryFrom<&ArgMatches> for ExportCodeSettings {
+    type Error = CrusterCLIError;
+    fn try_from(args: &ArgMatches) -> Result<Self, Self::Error> {
+        let index: usize = args.get_one::<String>("index").unwrap().parse()?;
+        let lang = match args.get_one::<String>("lang") {
+            Some(lang) => CodeLang::try_from(lang.as_str())?,
+            None => CodeLang::Curl
+        };
+
+        return Ok(
+            ExportCodeSettings {
+                index,
+                lang
+            }
+        );
+    }
+}
+
+pub(crate) fn execute(settings: &ExportCodeSettings, http_path: &str) -> Result<(), CrusterCLIError> {
+    for raw_line in RecordReader::open(http_path)? {
+        let line = &raw_line?;
+
+        let pair_ser: http_storage::serializable::SerializableProxyData = json::from_str(line)?;
+        let pair: http_storage::RequestResponsePair = pair_ser.try_into()?;
+
+        if pair.index != settings.index {
+            continue;
+        }
+
+        let request = match pair.request.as_ref() {
+            Some(request) => request,
+            None => {
+                return Err(CrusterCLIError::from("Cannot export record with empty request"));
+            }
+        };
+
+        let code = export_code::export_request(request, settings.lang);
+        let mut stdout = std::io::stdout();
+        stdout.write_all(&code)?;
+        stdout.flush()?;
+
+        return Ok(());
+    }
+
+    Err(
+        CrusterCLIError::from(format!("Could not find record with index {}", settings.index))
+    )
+}
@@ -1,15 +1,19 @@
 use crate::cli::CrusterCLIError;
 use clap::ArgMatches;
-use std::io::{BufRead, BufReader};
+use std::collections::HashSet;
 use std::thread::sleep;
 use std::time::Duration;
 use serde_json as json;
 use crate::http_storage;
+use crate::project::files;
+use crate::query::Query;
+use std::str::FromStr;
 
 
 pub(crate) struct HttpFollowSettings {
     pub(crate) no_old_lines: bool,
     pub(crate) urls: bool,
+    pub(crate) query: Option<Query>,
 }
 
 impl TryFrom<&ArgMatches> for HttpFollowSettings {
@@ -17,52 +21,55 @@ impl TryFrom<&ArgMatches> for HttpFollowSettings {
     fn try_from(args: &ArgMatches) -> Result<Self, Self::Error> {
         let mut settings = HttpFollowSettings {
             no_old_lines: false,
-            urls: false
+            urls: false,
+            query: None
         };
 
         settings.no_old_lines = args.get_flag("no-old-lines");
         settings.urls = args.get_flag("urls");
+        if let Some(query) = args.get_one::<String>("query") {
+            settings.query = Some(Query::from_str(query).map_err(|e| CrusterCLIError::from(e.explain(query)))?);
+        }
 
         return Ok(settings);
     }
 }
 
-pub(crate) fn exec(settings: &HttpFollowSettings, path: &str) -> Result<(), CrusterCLIError> {
-    let fin = std::fs::File::open(path)?;
-    let mut reader = BufReader::new(fin);
+/// `in_scope` checks URIs for 'in scope' conditions of query
+pub(crate) fn exec(settings: &HttpFollowSettings, path: &str, in_scope: &dyn Fn(&str) -> bool) -> Result<(), CrusterCLIError> {
+    let mut reader = files::RecordReader::open(path)?;
+
+    // Records seen before the file was rewritten (e.g. compacted) are not printed again. IDs are tracked one by one,
+    // because pairs are appended when they get response, so IDs in file are not ordered
+    let mut seen: HashSet<usize> = HashSet::new();
+    let mut old_lines = true;
 
-    let mut buf = String::with_capacity(1000000);
-    while reader.read_line(&mut buf)? > 0 {
-        if ! settings.no_old_lines {
-            let line_ptr = &buf;
-            let serializable_data: http_storage::serializable::SerializableProxyData = json::from_str(line_ptr)?;
+    loop {
+        if let Some(record) = reader.next_complete()? {
+            let serializable_data: http_storage::serializable::SerializableProxyData = json::from_str(&record)?;
             let pair: http_storage::RequestResponsePair = serializable_data.try_into()?;
+            let is_new = seen.insert(pair.index);
 
-            if settings.urls {
-                super::print_urls(&pair);
-            } else {
-                super::print_briefly(&pair, false);
-            }
-        }
+            let is_matched = settings.query.as_ref().is_none_or(|query| query.matches(&pair, in_scope));
 
-        buf.clear();
-    }
+            if is_new && is_matched && !(old_lines && settings.no_old_lines) {
+                if settings.urls {
+                    super::print_urls(&pair);
+                } else {
+                    super::print_briefly(&pair, false);
+                }
+            }
 
-    loop {
-        while reader.read_line(&mut buf)? == 0 {
-            sleep(Duration::from_millis(100));
+            continue;
         }
 
-        let line_ptr = &buf;
-        let serializable_data: http_storage::serializable::SerializableProxyData = json::from_str(line_ptr)?;
-        let pair: http_storage::RequestResponsePair = serializable_data.try_into()?;
-        
-        if settings.urls {
-            super::print_urls(&pair);
-        } else {
-            super::print_briefly(&pair, false);
+        // Record which is not written completely is being written right now, its rest will be read later
+        old_lines = false;
+        if reader.is_replaced() {
+            reader = files::RecordReader::open(path)?;
+            continue;
         }
-        
-        buf.clear();
+
+        sleep(Duration::from_millis(100));
     }
 }
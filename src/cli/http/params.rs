@@ -0,0 +1,40 @@
+use clap::ArgMatches;
+use serde_json as json;
+
+use crate::cli::CrusterCLIError;
+use crate::http_storage::HTTPStorage;
+use crate::analysis::params::ParamsInventory;
+
+pub(crate) struct ParamsSettings {
+    pub(crate) json: bool,
+}
+
+impl TryFrom<&ArgMatches> for ParamsSettings {
+    type Error = CrusterCLIError;
+    fn try_from(args: &ArgMatches) -> Result<Self, Self::Error> {
+        let settings = ParamsSettings {
+            json: args.get_flag("json")
+        };
+
+        return Ok(settings);
+    }
+}
+
+pub(crate) fn execute(settings: &ParamsSettings, http_path: &str) -> Result<(), CrusterCLIError> {
+    let mut storage = HTTPStorage::default();
+    if let Err(err) = storage.load(http_path) {
+        return Err(CrusterCLIError::from(err));
+    }
+
+    let inventory = ParamsInventory::from_pairs(&storage);
+    if settings.json {
+        println!("{}", json::to_string_pretty(&inventory)?);
+    }
+    else {
+        for line in inventory.to_table_lines() {
+            println!("{}", line);
+        }
+    }
+
+    Ok(())
+}
@@ -1,9 +1,13 @@
 use crate::http_storage;
 use crate::cli::CrusterCLIError;
+use crate::project::{self, files::RecordReader, views::ProjectViews};
+use crate::query::Query;
 
 use serde_json as json;
-use std::io::BufRead;
 use regex::Regex;
+use colored::Colorize;
+use std::io::IsTerminal;
+use std::str::FromStr;
 
 use regex;
 use clap::ArgMatches;
@@ -74,6 +78,9 @@ pub(crate) struct ShowSettings {
     pub(super) pretty: bool,
     pub(super) raw: bool,
     pub(super) filter: Option<String>,
+    pub(super) query: Option<Query>,
+    // Name of view saved in project, its query is used as '-q'
+    pub(super) view: Option<String>,
     pub(super) attribute: Option<ExtractionAttribute>,
     pub(super) index: Option<usize>
 }
@@ -85,6 +92,8 @@ impl Default for ShowSettings {
             pretty: false,
             raw: false,
             filter: None,
+            query: None,
+            view: None,
             attribute: None,
             index: None
         }
@@ -101,6 +110,11 @@ pub(crate) fn parse_settings(args: &ArgMatches) -> Result<ShowSettings, CrusterC
         Some(filter) => Some(filter.clone()),
         None => None
     };
+    settings.query = match args.get_one::<String>("query") {
+        Some(query) => Some(Query::from_str(query).map_err(|e| CrusterCLIError::from(e.explain(query)))?),
+        None => None
+    };
+    settings.view = args.get_one::<String>("view").cloned();
     settings.attribute = match args.get_one::<String>("extract") {
         Some(attribute) => Some(ExtractionAttribute::try_from(attribute.as_str())?),
         None => None
@@ -128,6 +142,18 @@ pub(crate) fn parse_settings(args: &ArgMatches) -> Result<ShowSettings, CrusterC
         )
     }
 
+    if settings.query.is_some() && settings.view.is_some() {
+        return Err(
+            CrusterCLIError::from("Parameters '-q' and '--view' cannot be used at the same time")
+        )
+    }
+
+    if (settings.query.is_some() || settings.view.is_some()) && settings.raw {
+        return Err(
+            CrusterCLIError::from("Query ('-q' or '--view') cannot be used with raw printing ('-r')")
+        )
+    }
+
     if settings.attribute.is_some() && settings.raw {
         return Err(
             CrusterCLIError::from("Extraction by attribute ('-e') cannot be used with raw printing ('-r')")
@@ -188,64 +214,58 @@ pub(crate) fn parse_range(str_range: &str) -> Result<HTTPTableRange, CrusterCLIE
     );
 }
 
-fn print_pretty(pair: &http_storage::RequestResponsePair) {
+// Matches of filter are highlighted, if output is terminal
+fn highlight(text: String, filter: Option<&Regex>) -> String {
+    let re = match filter {
+        Some(re) if std::io::stdout().is_terminal() => re,
+        _ => return text
+    };
+
+    return re
+        .replace_all(&text, |caps: &regex::Captures| caps[0].black().on_yellow().to_string())
+        .into_owned();
+}
+
+fn print_pretty(pair: &http_storage::RequestResponsePair, filter: Option<&Regex>) {
     println!("----------------------");
     println!("   {}", pair.index);
     println!("----------------------\n");
 
     match (pair.request.as_ref(), pair.response.as_ref()) {
         (Some(request), Some(response)) => {
-            println!("{}", request.to_string());
+            println!("{}", highlight(request.to_string(), filter));
             println!();
-            println!("{}", response.to_string());
+            println!("{}", highlight(response.to_string(), filter));
             println!();
         },
         (Some(request), None) => {
-            println!("{}", request.to_string());
+            println!("{}", highlight(request.to_string(), filter));
             println!();
             println!("\n<EMPTY RESPONSE>\n");
         },
         (None, Some(response)) => {
             println!("<EMPTY REQUEST>\n");
-            println!("{}", response.to_string());
+            println!("{}", highlight(response.to_string(), filter));
             println!();
         }
         _ => unreachable!()
     }
 }
 
-fn print_pair(pair: &http_storage::RequestResponsePair, settings: &ShowSettings, header_if_any: bool) {
+fn print_pair(pair: &http_storage::RequestResponsePair, settings: &ShowSettings, filter: Option<&Regex>, header_if_any: bool) {
     if settings.print_urls {
         super::print_urls(pair);
     }
     else if settings.pretty {
-        print_pretty(pair);
+        print_pretty(pair, filter);
     }
     else {
         super::print_briefly(pair, header_if_any);
     }
 }
 
-fn matches_the_filter(pair: &http_storage::RequestResponsePair, re: &Regex) -> bool {
-    let request_matched = if let Some(request) = pair.request.as_ref() {
-        request.serach_with_re(re)
-    }
-    else {
-        false
-    };
-
-    if request_matched {
-        return true;
-    }
-
-    let response_matched = if let Some(response) = pair.response.as_ref() {
-        response.serach_with_re(re)
-    }
-    else {
-        false
-    };
-
-    return response_matched;
+pub(crate) fn matches_the_filter(pair: &http_storage::RequestResponsePair, re: &Regex) -> bool {
+    return pair.is_match(re);
 }
 
 fn has_appropriate_attribute(pair: &http_storage::RequestResponsePair, attribute: &ExtractionAttribute) -> bool {
@@ -291,7 +311,8 @@ fn has_appropriate_attribute(pair: &http_storage::RequestResponsePair, attribute
     }
 }
 
-pub(crate) fn execute(range: HTTPTableRange, http_storage: &str, settings: ShowSettings) -> Result<(), CrusterCLIError> {
+/// `in_scope` checks URIs for 'in scope' conditions of query
+pub(crate) fn execute(range: HTTPTableRange, http_storage: &str, mut settings: ShowSettings, in_scope: &dyn Fn(&str) -> bool) -> Result<(), CrusterCLIError> {
     if range.to < range.from {
         return Err(
             CrusterCLIError::from("Right bound of range cannot be lower than left one")
@@ -310,13 +331,28 @@ pub(crate) fn execute(range: HTTPTableRange, http_storage: &str, settings: ShowS
         None => None
     };
 
+    if let Some(name) = settings.view.as_ref() {
+        let views = ProjectViews::load(&project::project_of(http_storage))?;
+        let view = match views.get(name) {
+            Some(view) => view,
+            None => return Err(CrusterCLIError::from(format!("There is no view '{}' in project", name)))
+        };
+
+        let query = Query::from_str(&view.query)
+            .map_err(|e| CrusterCLIError::from(format!("View '{}' has invalid query. {}", name, e.explain(&view.query))))?;
+        settings.query = Some(query);
+    }
+
+    // What query looks for is highlighted as regex filter does
+    let highlight_re = filter_re
+        .clone()
+        .or_else(|| settings.query.as_ref().and_then(|query| query.highlight_regex()));
+
     let mut first: bool = true;
-    let fin = std::fs::File::open(http_storage)?;
-    let fin_reader = std::io::BufReader::new(fin);
 
     let mut count = left_idx.saturating_sub(1);
     let mut found = false;
-    for line in fin_reader.lines().skip(count) {
+    for line in RecordReader::open(http_storage)?.skip(count) {
         let line_ptr = &line?;
 
         if settings.raw {
@@ -363,10 +399,21 @@ pub(crate) fn execute(range: HTTPTableRange, http_storage: &str, settings: ShowS
                 }
             }
 
+            if let Some(query) = settings.query.as_ref() {
+                if ! query.matches(&pair, in_scope) {
+                    count += 1;
+                    if count == right_idx {
+                        break;
+                    }
+
+                    continue;
+                }
+            }
+
             if let Some(re) = filter_re.as_ref() {
                 if matches_the_filter(&pair, re) {
                     found = true;
-                    print_pair(&pair, &settings, first);
+                    print_pair(&pair, &settings, highlight_re.as_ref(), first);
                     if first {
                         first = false;
                     }        
@@ -374,7 +421,7 @@ pub(crate) fn execute(range: HTTPTableRange, http_storage: &str, settings: ShowS
             }
             else {
                 found = true;
-                print_pair(&pair, &settings, first);
+                print_pair(&pair, &settings, highlight_re.as_ref(), first);
                 if first {
                     first = false;
                 }
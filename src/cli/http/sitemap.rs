@@ -0,0 +1,40 @@
+use clap::ArgMatches;
+use serde_json as json;
+
+use crate::cli::CrusterCLIError;
+use crate::http_storage::HTTPStorage;
+use crate::analysis::sitemap::SiteMap;
+
+pub(crate) struct SiteMapSettings {
+    pub(crate) json: bool,
+}
+
+impl TryFrom<&ArgMatches> for SiteMapSettings {
+    type Error = CrusterCLIError;
+    fn try_from(args: &ArgMatches) -> Result<Self, Self::Error> {
+        let settings = SiteMapSettings {
+            json: args.get_flag("json")
+        };
+
+        return Ok(settings);
+    }
+}
+
+pub(crate) fn execute(settings: &SiteMapSettings, http_path: &str) -> Result<(), CrusterCLIError> {
+    let mut storage = HTTPStorage::default();
+    if let Err(err) = storage.load(http_path) {
+        return Err(CrusterCLIError::from(err));
+    }
+
+    let sitemap = SiteMap::from_pairs(&storage);
+    if settings.json {
+        println!("{}", json::to_string_pretty(&sitemap)?);
+    }
+    else {
+        for line in sitemap.to_tree_lines() {
+            println!("{}", line);
+        }
+    }
+
+    Ok(())
+}
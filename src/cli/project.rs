@@ -0,0 +1,138 @@
+pub(crate) mod init;
+pub(crate) mod info;
+pub(crate) mod merge;
+pub(crate) mod prune;
+pub(crate) mod compact;
+pub(crate) mod crypt;
+pub(crate) mod compress;
+pub(crate) mod view;
+
+use std::fs;
+use std::path;
+use serde_json as json;
+
+use super::CrusterCLIError;
+use crate::project::ProjectMeta;
+use crate::project::files::{self, FileLock, RecordCodec, RecordReader};
+use crate::http_storage::{RequestResponsePair, serializable::SerializableProxyData};
+
+/// Reads all pairs from file with HTTP data, missing file means there are no pairs
+pub(crate) fn read_pairs(http_path: &str) -> Result<Vec<RequestResponsePair>, CrusterCLIError> {
+    if !path::Path::new(http_path).is_file() {
+        return Ok(Vec::default());
+    }
+
+    let mut pairs: Vec<RequestResponsePair> = Vec::with_capacity(1000);
+    for raw_line in RecordReader::open(http_path)? {
+        let line = raw_line?;
+        let pair_ser: SerializableProxyData = json::from_str(&line)?;
+        let pair: RequestResponsePair = pair_ser.try_into()?;
+        pairs.push(pair);
+    }
+
+    return Ok(pairs);
+}
+
+/// Rewrites file with HTTP data, so it contains only provided pairs
+pub(crate) fn write_pairs(lock: &FileLock, http_path: &str, pairs: &[RequestResponsePair]) -> Result<(), CrusterCLIError> {
+    files::rewrite(lock, http_path, |fout| {
+        for pair in pairs {
+            let serializable_record = SerializableProxyData::try_from(pair)?;
+            let jsn = json::to_string(&serializable_record)?;
+            fout.write_all(jsn.as_bytes())?;
+            fout.write_all(b"\n")?;
+        }
+
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+pub(crate) fn load_meta(project: &str) -> Result<ProjectMeta, CrusterCLIError> {
+    return match ProjectMeta::load(project)? {
+        Some(meta) => Ok(meta),
+        None => {
+            Err(
+                CrusterCLIError::from(format!("Project at '{}' is not initialized, run 'cruster project init' first", project))
+            )
+        }
+    };
+}
+
+/// All JSON Lines files of project, i.e. HTTP data, repeaters and whatever will be stored later
+pub(crate) fn project_files(project: &str) -> Result<Vec<String>, CrusterCLIError> {
+    let mut paths: Vec<String> = Vec::default();
+    for entry in fs::read_dir(project)? {
+        let entry_path = entry?.path();
+        if entry_path.is_file() && entry_path.extension().is_some_and(|ext| ext == "jsonl") {
+            paths.push(entry_path.to_string_lossy().to_string());
+        }
+    }
+
+    paths.sort();
+    return Ok(paths);
+}
+
+/// Rewrites file, so all its records read with `reader` are encoded with `codec`, returns number of records
+pub(crate) fn recode_file(path: &str, reader: RecordCodec, codec: RecordCodec) -> Result<usize, CrusterCLIError> {
+    let lock = FileLock::exclusive(path)?;
+    let mut count: usize = 0;
+    files::rewrite_with(&lock, path, codec, |fout| {
+        for record in RecordReader::open_with(path, reader)? {
+            fout.write_all(record?.as_bytes())?;
+            fout.write_all(b"\n")?;
+            count += 1;
+        }
+
+        Ok(())
+    })?;
+
+    return Ok(count);
+}
+
+/// Size of records as they are, i.e. without compression and encryption
+pub(crate) fn records_size(path: &str) -> Result<u64, CrusterCLIError> {
+    if !path::Path::new(path).is_file() {
+        return Ok(0);
+    }
+
+    let mut size: u64 = 0;
+    for record in RecordReader::open(path)? {
+        size += record?.len() as u64 + 1;
+    }
+
+    return Ok(size);
+}
+
+/// Bytes taken by base64 of encoded records of file, beyond their payload
+pub(crate) fn base64_overhead(path: &str) -> Result<u64, CrusterCLIError> {
+    if !path::Path::new(path).is_file() {
+        return Ok(0);
+    }
+
+    return Ok(RecordReader::open(path)?.base64_overhead()?);
+}
+
+pub(crate) fn file_size(file_path: &str) -> u64 {
+    return fs::metadata(file_path)
+        .map(|meta| meta.len())
+        .unwrap_or_default();
+}
+
+pub(crate) fn format_size(size: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    return if unit == 0 {
+        format!("{} {}", size, units[0])
+    }
+    else {
+        format!("{:.1} {}", value, units[unit])
+    };
+}
@@ -0,0 +1,80 @@
+use clap::ArgMatches;
+use serde_json as json;
+use std::collections::HashSet;
+
+use super::{read_pairs, write_pairs, file_size, format_size};
+use crate::cli::CrusterCLIError;
+use crate::project::files::FileLock;
+use crate::http_storage::{RequestResponsePair, serializable::SerializableProxyData};
+
+pub(crate) struct ProjectCompactSettings {
+    pub(crate) renumber: bool,
+}
+
+impl TryFrom<&ArgMatches> for ProjectCompactSettings {
+    type Error = CrusterCLIError;
+    fn try_from(args: &ArgMatches) -> Result<Self, Self::Error> {
+        let settings = ProjectCompactSettings {
+            renumber: args.get_flag("renumber")
+        };
+
+        return Ok(settings);
+    }
+}
+
+/// Content of pair without its ID and capture time, so equal requests with equal responses have equal keys
+fn dedup_key(pair: &RequestResponsePair) -> Result<String, CrusterCLIError> {
+    let mut value = json::to_value(SerializableProxyData::try_from(pair)?)?;
+    if let Some(object) = value.as_object_mut() {
+        object.remove("index");
+        object.remove("timestamp");
+    }
+
+    return Ok(value.to_string());
+}
+
+pub(crate) fn execute(settings: &ProjectCompactSettings, project: &str) -> Result<(), CrusterCLIError> {
+    let http_path = format!("{}/http.jsonl", project);
+    let size_before = file_size(&http_path);
+
+    // Proxy could append pairs meanwhile, so they are locked out until the file is rewritten
+    let lock = FileLock::exclusive(&http_path)?;
+    let mut pairs = read_pairs(&http_path)?;
+    let total = pairs.len();
+    pairs.sort_by_key(|pair| pair.index);
+
+    let mut seen_ids: HashSet<usize> = HashSet::with_capacity(total);
+    let mut seen_content: HashSet<String> = HashSet::with_capacity(total);
+    let mut kept: Vec<RequestResponsePair> = Vec::with_capacity(total);
+    for pair in pairs {
+        // The first record wins, if the same ID was written several times
+        if !seen_ids.insert(pair.index) {
+            continue;
+        }
+
+        if !seen_content.insert(dedup_key(&pair)?) {
+            continue;
+        }
+
+        kept.push(pair);
+    }
+
+    if settings.renumber {
+        for (id, pair) in kept.iter_mut().enumerate() {
+            pair.index = id;
+        }
+    }
+
+    write_pairs(&lock, &http_path, &kept)?;
+    drop(lock);
+
+    println!(
+        "Removed {} duplicates of {} pairs, size: {} -> {}",
+        total - kept.len(),
+        total,
+        format_size(size_before),
+        format_size(file_size(&http_path))
+    );
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use super::{CrusterCLIError, load_meta, project_files, recode_file, file_size, format_size};
+use crate::project::files::{RecordCodec, COMPRESSION_ALGORITHM};
+
+fn recode_project(project: &str, verb: &str) -> Result<(), CrusterCLIError> {
+    for path in project_files(project)? {
+        let size_before = file_size(&path);
+        let count = recode_file(&path, RecordCodec::new(&path), RecordCodec::for_writing(&path)?)?;
+        println!(
+            "{} {} records of '{}', size: {} -> {}",
+            verb,
+            count,
+            &path,
+            format_size(size_before),
+            format_size(file_size(&path))
+        );
+    }
+
+    Ok(())
+}
+
+pub(crate) fn compress(project: &str) -> Result<(), CrusterCLIError> {
+    let mut meta = load_meta(project)?;
+    if meta.compression.is_some() {
+        return Err(CrusterCLIError::from("Project is compressed already"));
+    }
+
+    // Records are decoded by their own flags, so the order of metadata and data changes does not matter
+    meta.compression = Some(COMPRESSION_ALGORITHM.to_string());
+    meta.store(project)?;
+
+    return recode_project(project, "Compressed");
+}
+
+pub(crate) fn decompress(project: &str) -> Result<(), CrusterCLIError> {
+    let mut meta = load_meta(project)?;
+    if meta.compression.is_none() {
+        return Err(CrusterCLIError::from("Project is not compressed"));
+    }
+
+    meta.compression = None;
+    meta.store(project)?;
+
+    return recode_project(project, "Decompressed");
+}
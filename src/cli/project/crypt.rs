@@ -0,0 +1,52 @@
+use super::{CrusterCLIError, load_meta, project_files, recode_file};
+use crate::project::crypto::{self, EncryptionMeta};
+use crate::project::files::RecordCodec;
+
+pub(crate) fn encrypt(project: &str) -> Result<(), CrusterCLIError> {
+    let mut meta = load_meta(project)?;
+    if meta.encryption.is_some() {
+        // Encryption could be interrupted, then the rest of records is encrypted with the same key
+        crypto::unlock(project)?;
+        println!("Project is encrypted already, records which are not encrypted yet are encrypted");
+    }
+    else {
+        let passphrase = crypto::read_passphrase("New passphrase: ", true)?;
+        let (encryption, key) = EncryptionMeta::new(&passphrase)?;
+        crypto::remember(project, key);
+
+        // Metadata goes first, so data is never left encrypted without the salt to decrypt it.
+        // If the process is interrupted, plain records are read only by this command, which finishes encryption
+        meta.encryption = Some(encryption);
+        meta.store(project)?;
+    }
+
+    for path in project_files(project)? {
+        let count = recode_file(&path, RecordCodec::for_conversion(&path), RecordCodec::for_writing(&path)?)?;
+        println!("Encrypted {} records of '{}'", count, &path);
+    }
+
+    println!("Project is encrypted, keep the passphrase: data cannot be recovered without it");
+    Ok(())
+}
+
+pub(crate) fn decrypt(project: &str) -> Result<(), CrusterCLIError> {
+    let mut meta = load_meta(project)?;
+    if meta.encryption.is_none() {
+        return Err(CrusterCLIError::from("Project is not encrypted"));
+    }
+
+    // Key is remembered, so files are read with it
+    crypto::unlock(project)?;
+
+    // Data goes first, so encryption metadata is removed only when nothing needs it
+    for path in project_files(project)? {
+        let count = recode_file(&path, RecordCodec::for_conversion(&path), RecordCodec::with_options(&path, meta.compression.is_some(), None))?;
+        println!("Decrypted {} records of '{}'", count, &path);
+    }
+
+    meta.encryption = None;
+    meta.store(project)?;
+
+    println!("Project is decrypted");
+    Ok(())
+}
@@ -0,0 +1,183 @@
+use std::path;
+use clap::ArgMatches;
+use serde::Serialize;
+use serde_json as json;
+use std::collections::BTreeMap;
+
+use super::{read_pairs, records_size, base64_overhead, file_size, format_size};
+use crate::cli::CrusterCLIError;
+use crate::project::{self, ProjectMeta, files::RecordReader};
+
+pub(crate) struct ProjectInfoSettings {
+    pub(crate) json: bool,
+}
+
+impl TryFrom<&ArgMatches> for ProjectInfoSettings {
+    type Error = CrusterCLIError;
+    fn try_from(args: &ArgMatches) -> Result<Self, Self::Error> {
+        let settings = ProjectInfoSettings {
+            json: args.get_flag("json")
+        };
+
+        return Ok(settings);
+    }
+}
+
+#[derive(Serialize)]
+struct ProjectInfo {
+    path: String,
+    meta: Option<ProjectMeta>,
+    pairs: usize,
+    completed_pairs: usize,
+    repeaters: usize,
+    http_size: u64,
+    repeater_size: u64,
+    // Size of records as they are, without compression and encryption
+    records_size: u64,
+    compression_ratio: f64,
+    // Part of stored size taken by base64 of compressed and encrypted records, it is counted in the ratio
+    base64_overhead: u64,
+    // Unix time in seconds of the earliest and the latest captured requests
+    first_captured: Option<u64>,
+    last_captured: Option<u64>,
+    hosts: BTreeMap<String, usize>,
+}
+
+fn count_repeaters(repeater_path: &str) -> Result<usize, CrusterCLIError> {
+    if !path::Path::new(repeater_path).is_file() {
+        return Ok(0);
+    }
+
+    let mut count: usize = 0;
+    for line in RecordReader::open(repeater_path)? {
+        let _ = line?;
+        count += 1;
+    }
+
+    return Ok(count);
+}
+
+fn collect_info(project: &str) -> Result<ProjectInfo, CrusterCLIError> {
+    let http_path = format!("{}/http.jsonl", project);
+    let repeater_path = format!("{}/repeater.jsonl", project);
+
+    let mut info = ProjectInfo {
+        path: project.to_string(),
+        meta: ProjectMeta::load(project)?,
+        pairs: 0,
+        completed_pairs: 0,
+        repeaters: count_repeaters(&repeater_path)?,
+        http_size: file_size(&http_path),
+        repeater_size: file_size(&repeater_path),
+        records_size: records_size(&http_path)? + records_size(&repeater_path)?,
+        compression_ratio: 1.0,
+        base64_overhead: base64_overhead(&http_path)? + base64_overhead(&repeater_path)?,
+        first_captured: None,
+        last_captured: None,
+        hosts: BTreeMap::default(),
+    };
+
+    let stored_size = info.http_size + info.repeater_size;
+    if stored_size > 0 {
+        info.compression_ratio = info.records_size as f64 / stored_size as f64;
+    }
+
+    for pair in read_pairs(&http_path)? {
+        info.pairs += 1;
+        if pair.response.is_some() {
+            info.completed_pairs += 1;
+        }
+
+        if let Some(request) = pair.request.as_ref() {
+            *info.hosts.entry(request.get_hostname()).or_insert(0) += 1;
+        }
+
+        let timestamp = pair.timestamp
+            .and_then(|ts| ts.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs());
+
+        if let Some(ts) = timestamp {
+            info.first_captured = Some(info.first_captured.map_or(ts, |first| first.min(ts)));
+            info.last_captured = Some(info.last_captured.map_or(ts, |last| last.max(ts)));
+        }
+    }
+
+    return Ok(info);
+}
+
+fn print_info(info: &ProjectInfo) {
+    match info.meta.as_ref() {
+        Some(meta) => {
+            println!("{:<13} {}", "Project:", &meta.name);
+            if let Some(description) = meta.description.as_ref() {
+                println!("{:<13} {}", "Description:", description);
+            }
+
+            println!("{:<13} {} (Cruster {})", "Created:", project::format_timestamp(meta.created), &meta.cruster_version);
+            match meta.encryption.as_ref() {
+                Some(encryption) => {
+                    println!("{:<13} {} (key from passphrase with {})", "Encryption:", &encryption.cipher, &encryption.kdf);
+                },
+                None => {
+                    println!("{:<13} <NONE>", "Encryption:");
+                }
+            }
+        },
+        None => {
+            println!("{:<13} <NOT INITIALIZED>", "Project:");
+        }
+    }
+
+    println!("{:<13} {}", "Path:", &info.path);
+    println!("{:<13} {} ({} with response)", "Pairs:", info.pairs, info.completed_pairs);
+    println!("{:<13} {}", "Repeaters:", info.repeaters);
+
+    match (info.first_captured, info.last_captured) {
+        (Some(first), Some(last)) => {
+            println!("{:<13} {} - {}", "Captured:", project::format_timestamp(first), project::format_timestamp(last));
+        },
+        _ => {
+            println!("{:<13} <UNKNOWN>", "Captured:");
+        }
+    }
+
+    println!(
+        "{:<13} {} (http.jsonl: {}, repeater.jsonl: {})",
+        "Size:",
+        format_size(info.http_size + info.repeater_size),
+        format_size(info.http_size),
+        format_size(info.repeater_size)
+    );
+
+    let compression = info.meta
+        .as_ref()
+        .and_then(|meta| meta.compression.as_deref())
+        .unwrap_or("<NONE>");
+
+    println!(
+        "{:<13} {}, ratio {:.2} ({} of records are stored in {}, base64 of encoded records takes {} of it)",
+        "Compression:",
+        compression,
+        info.compression_ratio,
+        format_size(info.records_size),
+        format_size(info.http_size + info.repeater_size),
+        format_size(info.base64_overhead)
+    );
+
+    println!("{:<13} {}", "Hosts:", info.hosts.len());
+    for (host, count) in info.hosts.iter() {
+        println!("    {:>6} {}", count, host);
+    }
+}
+
+pub(crate) fn execute(settings: &ProjectInfoSettings, project: &str) -> Result<(), CrusterCLIError> {
+    let info = collect_info(project)?;
+    if settings.json {
+        println!("{}", json::to_string_pretty(&info)?);
+    }
+    else {
+        print_info(&info);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use std::path;
+use clap::ArgMatches;
+
+use crate::cli::CrusterCLIError;
+use crate::project::ProjectMeta;
+
+pub(crate) struct ProjectInitSettings {
+    pub(crate) name: Option<String>,
+    pub(crate) description: Option<String>,
+    pub(crate) force: bool,
+}
+
+impl TryFrom<&ArgMatches> for ProjectInitSettings {
+    type Error = CrusterCLIError;
+    fn try_from(args: &ArgMatches) -> Result<Self, Self::Error> {
+        let settings = ProjectInitSettings {
+            name: args.get_one::<String>("name").cloned(),
+            description: args.get_one::<String>("description").cloned(),
+            force: args.get_flag("force"),
+        };
+
+        return Ok(settings);
+    }
+}
+
+pub(crate) fn execute(settings: &ProjectInitSettings, project: &str) -> Result<(), CrusterCLIError> {
+    let old_meta = ProjectMeta::load(project)?;
+    if old_meta.is_some() && !settings.force {
+        return Err(
+            CrusterCLIError::from(format!("Project at '{}' is initialized already, use --force to rewrite its metadata", project))
+        );
+    }
+
+    let name = match settings.name.as_ref() {
+        Some(name) => name.clone(),
+        None => {
+            path::Path::new(project)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| project.to_string())
+        }
+    };
+
+    let mut meta = ProjectMeta::new(&name, settings.description.as_deref());
+    // Data stays encrypted and compressed, so these parameters must survive rewriting of metadata
+    if let Some(old_meta) = old_meta {
+        meta.encryption = old_meta.encryption;
+        meta.compression = old_meta.compression;
+    }
+    meta.store(project)?;
+
+    println!("Project '{}' is initialized at '{}'", &name, project);
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use std::path;
+use clap::ArgMatches;
+use serde_json as json;
+use shellexpand::tilde;
+
+use super::read_pairs;
+use crate::cli::CrusterCLIError;
+use crate::utils::CrusterError;
+use crate::project::files::{FileLock, RecordAppender, RecordReader};
+use crate::http_storage::serializable::SerializableProxyData;
+
+pub(crate) struct ProjectMergeSettings {
+    pub(crate) source: String,
+    pub(crate) with_repeaters: bool,
+}
+
+impl TryFrom<&ArgMatches> for ProjectMergeSettings {
+    type Error = CrusterCLIError;
+    fn try_from(args: &ArgMatches) -> Result<Self, Self::Error> {
+        let source = args.get_one::<String>("SOURCE").unwrap();
+        let settings = ProjectMergeSettings {
+            source: tilde(source).to_string(),
+            with_repeaters: !args.get_flag("no-repeaters"),
+        };
+
+        return Ok(settings);
+    }
+}
+
+fn merge_pairs(source: &str, target: &str) -> Result<(usize, usize), CrusterCLIError> {
+    let target_path = format!("{}/http.jsonl", target);
+    // IDs are taken after the ones of target, so nobody may append there until merge is done
+    let lock = FileLock::exclusive(&target_path)?;
+    let next_id = read_pairs(&target_path)?
+        .iter()
+        .map(|pair| pair.index + 1)
+        .max()
+        .unwrap_or(0);
+
+    let mut source_pairs = read_pairs(&format!("{}/http.jsonl", source))?;
+    if source_pairs.is_empty() {
+        return Ok((0, next_id));
+    }
+
+    // Relative order of merged pairs is kept, IDs are placed after the ones of target
+    source_pairs.sort_by_key(|pair| pair.index);
+
+    let mut records: Vec<String> = Vec::with_capacity(source_pairs.len());
+    for (offset, pair) in source_pairs.iter_mut().enumerate() {
+        pair.index = next_id + offset;
+        let serializable_record = SerializableProxyData::try_from(&*pair)?;
+        records.push(json::to_string(&serializable_record)?);
+    }
+
+    // Pairs are appended at once, so they are compressed together, if project is compressed
+    RecordAppender::open(&target_path)?.append_all_locked(&lock, &records)?;
+
+    return Ok((source_pairs.len(), next_id));
+}
+
+fn merge_repeaters(source: &str, target: &str) -> Result<usize, CrusterCLIError> {
+    let source_path = format!("{}/repeater.jsonl", source);
+    if !path::Path::new(&source_path).is_file() {
+        return Ok(0);
+    }
+
+    let target_path = format!("{}/repeater.jsonl", target);
+    let lock = FileLock::exclusive(&target_path)?;
+    let records = RecordReader::open(&source_path)?.collect::<Result<Vec<String>, CrusterError>>()?;
+    RecordAppender::open(&target_path)?.append_all_locked(&lock, &records)?;
+
+    return Ok(records.len());
+}
+
+pub(crate) fn execute(settings: &ProjectMergeSettings, project: &str) -> Result<(), CrusterCLIError> {
+    let source_dir = path::Path::new(&settings.source);
+    if !source_dir.is_dir() {
+        return Err(CrusterCLIError::from(format!("Could not find project to merge at '{}'", &settings.source)));
+    }
+
+    if source_dir.canonicalize()? == path::Path::new(project).canonicalize()? {
+        return Err(CrusterCLIError::from("Could not merge project into itself"));
+    }
+
+    let (pairs, first_id) = merge_pairs(&settings.source, project)?;
+    if pairs > 0 {
+        println!("Merged {} pairs, they got IDs from {} to {}", pairs, first_id, first_id + pairs - 1);
+    }
+    else {
+        println!("There are no pairs to merge");
+    }
+
+    if settings.with_repeaters {
+        let repeaters = merge_repeaters(&settings.source, project)?;
+        println!("Merged {} repeaters", repeaters);
+    }
+
+    Ok(())
+}
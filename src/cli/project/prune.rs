@@ -0,0 +1,152 @@
+use regex::Regex;
+use clap::ArgMatches;
+use std::time::{Duration, SystemTime};
+
+use super::{read_pairs, write_pairs};
+use crate::scope::ScopeMatcher;
+use crate::config::Config;
+use crate::cli::CrusterCLIError;
+use crate::project::files::FileLock;
+use crate::cli::http::show::matches_the_filter;
+use crate::http_storage::RequestResponsePair;
+
+pub(crate) struct ProjectPruneSettings {
+    pub(crate) out_of_scope: bool,
+    pub(crate) filter: Option<Regex>,
+    pub(crate) older_than: Option<Duration>,
+    pub(crate) dry_run: bool,
+}
+
+/// Parses durations like '90s', '30m', '12h', '7d' or '2w'
+fn parse_duration(value: &str) -> Result<Duration, CrusterCLIError> {
+    let value = value.trim();
+    let wrong_format = || {
+        CrusterCLIError::from(format!("Could not parse duration '{}', it must be a number with one of units: s, m, h, d, w", value))
+    };
+
+    // Unit could be multi-byte character, so value is split at its boundary
+    let (unit_start, _) = value.char_indices().last().ok_or_else(wrong_format)?;
+    let (number, unit) = value.split_at(unit_start);
+    let multiplier: u64 = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => {
+            return Err(wrong_format());
+        }
+    };
+
+    let number: u64 = number.parse()?;
+    let seconds = number
+        .checked_mul(multiplier)
+        .ok_or_else(|| CrusterCLIError::from(format!("Duration '{}' is too large", value)))?;
+
+    return Ok(Duration::from_secs(seconds));
+}
+
+impl TryFrom<&ArgMatches> for ProjectPruneSettings {
+    type Error = CrusterCLIError;
+    fn try_from(args: &ArgMatches) -> Result<Self, Self::Error> {
+        let filter = match args.get_one::<String>("filter") {
+            Some(filter) => Some(Regex::new(filter)?),
+            None => None
+        };
+
+        let older_than = match args.get_one::<String>("older-than") {
+            Some(duration) => Some(parse_duration(duration)?),
+            None => None
+        };
+
+        let settings = ProjectPruneSettings {
+            out_of_scope: args.get_flag("out-of-scope"),
+            filter,
+            older_than,
+            dry_run: args.get_flag("dry-run"),
+        };
+
+        if !settings.out_of_scope && settings.filter.is_none() && settings.older_than.is_none() {
+            return Err(CrusterCLIError::from("Nothing to prune by, specify scope, filter or age"));
+        }
+
+        return Ok(settings);
+    }
+}
+
+struct PruneRules {
+    scope: ScopeMatcher,
+    out_of_scope: bool,
+    filter: Option<Regex>,
+    // Pairs captured before this moment are pruned, ones without timestamp are kept
+    deadline: Option<SystemTime>,
+}
+
+impl PruneRules {
+    fn should_prune(&self, pair: &RequestResponsePair) -> bool {
+        if self.out_of_scope {
+            if let Some(request) = pair.request.as_ref() {
+                if !self.scope.fits(&request.uri) {
+                    return true;
+                }
+            }
+        }
+
+        if let Some(re) = self.filter.as_ref() {
+            if matches_the_filter(pair, re) {
+                return true;
+            }
+        }
+
+        if let (Some(deadline), Some(timestamp)) = (self.deadline, pair.timestamp) {
+            if timestamp < deadline {
+                return true;
+            }
+        }
+
+        return false;
+    }
+}
+
+pub(crate) fn execute(settings: ProjectPruneSettings, project: &str, config: &Config) -> Result<(), CrusterCLIError> {
+    let deadline = match settings.older_than {
+        Some(age) => {
+            let deadline = SystemTime::now()
+                .checked_sub(age)
+                .ok_or_else(|| CrusterCLIError::from("Age to prune by is too large"))?;
+
+            Some(deadline)
+        },
+        None => None
+    };
+
+    let rules = PruneRules {
+        scope: ScopeMatcher::from_config(config.scope.as_ref()),
+        out_of_scope: settings.out_of_scope,
+        filter: settings.filter,
+        deadline,
+    };
+
+    let http_path = format!("{}/http.jsonl", project);
+    // Proxy could append pairs meanwhile, so they are locked out until the file is rewritten
+    let lock = FileLock::exclusive(&http_path)?;
+    let pairs = read_pairs(&http_path)?;
+    let total = pairs.len();
+    let kept: Vec<RequestResponsePair> = pairs
+        .into_iter()
+        .filter(|pair| !rules.should_prune(pair))
+        .collect();
+
+    let pruned = total - kept.len();
+    if settings.dry_run {
+        println!("{} of {} pairs would be pruned", pruned, total);
+        return Ok(());
+    }
+
+    if pruned > 0 {
+        write_pairs(&lock, &http_path, &kept)?;
+    }
+
+    println!("Pruned {} of {} pairs", pruned, total);
+    Ok(())
+}
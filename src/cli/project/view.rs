@@ -0,0 +1,81 @@
+use std::str::FromStr;
+use clap::ArgMatches;
+
+use crate::cli::CrusterCLIError;
+use crate::project::views::ProjectViews;
+use crate::query::Query;
+
+pub(crate) enum ViewAction {
+    List,
+    Add { name: String, query: String },
+    Remove { name: String },
+}
+
+pub(crate) struct ProjectViewSettings {
+    pub(crate) action: ViewAction,
+}
+
+impl TryFrom<&ArgMatches> for ProjectViewSettings {
+    type Error = CrusterCLIError;
+    fn try_from(args: &ArgMatches) -> Result<Self, Self::Error> {
+        let action = match args.subcommand() {
+            Some(("list", _)) => ViewAction::List,
+            Some(("add", add_args)) => {
+                let name = add_args.get_one::<String>("NAME").unwrap().to_string();
+                let query = add_args.get_one::<String>("QUERY").unwrap().to_string();
+                // Broken query must not get into project, it would fail every time view is used
+                if let Err(e) = Query::from_str(&query) {
+                    return Err(CrusterCLIError::from(e.explain(&query)));
+                }
+
+                ViewAction::Add { name, query }
+            },
+            Some(("remove", remove_args)) => {
+                ViewAction::Remove { name: remove_args.get_one::<String>("NAME").unwrap().to_string() }
+            },
+            _ => unreachable!()
+        };
+
+        return Ok(ProjectViewSettings { action });
+    }
+}
+
+pub(crate) fn execute(settings: &ProjectViewSettings, project: &str) -> Result<(), CrusterCLIError> {
+    let lock = ProjectViews::lock(project)?;
+    let mut views = ProjectViews::load(project)?;
+
+    match &settings.action {
+        ViewAction::List => {
+            if views.iter().next().is_none() {
+                println!("There are no saved views");
+            }
+
+            for view in views.iter() {
+                let mark = if view.active { "*" } else { " " };
+                println!("{} {:<20} {}", mark, &view.name, &view.query);
+            }
+        },
+        ViewAction::Add { name, query } => {
+            let is_new = views.get(name).is_none();
+            views.save(name, query);
+            views.store(&lock, project)?;
+
+            if is_new {
+                println!("View '{}' is saved", name);
+            }
+            else {
+                println!("Query of view '{}' is replaced", name);
+            }
+        },
+        ViewAction::Remove { name } => {
+            if !views.remove(name) {
+                return Err(CrusterCLIError::from(format!("There is no view '{}' in project", name)));
+            }
+
+            views.store(&lock, project)?;
+            println!("View '{}' is removed", name);
+        }
+    }
+
+    Ok(())
+}
@@ -1,7 +1,7 @@
-use std::fs::File;
 use serde_json as json;
 use super::CrusterCLIError;
-use std::io::{BufReader, BufRead, Write};
+use crate::utils::CrusterError;
+use crate::project::files::{self, FileLock, RecordReader};
 use crate::siv_ui::repeater::{RepeaterState, RepeaterStateSerializable};
 
 pub(crate) mod list;
@@ -12,56 +12,50 @@ pub(crate) mod add;
 
 
 pub(crate) struct RepeaterIterator {
-    reader: BufReader<File>
+    reader: RecordReader
 }
 
 impl RepeaterIterator {
-    pub(crate) fn new(path: &str) -> RepeaterIterator {
-        let file = File::open(path).expect("No file with saved repeaters exists!");
+    pub(crate) fn new(path: &str) -> Result<RepeaterIterator, CrusterError> {
         let iter = RepeaterIterator {
-            reader: BufReader::new(file)
+            reader: RecordReader::open(path)?
         };
 
-        return iter;
+        return Ok(iter);
     }
 }
 
+/// Record which cannot be read, i.e. not encrypted one in encrypted project, is an error, not a panic,
+/// because repeaters are read by proxy and API too
 impl Iterator for RepeaterIterator {
-    type Item = RepeaterState;
+    type Item = Result<RepeaterState, CrusterError>;
     fn next(&mut self) -> Option<Self::Item> {
-        let mut buf = String::default();
-        while buf.trim().len() == 0 {
-            let len = self.reader.read_line(&mut buf).unwrap();
-            if len == 0 {
-                return None
-            }
-        }
+        let line = match self.reader.next()? {
+            Ok(line) => line,
+            Err(err) => return Some(Err(err))
+        };
 
-        let ser_repeater = json::from_str::<RepeaterStateSerializable>(&buf).unwrap();
-        let repeater = RepeaterState::try_from(ser_repeater).unwrap();
+        let repeater = json::from_str::<RepeaterStateSerializable>(&line)
+            .map_err(CrusterError::from)
+            .and_then(RepeaterState::try_from);
 
         return Some(repeater);
     }
 }
 
 pub(crate) fn update_repeaters(path: &str, repeater: &RepeaterState, number: usize) -> Result<(), CrusterCLIError> {
+    // Repeaters are read and written under the same lock, so no concurrent change is lost
+    let lock = FileLock::exclusive(path)?;
     let buf = if std::path::Path::new(path).is_file() {
-        let file = File::open(path)?;
-        let fin = BufReader::with_capacity(4000000, file);
         let mut buf: Vec<Option<String>> = Vec::with_capacity(20);
 
-        for (i, line) in fin.lines().enumerate() {
-            if let Ok(repeater_str) = line {
-                if repeater_str.trim().len() == 0 {
-                    continue;
-                }
-
-                if i != number {
-                    buf.push(Some(repeater_str));
-                }
-                else {
-                    buf.push(None);
-                }
+        for (i, line) in RecordReader::open(path)?.enumerate() {
+            let repeater_str = line?;
+            if i != number {
+                buf.push(Some(repeater_str));
+            }
+            else {
+                buf.push(None);
             }
         }
 
@@ -71,28 +65,30 @@ pub(crate) fn update_repeaters(path: &str, repeater: &RepeaterState, number: usi
         vec![None]
     };
 
-    let mut found_flag: bool = false;
-    let mut fout = std::fs::OpenOptions::new().create(true).truncate(true).write(true).open(path)?;
-    for possible_repeater_str in buf {
-        if let Some(repeater_str) = possible_repeater_str {
-            let line = format!("{}\n", repeater_str);
-            let _ = fout.write(line.as_bytes())?;
+    let repeater_ser = RepeaterStateSerializable::from(repeater);
+    let repeater_str = json::to_string(&repeater_ser)?;
+
+    files::rewrite(&lock, path, |fout| {
+        let mut found_flag: bool = false;
+        for possible_repeater_str in buf {
+            if let Some(old_repeater_str) = possible_repeater_str {
+                let line = format!("{}\n", old_repeater_str);
+                fout.write_all(line.as_bytes())?;
+            }
+            else {
+                let line = format!("{}\n", repeater_str);
+                fout.write_all(line.as_bytes())?;
+                found_flag = true;
+            }
         }
-        else {
-            let repeater_ser = RepeaterStateSerializable::from(repeater);
-            let repeater_str = json::to_string(&repeater_ser)?;
+
+        if !found_flag {
             let line = format!("{}\n", repeater_str);
-            let _ = fout.write(line.as_bytes())?;
-            found_flag = true;
+            fout.write_all(line.as_bytes())?;
         }
-    }
 
-    if !found_flag {
-        let repeater_ser = RepeaterStateSerializable::from(repeater);
-        let repeater_str = json::to_string(&repeater_ser)?;
-        let line = format!("{}\n", repeater_str);
-        let _ = fout.write(line.as_bytes())?;
-    }
+        Ok(())
+    })?;
 
     Ok(())
 }
@@ -1,6 +1,6 @@
 use clap::ArgMatches;
 use crate::{cli::CrusterCLIError, http_storage, siv_ui::repeater::{RepeaterState, RepeaterParameters}};
-use std::io::{BufReader, BufRead};
+use crate::project::files::RecordReader;
 use serde_json as json;
 use cursive::views::TextContent;
 
@@ -25,17 +25,14 @@ impl TryFrom<&ArgMatches> for RepeaterAddSettings {
 pub(crate) fn exec(settings: &RepeaterAddSettings, http_path: &str, repeater_path: &str) -> Result<(), CrusterCLIError> {
     // Not very good way, but...
     let next_repeater_id = if std::path::Path::new(repeater_path).is_file() {
-        super::RepeaterIterator::new(repeater_path).count()
+        super::RepeaterIterator::new(repeater_path)?.count()
     }
     else {
         0
     };
 
 
-    let file = std::fs::File::open(http_path)?;
-    let fin = BufReader::new(file);
-
-    for raw_line in fin.lines() {
+    for raw_line in RecordReader::open(http_path)? {
         let line = &raw_line?;
 
         let pair_ser: http_storage::serializable::SerializableProxyData = json::from_str(line)?;
@@ -94,11 +94,12 @@ pub(crate) fn execute(settings: &RepeaterEditSettings, path: &str) -> Result<(),
         }
     };
 
-    let repeater_iter = super::RepeaterIterator::new(path);
+    let repeater_iter = super::RepeaterIterator::new(path)?;
     let mut number_to_update: usize = 0;
     let mut repeater_to_update: Option<RepeaterState> = None;
 
-    for (i, mut repeater) in repeater_iter.enumerate() {
+    for (i, repeater) in repeater_iter.enumerate() {
+        let mut repeater = repeater?;
         if let Some(number) = settings.number_to_get {
             if number == (i + 1) {
                 number_to_update = i;
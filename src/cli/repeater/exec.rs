@@ -9,6 +9,8 @@ use reqwest::{self, Request, Response};
 
 use super::RepeaterIterator;
 use crate::cli::CrusterCLIError;
+use crate::cruster_proxy::request_response::HyperResponseWrapper;
+use crate::session::Sessions;
 use crate::siv_ui::repeater::{RepeaterState, RepeaterParameters};
 
 
@@ -113,6 +115,15 @@ async fn send_request(request: Request, params: &RepeaterParameters) -> Result<(
     return Ok((response, params.max_redirects));
 }
 
+async fn send_with_session(request: Request, params: &RepeaterParameters, sessions: &Sessions) -> Result<(HyperResponseWrapper, usize), CrusterCLIError> {
+    let send = |request: Request| async move {
+        let (response, redirects) = send_request(request, params).await?;
+        return Ok::<_, CrusterCLIError>((HyperResponseWrapper::from_reqwest(response).await?, redirects));
+    };
+
+    return sessions.send_with_session(request, send).await;
+}
+
 fn open_editor(editor: &str, request: String) -> Result<String, CrusterCLIError> {
     let tmp_path = format!(
         "/tmp/cruster-repeater-{}.txt",
@@ -153,7 +164,7 @@ fn get_ready_request(repeater: &mut RepeaterState, editor: &str, force: bool) ->
     };
 }
 
-async fn handle_repeater(mut repeater: &mut RepeaterState, number: usize, path: &str, editor: &str, settings: &RepeaterExecSettings) -> Result<(), CrusterCLIError> {
+async fn handle_repeater(mut repeater: &mut RepeaterState, number: usize, path: &str, editor: &str, settings: &RepeaterExecSettings, sessions: &Sessions) -> Result<(), CrusterCLIError> {
     let request = get_ready_request(&mut repeater, editor, settings.force)?;
 
     if settings.no_body {
@@ -167,8 +178,7 @@ async fn handle_repeater(mut repeater: &mut RepeaterState, number: usize, path:
     
     super::update_repeaters(path, &repeater, number.to_owned())?;
 
-    let (response, redirects) = send_request(request, &repeater.parameters).await?;
-    let wrapper = crate::cruster_proxy::request_response::HyperResponseWrapper::from_reqwest(response).await?;
+    let (wrapper, redirects) = send_with_session(request, &repeater.parameters, sessions).await?;
     let response_str = wrapper.to_string();
     repeater.response = TextContent::new(response_str.clone());
 
@@ -187,12 +197,13 @@ async fn handle_repeater(mut repeater: &mut RepeaterState, number: usize, path:
     return Ok(())
 }
 
-pub(crate) async fn execute(settings: &RepeaterExecSettings, path: &str, editor: &str) -> Result<(), CrusterCLIError> {
-    let repeater_iter = RepeaterIterator::new(path);
-    for (i, mut repeater) in repeater_iter.enumerate() {
+pub(crate) async fn execute(settings: &RepeaterExecSettings, path: &str, editor: &str, sessions: &Sessions) -> Result<(), CrusterCLIError> {
+    let repeater_iter = RepeaterIterator::new(path)?;
+    for (i, repeater) in repeater_iter.enumerate() {
+        let mut repeater = repeater?;
         if let Some(number) = settings.number.as_ref() {
             if &(i + 1) == number {
-                return handle_repeater(&mut repeater, i, path, editor, settings).await;
+                return handle_repeater(&mut repeater, i, path, editor, settings, sessions).await;
             }
 
             continue;
@@ -200,7 +211,7 @@ pub(crate) async fn execute(settings: &RepeaterExecSettings, path: &str, editor:
 
         if let Some(name) = settings.name.as_ref() {
             if &repeater.name == name {
-                return handle_repeater(&mut repeater, i, path, editor, settings).await;
+                return handle_repeater(&mut repeater, i, path, editor, settings, sessions).await;
             }
 
             continue;
@@ -210,4 +221,35 @@ pub(crate) async fn execute(settings: &RepeaterExecSettings, path: &str, editor:
     Err(
         CrusterCLIError::from("Cannot find repeater by specified mark (number/name)")
     )
-}
\ No newline at end of file
+}
+/// Sends request of repeater as is, without editor, and saves response, so it can be done by control API.
+/// Returns number of repeater, updated repeater and whether redirects count was exceeded
+pub(crate) async fn send_saved(path: &str, mark: &str, sessions: &Sessions) -> Result<(usize, RepeaterState, bool), CrusterCLIError> {
+    let number = mark.parse::<usize>().ok();
+    let mut found: Option<(usize, RepeaterState)> = None;
+    for (i, repeater) in RepeaterIterator::new(path)?.enumerate() {
+        let repeater = repeater?;
+        let is_found = match number {
+            Some(number) => i + 1 == number,
+            None => repeater.name == mark
+        };
+
+        if is_found {
+            found = Some((i, repeater));
+            break;
+        }
+    }
+
+    let (i, mut repeater) = match found {
+        Some(found) => found,
+        None => return Err(CrusterCLIError::from("Cannot find repeater by specified mark (number/name)"))
+    };
+
+    let request = repeater.make_reqwest()?;
+    let (wrapper, redirects) = send_with_session(request, &repeater.parameters, sessions).await?;
+    repeater.response = TextContent::new(wrapper.to_string());
+
+    super::update_repeaters(path, &repeater, i)?;
+
+    return Ok((i + 1, repeater, redirects == 0));
+}
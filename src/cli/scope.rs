@@ -0,0 +1,31 @@
+use url::Url;
+
+use super::CrusterCLIError;
+use crate::config::Scope;
+use crate::scope::ScopeMatcher;
+
+/// Prints whether URL is in scope and which include/exclude rules decided it
+pub(crate) fn test(url: &str, scope: Option<&Scope>) -> Result<(), CrusterCLIError> {
+    if let Err(e) = Url::parse(url) {
+        return Err(CrusterCLIError::from(format!("'{}' is not a full URL: {}", url, e)));
+    }
+
+    let matcher = ScopeMatcher::from_config(scope);
+    let decision = matcher.decide(url);
+
+    let verdict = if decision.in_scope { "IN scope" } else { "OUT of scope" };
+    println!("{} is {}", url, verdict);
+
+    match decision.included_by.as_ref() {
+        Some(source) => println!("  included by {}", source),
+        None if matcher.has_include() => println!("  not included: fits none of include rules and regexes"),
+        None => println!("  included: there are no include rules and regexes, everything is included")
+    }
+
+    match decision.excluded_by.as_ref() {
+        Some(source) => println!("  excluded by {}", source),
+        None => println!("  not excluded: fits none of exclude rules and regexes")
+    }
+
+    Ok(())
+}
@@ -3,6 +3,14 @@ use clap::{self, ArgMatches};
 use serde_yaml as yml;
 use shellexpand::tilde;
 use serde::{Serialize, Deserialize};
+use colored::Color;
+
+use crate::noise::{self, DedupeKey};
+use crate::query::Query;
+use crate::dump::template::DumpTemplate;
+use crate::daemon::DEFAULT_GRACE_PERIOD;
+use crate::scope::{ScopeMatcher, ScopeRule};
+use std::str::FromStr;
 
 use log::{LevelFilter, debug};
 use log4rs::append::file::FileAppender;
@@ -31,14 +39,158 @@ where
 pub(crate) enum CrusterMode {
     INTERACTIVE,
     DUMP(ArgMatches),
-    CLI(ArgMatches)
+    DAEMON(ArgMatches),
+    CLI(ArgMatches),
+    PROJECT(ArgMatches),
+    SCOPE(ArgMatches)
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Default)]
 pub(crate) struct Scope {
     pub(crate) include: Option<Vec<String>>,
     pub(crate) exclude: Option<Vec<String>>,
     pub(crate) strict: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) include_rules: Option<Vec<ScopeRule>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) exclude_rules: Option<Vec<ScopeRule>>,
+    // Scope file of program (domains list, Burp options or CSV of assets), its entries are added to rules and regexes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) file: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Default)]
+pub(crate) struct Noise {
+    pub(crate) dedupe: Option<Vec<DedupeKey>>,
+    pub(crate) hide_extensions: Option<Vec<String>>,
+    pub(crate) hide_mime: Option<Vec<String>>,
+    #[serde(default)]
+    pub(crate) strict: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum DumpFormat {
+    #[default]
+    Text,
+    // One JSON object per exchange or WebSocket message (NDJSON)
+    Json,
+}
+
+// Part of exchange printed in text format of dump mode
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DumpSection {
+    RequestHeaders,
+    ResponseHeaders,
+    RequestBody,
+    ResponseBody,
+}
+
+impl FromStr for DumpSection {
+    type Err = CrusterConfigError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s.trim() {
+            "request_headers" => Ok(DumpSection::RequestHeaders),
+            "response_headers" => Ok(DumpSection::ResponseHeaders),
+            "request_body" => Ok(DumpSection::RequestBody),
+            "response_body" => Ok(DumpSection::ResponseBody),
+            _ => Err(CrusterConfigError::from(format!("Unknown dump section '{}', possible: request_headers, response_headers, request_body, response_body", s)))
+        };
+    }
+}
+
+// Exchanges matching query are printed with color in text format of dump mode
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub(crate) struct HighlightRule {
+    pub(crate) color: String,
+    pub(crate) query: String,
+}
+
+impl FromStr for HighlightRule {
+    type Err = CrusterConfigError;
+    // 'COLOR:QUERY', i.e. 'red:status >= 500'
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s.split_once(':') {
+            Some((color, query)) => Ok(HighlightRule { color: color.trim().to_string(), query: query.trim().to_string() }),
+            None => Err(CrusterConfigError::from(format!("Highlight rule '{}' must be written as 'COLOR:QUERY', i.e. 'red:status >= 500'", s)))
+        };
+    }
+}
+
+impl HighlightRule {
+    /// Color and query are checked before proxy is started
+    pub(crate) fn check(&self) -> Result<(), CrusterConfigError> {
+        if Color::from_str(&self.color).is_err() {
+            return Err(
+                CrusterConfigError::from(
+                    format!("Unknown highlight color '{}', possible: black, red, green, yellow, blue, magenta, cyan, white and bright ones, i.e. 'bright red'", self.color)
+                )
+            );
+        }
+
+        if let Err(e) = Query::from_str(&self.query) {
+            return Err(CrusterConfigError::from(e.explain(&self.query)));
+        }
+
+        return Ok(());
+    }
+}
+
+// File to write dump output to instead of stdout, old files are rotated by size and/or time
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub(crate) struct DumpOutput {
+    pub(crate) path: String,
+    // Bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) max_size: Option<u64>,
+    // Seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) rotate_every: Option<u64>,
+    // How many rotated files are kept
+    #[serde(default = "default_keep_rotated")]
+    pub(crate) keep: u32,
+    // Rotated files are compressed with gzip
+    #[serde(default = "default_compress_rotated")]
+    pub(crate) compress: bool,
+}
+
+pub(crate) const DEFAULT_KEEP_ROTATED: u32 = 5;
+
+fn default_keep_rotated() -> u32 {
+    return DEFAULT_KEEP_ROTATED;
+}
+
+fn default_compress_rotated() -> bool {
+    return true;
+}
+
+impl DumpOutput {
+    fn new(path: String) -> Self {
+        DumpOutput {
+            path,
+            max_size: None,
+            rotate_every: None,
+            keep: DEFAULT_KEEP_ROTATED,
+            compress: true
+        }
+    }
+}
+
+// Size with optional suffix, i.e. '1048576', '512K', '100M', '1G'
+fn parse_size(size: &str) -> Result<u64, String> {
+    let size = size.trim();
+    let (number, multiplier) = match size.chars().last().map(|c| c.to_ascii_uppercase()) {
+        Some('K') => (&size[.. size.len() - 1], 1024),
+        Some('M') => (&size[.. size.len() - 1], 1024 * 1024),
+        Some('G') => (&size[.. size.len() - 1], 1024 * 1024 * 1024),
+        _ => (size, 1)
+    };
+
+    return match number.trim().parse::<u64>() {
+        Ok(number) if number > 0 => Ok(number * multiplier),
+        _ => Err(format!("Invalid size '{}', expected positive number with optional suffix K, M or G, i.e. '100M'", size))
+    };
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
@@ -46,6 +198,48 @@ pub(crate) struct Dump {
     pub(crate) enabled: bool,
     pub(crate) verbosity: u8,
     pub(crate) color: bool,
+    // Query to choose pairs to print
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) filter: Option<String>,
+    #[serde(default)]
+    pub(crate) format: DumpFormat,
+    // Bodies are written as text too in JSON format, not only in base64
+    #[serde(default)]
+    pub(crate) decode_bodies: bool,
+    // Seconds to wait for response before request is dropped
+    #[serde(default = "default_uncompleted_ttl")]
+    pub(crate) uncompleted_ttl: u64,
+    // Seconds between checks for requests without response
+    #[serde(default = "default_cleanup_interval")]
+    pub(crate) cleanup_interval: u64,
+    // Line printed for every exchange in text format instead of request and response first lines
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) template: Option<String>,
+    // Sections to print in text format, verbosity decides if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) sections: Option<Vec<DumpSection>>,
+    // Header names, '*' is a wildcard. Allowed headers are always printed, denied ones never
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) headers_allow: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) headers_deny: Option<Vec<String>>,
+    // The first matching rule colors exchange
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) highlight: Option<Vec<HighlightRule>>,
+    // Output is written to stdout if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) output: Option<DumpOutput>,
+}
+
+pub(crate) const DEFAULT_UNCOMPLETED_TTL: u64 = 300;
+pub(crate) const DEFAULT_CLEANUP_INTERVAL: u64 = 600;
+
+fn default_uncompleted_ttl() -> u64 {
+    return DEFAULT_UNCOMPLETED_TTL;
+}
+
+fn default_cleanup_interval() -> u64 {
+    return DEFAULT_CLEANUP_INTERVAL;
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
@@ -58,7 +252,170 @@ pub(crate) struct Config {
     pub(crate) project: Option<String>,
     pub(crate) scope: Option<Scope>,
     pub(crate) dump_mode: Option<Dump>,
-    pub(crate) editor: Option<String>
+    pub(crate) editor: Option<String>,
+    pub(crate) noise: Option<Noise>,
+    // Local JSON API of running proxy, served in dump and daemon modes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) api: Option<Api>,
+    // External executables which get every request or response and can change, drop or tag it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) hooks: Option<Vec<Hook>>,
+    // Rhai scripts or directories with them, relative paths are in workplace
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) scripts: Option<Vec<String>>,
+    // Tokens got by login macros and put into requests of proxy and repeaters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) sessions: Option<Vec<SessionRule>>
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub(crate) struct Api {
+    // Address with port, i.e. 127.0.0.1:8099
+    pub(crate) address: String,
+    // Required as 'Authorization: Bearer <token>' if set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) token: Option<String>,
+}
+
+pub(crate) const DEFAULT_HOOK_TIMEOUT: u64 = 5000;
+
+fn default_hook_timeout() -> u64 {
+    return DEFAULT_HOOK_TIMEOUT;
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum HookStage {
+    Request,
+    Response,
+    #[default]
+    Both,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub(crate) struct Hook {
+    pub(crate) command: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) args: Vec<String>,
+    #[serde(default)]
+    pub(crate) on: HookStage,
+    // Hook gets only messages with URI in its own scope, not in the scope of Cruster
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) include: Option<Vec<ScopeRule>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) exclude: Option<Vec<ScopeRule>>,
+    // In milliseconds, message goes on unchanged if hook does not answer in time
+    #[serde(default = "default_hook_timeout")]
+    pub(crate) timeout: u64,
+}
+
+impl Hook {
+    pub(crate) fn get_scope(&self) -> Scope {
+        return Scope {
+            include_rules: self.include.clone(),
+            exclude_rules: self.exclude.clone(),
+            ..Scope::default()
+        };
+    }
+}
+
+pub(crate) const DEFAULT_SESSION_HEADER: &str = "authorization";
+pub(crate) const DEFAULT_SESSION_VALUE: &str = "Bearer {token}";
+pub(crate) const DEFAULT_SESSION_EXPIRED: &str = "status == 401";
+
+fn default_session_header() -> String {
+    return DEFAULT_SESSION_HEADER.to_string();
+}
+
+fn default_session_value() -> String {
+    return DEFAULT_SESSION_VALUE.to_string();
+}
+
+fn default_session_expired() -> String {
+    return DEFAULT_SESSION_EXPIRED.to_string();
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub(crate) struct SessionRule {
+    // Name or number of repeater in project with login request, it is the macro sent to get token
+    pub(crate) login: String,
+    // Token is the first group or the whole match in response to login, headers included
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) token_regex: Option<String>,
+    // Path of token in JSON body of response to login, i.e. 'data.access_token'
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) token_json: Option<String>,
+    #[serde(default = "default_session_header")]
+    pub(crate) header: String,
+    // '{token}' is replaced with token
+    #[serde(default = "default_session_value")]
+    pub(crate) value: String,
+    // Query for exchange, if it matches, token is got again and request is sent once more
+    #[serde(default = "default_session_expired")]
+    pub(crate) expired: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) include: Option<Vec<ScopeRule>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) exclude: Option<Vec<ScopeRule>>,
+}
+
+impl SessionRule {
+    fn has_include(&self) -> bool {
+        return self.include.as_ref().is_some_and(|include| !include.is_empty());
+    }
+
+    /// Token must not go to every host, so rule without its own 'include' is limited by scope of Cruster,
+    /// 'exclude' of rule is applied in both cases
+    pub(crate) fn get_scope(&self, config_scope: Option<&Scope>) -> Scope {
+        if self.has_include() {
+            return Scope {
+                include_rules: self.include.clone(),
+                exclude_rules: self.exclude.clone(),
+                ..Scope::default()
+            };
+        }
+
+        let mut scope = config_scope.cloned().unwrap_or_default();
+        if let Some(exclude) = self.exclude.as_ref() {
+            scope.exclude_rules.get_or_insert_with(Vec::default).extend(exclude.iter().cloned());
+        }
+
+        return scope;
+    }
+
+    /// Rule is checked before proxy is started, so compiled parts of it are not checked again
+    fn check(&self, config_scope: Option<&Scope>) -> Result<(), CrusterConfigError> {
+        if self.login.trim().is_empty() {
+            return Err(CrusterConfigError::from("Session rule must have 'login' repeater"));
+        }
+
+        match (self.token_regex.as_ref(), self.token_json.as_ref()) {
+            (Some(re), None) => {
+                regex::Regex::new(re)
+                    .map_err(|e| CrusterConfigError::from(format!("Cannot compile token regex '{}': {}", re, e)))?;
+            },
+            (None, Some(_)) => {},
+            _ => return Err(CrusterConfigError::from(format!("Session rule with login '{}' must have either 'token_regex' or 'token_json'", self.login)))
+        }
+
+        http::header::HeaderName::from_str(&self.header)
+            .map_err(|_| CrusterConfigError::from(format!("Invalid session header name '{}'", self.header)))?;
+
+        if !self.value.contains("{token}") {
+            return Err(CrusterConfigError::from(format!("Session header value '{}' must contain '{{token}}'", self.value)));
+        }
+
+        if let Err(e) = Query::from_str(&self.expired) {
+            return Err(CrusterConfigError::from(e.explain(&self.expired)));
+        }
+
+        let scope = ScopeMatcher::try_from(&self.get_scope(config_scope))?;
+        if !scope.has_include() {
+            return Err(CrusterConfigError::from(format!("Session rule with login '{}' must have 'include' rules or scope of Cruster must have them, otherwise token goes to every host", self.login)));
+        }
+
+        return Ok(());
+    }
 }
 
 impl Default for Dump {
@@ -66,7 +423,18 @@ impl Default for Dump {
         Dump {
             enabled: false,
             verbosity: 0,
-            color: true
+            color: true,
+            filter: None,
+            format: DumpFormat::Text,
+            decode_bodies: false,
+            uncompleted_ttl: DEFAULT_UNCOMPLETED_TTL,
+            cleanup_interval: DEFAULT_CLEANUP_INTERVAL,
+            template: None,
+            sections: None,
+            headers_allow: None,
+            headers_deny: None,
+            highlight: None,
+            output: None
         }
     }
 }
@@ -82,7 +450,12 @@ impl Default for Config {
             project: None,
             scope: None,
             dump_mode: None,
-            editor: None
+            editor: None,
+            noise: None,
+            api: None,
+            hooks: None,
+            scripts: None,
+            sessions: None
         }
     }
 }
@@ -98,12 +471,16 @@ fn parse_cmd() -> clap::ArgMatches {
     let strict_help = "If set, none of out-of-scope data will be written in storage, otherwise it will be just hidden from ui";
     let include_help = "Regex for URI to include in scope, i.e. ^https?://www\\.google\\.com/.*$. Option can repeat.";
     let exclude_help = "Regex for URI to exclude from scope, i.e. ^https?://www\\.google\\.com/.*$. Processed after include regex if any. Option can repeat.";
+    let scope_file_help = "File with scope of program to add to scope: list of domains and URLs ('!' excludes entry), Burp options JSON or CSV of assets with in/out scope column";
     let verbosity_help = "Verbosity in dump mode, ignored in intercative mode. 0: request/response first line, 
 1: 0 + response headers, 2: 1 + request headers, 3: 2 + response body, 4: 3 + request body";
     let nc_help = "Disable colorizing in dump mode, ignored in interactive mode";
     let filter_help = "Filter pairs in specifyied bounds with regular expression in format of 're2'";
+    let query_help = "Filter pairs with query, i.e. 'method = POST and res.status >= 400'. See docs/Query.md for syntax";
     let extract_help = "Extract pairs from range by attribute. parameter syntax: method=<name>|status=<value>|host=<prefix>|path=<prefix>";
     let editor_help = "Path to editor executable to use in CLI mode";
+    let dedupe_help = "Store only the first of requests with equal parts and count the rest, parts are comma-separated: method, url, path, body. Default: method,url,body";
+    let hide_static_help = "Hide images, fonts and styles, detected by extension or MIME type, from table and dump output";
 
     let matches = clap::Command::new("cruster")
         .version("0.7.2")
@@ -130,6 +507,127 @@ fn parse_cmd() -> clap::ArgMatches {
                         .action(clap::ArgAction::SetTrue)
                         .help(nc_help)
                 )
+                .arg(
+                    clap::Arg::new("filter")
+                        .short('f')
+                        .long("filter")
+                        .value_name("QUERY")
+                        .help("Print only pairs matching query, others are still stored. See docs/Query.md for syntax")
+                )
+                .arg(
+                    clap::Arg::new("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .value_parser(["text", "json"])
+                        .help("Output format: 'text' for humans or 'json' for one JSON object per exchange and WebSocket message (NDJSON), verbosity and colors are ignored for 'json'")
+                )
+                .arg(
+                    clap::Arg::new("decode-bodies")
+                        .long("decode-bodies")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Add bodies and WebSocket messages as text to JSON output, besides base64 ones")
+                )
+                .arg(
+                    clap::Arg::new("uncompleted-ttl")
+                        .long("uncompleted-ttl")
+                        .value_name("SECONDS")
+                        .value_parser(clap::value_parser!(u64))
+                        .help(format!("Seconds to wait for response before request is dropped, default: {}", DEFAULT_UNCOMPLETED_TTL))
+                )
+                .arg(
+                    clap::Arg::new("cleanup-interval")
+                        .long("cleanup-interval")
+                        .value_name("SECONDS")
+                        .value_parser(clap::value_parser!(u64))
+                        .help(format!("Seconds between checks for requests without response, default: {}", DEFAULT_CLEANUP_INTERVAL))
+                )
+                .arg(
+                    clap::Arg::new("template")
+                        .long("template")
+                        .short('t')
+                        .value_name("TEMPLATE")
+                        .help("Line to print for every exchange, i.e. '{id} {method} {host}{path} -> {status} {length}b {duration}ms'. Placeholders are fields of queries (docs/Query.md), 'duration', 'time' and 'tags'")
+                )
+                .arg(
+                    clap::Arg::new("sections")
+                        .long("sections")
+                        .value_name("SECTIONS")
+                        .help("Comma-separated sections to print instead of ones set by verbosity: request_headers, response_headers, request_body, response_body or 'none'")
+                )
+                .arg(
+                    clap::Arg::new("header-allow")
+                        .long("header-allow")
+                        .value_name("NAME")
+                        .action(clap::ArgAction::Append)
+                        .help("Header to print always, even if headers are not printed, '*' is a wildcard, i.e. 'Set-Cookie'. Option can repeat")
+                )
+                .arg(
+                    clap::Arg::new("header-deny")
+                        .long("header-deny")
+                        .value_name("NAME")
+                        .action(clap::ArgAction::Append)
+                        .help("Header to never print, '*' is a wildcard, i.e. 'Accept-*'. Option can repeat")
+                )
+                .arg(
+                    clap::Arg::new("highlight")
+                        .long("highlight")
+                        .value_name("COLOR:QUERY")
+                        .action(clap::ArgAction::Append)
+                        .help("Color exchanges matching query, i.e. 'red:status >= 500'. The first matching rule is used. Option can repeat")
+                )
+                .args(api_args())
+                .arg(
+                    clap::Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .value_name("FILE")
+                        .help("Write output to file instead of stdout, colors are disabled then. Errors are still written to stderr")
+                )
+                .arg(
+                    clap::Arg::new("rotate-size")
+                        .long("rotate-size")
+                        .value_name("SIZE")
+                        .value_parser(parse_size)
+                        .help("Rotate output file when it grows over size, i.e. '100M'. Suffixes K, M and G are supported")
+                )
+                .arg(
+                    clap::Arg::new("rotate-every")
+                        .long("rotate-every")
+                        .value_name("SECONDS")
+                        .value_parser(clap::value_parser!(u64).range(1..))
+                        .help("Rotate output file every SECONDS, i.e. 86400 for daily files")
+                )
+                .arg(
+                    clap::Arg::new("keep")
+                        .long("keep")
+                        .value_name("COUNT")
+                        .value_parser(clap::value_parser!(u32).range(1..))
+                        .help(format!("How many rotated output files to keep, default: {}", DEFAULT_KEEP_ROTATED))
+                )
+                .arg(
+                    clap::Arg::new("no-compress")
+                        .long("no-compress")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Do not compress rotated output files with gzip")
+                )
+        )
+        .subcommand(
+            clap::Command::new("daemon")
+                .about("Run headless proxy for automation: traffic is stored in project, SIGTERM or SIGINT stop it gracefully")
+                .args(api_args())
+                .arg(
+                    clap::Arg::new("pid-file")
+                        .long("pid-file")
+                        .value_name("FILE")
+                        .help("File to write process ID to, it is removed on shutdown")
+                )
+                .arg(
+                    clap::Arg::new("grace-period")
+                        .long("grace-period")
+                        .value_name("SECONDS")
+                        .value_parser(clap::value_parser!(u64))
+                        .help(format!("Seconds to wait for in-flight exchanges on shutdown, default: {}", DEFAULT_GRACE_PERIOD))
+                )
         )
         .subcommand(
             clap::Command::new("cli")
@@ -178,6 +676,19 @@ fn parse_cmd() -> clap::ArgMatches {
                                         .long("filter")
                                         .help(filter_help)
                                 )
+                                .arg(
+                                    clap::Arg::new("query")
+                                        .short('q')
+                                        .long("query")
+                                        .value_name("QUERY")
+                                        .help(query_help)
+                                )
+                                .arg(
+                                    clap::Arg::new("view")
+                                        .long("view")
+                                        .value_name("NAME")
+                                        .help("Filter pairs with query of view saved in project, see 'cruster project view'")
+                                )
                                 .arg(
                                     clap::Arg::new("extract")
                                         .short('e')
@@ -209,6 +720,90 @@ fn parse_cmd() -> clap::ArgMatches {
                                         .action(clap::ArgAction::SetTrue)
                                         .help("print ID with full URL")
                                 )
+                                .arg(
+                                    clap::Arg::new("query")
+                                        .short('q')
+                                        .long("query")
+                                        .value_name("QUERY")
+                                        .help(query_help)
+                                )
+                        )
+                        .subcommand(
+                            clap::Command::new("export-code")
+                                .alias("x")
+                                .about("Export request from HTTP history as ready-to-run code")
+                                .arg_required_else_help(true)
+                                .arg(
+                                    clap::Arg::new("index")
+                                        .required(true)
+                                        .value_name("NUMBER")
+                                        .help("Index of record in HTTP history to export")
+                                )
+                                .arg(
+                                    clap::Arg::new("lang")
+                                        .short('l')
+                                        .long("lang")
+                                        .value_parser(["curl", "python", "raw", "powershell"])
+                                        .help("Language to export request in, default: curl")
+                                )
+                        )
+                        .subcommand(
+                            clap::Command::new("sitemap")
+                                .alias("m")
+                                .about("Print hierarchical site map of captured hosts and paths")
+                                .arg(
+                                    clap::Arg::new("json")
+                                        .short('j')
+                                        .long("json")
+                                        .action(clap::ArgAction::SetTrue)
+                                        .help("Print site map as JSON instead of indented tree")
+                                )
+                        )
+                        .subcommand(
+                            clap::Command::new("params")
+                                .alias("p")
+                                .about("Print inventory of endpoints and their parameters (query, form, JSON, cookies, headers)")
+                                .arg(
+                                    clap::Arg::new("json")
+                                        .short('j')
+                                        .long("json")
+                                        .action(clap::ArgAction::SetTrue)
+                                        .help("Print inventory as JSON instead of table")
+                                )
+                        )
+                        .subcommand(
+                            clap::Command::new("diff")
+                                .alias("d")
+                                .about("Compare two responses (or requests) and print difference with similarity metrics")
+                                .arg_required_else_help(true)
+                                .arg(
+                                    clap::arg!(<LEFT> "Index of pair to compare, or 'r:<N>' to take Nth repeater")
+                                        .required(true)
+                                )
+                                .arg(
+                                    clap::arg!(<RIGHT> "Index of pair to compare with, or 'r:<N>' to take Nth repeater")
+                                        .required(true)
+                                )
+                                .arg(
+                                    clap::Arg::new("requests")
+                                        .short('q')
+                                        .long("requests")
+                                        .action(clap::ArgAction::SetTrue)
+                                        .help("Compare requests instead of responses")
+                                )
+                                .arg(
+                                    clap::Arg::new("words")
+                                        .short('w')
+                                        .long("words")
+                                        .action(clap::ArgAction::SetTrue)
+                                        .help("Show difference word by word instead of line by line")
+                                )
+                                .arg(
+                                    clap::Arg::new("no-color")
+                                        .long("nc")
+                                        .action(clap::ArgAction::SetTrue)
+                                        .help("Disable colorizing")
+                                )
                         )
                 )
                 .subcommand(
@@ -324,6 +919,157 @@ fn parse_cmd() -> clap::ArgMatches {
                         )
                 )
         )
+        .subcommand(
+            clap::Command::new("project")
+                .about("Manage project: metadata, statistics, merging and cleaning of stored data")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    clap::Command::new("init")
+                        .about("Initialize project with metadata")
+                        .arg(
+                            clap::Arg::new("name")
+                                .short('n')
+                                .long("name")
+                                .value_name("NAME")
+                                .help("Name of project, default is the name of project directory")
+                        )
+                        .arg(
+                            clap::Arg::new("description")
+                                .short('d')
+                                .long("description")
+                                .value_name("TEXT")
+                                .help("Description of project")
+                        )
+                        .arg(
+                            clap::Arg::new("force")
+                                .short('f')
+                                .long("force")
+                                .action(clap::ArgAction::SetTrue)
+                                .help("Rewrite metadata if project is initialized already")
+                        )
+                )
+                .subcommand(
+                    clap::Command::new("info")
+                        .about("Print project metadata, number of records, hosts, size and date range of captured data")
+                        .arg(
+                            clap::Arg::new("json")
+                                .short('j')
+                                .long("json")
+                                .action(clap::ArgAction::SetTrue)
+                                .help("Print info as JSON")
+                        )
+                )
+                .subcommand(
+                    clap::Command::new("merge")
+                        .about("Append HTTP data and repeaters of another project to this one, merged records get new IDs")
+                        .arg_required_else_help(true)
+                        .arg(
+                            clap::arg!(<SOURCE> "Path to directory of project to merge from")
+                                .required(true)
+                        )
+                        .arg(
+                            clap::Arg::new("no-repeaters")
+                                .long("no-repeaters")
+                                .action(clap::ArgAction::SetTrue)
+                                .help("Merge only HTTP data")
+                        )
+                )
+                .subcommand(
+                    clap::Command::new("prune")
+                        .about("Remove records from HTTP data by scope, filter or age")
+                        .arg_required_else_help(true)
+                        .arg(
+                            clap::Arg::new("out-of-scope")
+                                .short('s')
+                                .long("out-of-scope")
+                                .action(clap::ArgAction::SetTrue)
+                                .help("Remove records which do not fit scope set in config or with '-I'/'-E' options")
+                        )
+                        .arg(
+                            clap::Arg::new("filter")
+                                .short('f')
+                                .long("filter")
+                                .value_name("REGEX")
+                                .help("Remove records matching regex, it is matched as in 'cli http show -f'")
+                        )
+                        .arg(
+                            clap::Arg::new("older-than")
+                                .short('o')
+                                .long("older-than")
+                                .value_name("DURATION")
+                                .help("Remove records captured earlier than DURATION ago, i.e. 12h, 7d, 2w. Records without capture time are kept")
+                        )
+                        .arg(
+                            clap::Arg::new("dry-run")
+                                .long("dry-run")
+                                .action(clap::ArgAction::SetTrue)
+                                .help("Only print how many records would be removed")
+                        )
+                )
+                .subcommand(
+                    clap::Command::new("compact")
+                        .about("Remove duplicated records and rewrite HTTP data ordered by ID")
+                        .arg(
+                            clap::Arg::new("renumber")
+                                .long("renumber")
+                                .action(clap::ArgAction::SetTrue)
+                                .help("Assign sequential IDs to records starting from 0")
+                        )
+                )
+                .subcommand(
+                    clap::Command::new("encrypt")
+                        .about(
+                            format!(
+                                "Encrypt project data with passphrase, which is taken from {} environment variable or asked",
+                                crate::project::crypto::PASSPHRASE_ENV
+                            )
+                        )
+                )
+                .subcommand(
+                    clap::Command::new("decrypt")
+                        .about("Decrypt project data, so passphrase is not needed anymore")
+                )
+                .subcommand(
+                    clap::Command::new("compress")
+                        .about("Compress project data, every record separately, so it still can be appended")
+                )
+                .subcommand(
+                    clap::Command::new("decompress")
+                        .about("Decompress project data")
+                )
+                .subcommand(
+                    clap::Command::new("view")
+                        .about("Manage named filter queries (views) saved in project")
+                        .subcommand_required(true)
+                        .subcommand(
+                            clap::Command::new("list")
+                                .about("Print saved views, the active one is marked with '*'")
+                        )
+                        .subcommand(
+                            clap::Command::new("add")
+                                .about("Save view or replace query of existing one")
+                                .arg(clap::arg!(<NAME> "Name of view").required(true))
+                                .arg(clap::arg!(<QUERY> "Filter query, see docs/Query.md for syntax").required(true))
+                        )
+                        .subcommand(
+                            clap::Command::new("remove")
+                                .about("Remove saved view")
+                                .arg(clap::arg!(<NAME> "Name of view").required(true))
+                        )
+                )
+        )
+        .subcommand(
+            clap::Command::new("scope")
+                .about("Check scope set in config or with '-I'/'-E' options")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    clap::Command::new("test")
+                        .about("Explain why URL is in scope or out of it")
+                        .arg(clap::arg!(<URL> "Full URL to test, i.e. https://www.example.com/path?query").required(true))
+                )
+        )
         .arg(
             clap::Arg::new("workplace")
                 .short('W')
@@ -389,12 +1135,33 @@ fn parse_cmd() -> clap::ArgMatches {
                 .action(clap::ArgAction::Append)
                 .help(exclude_help)
         )
+        .arg(
+            clap::Arg::new("scope-file")
+                .long("scope-file")
+                .value_name("FILE")
+                .help(scope_file_help)
+        )
         .arg(
             clap::Arg::new("editor")
                 .long("editor")
                 .value_name("PATH_TO_EXECUTABLE")
                 .help(editor_help)
         )
+        .arg(
+            clap::Arg::new("dedupe")
+                .long("dedupe")
+                .value_name("PARTS")
+                .num_args(0..=1)
+                // Otherwise subcommand after '--dedupe' is taken as its value
+                .require_equals(true)
+                .help(dedupe_help)
+        )
+        .arg(
+            clap::Arg::new("hide-static")
+                .long("hide-static")
+                .action(clap::ArgAction::SetTrue)
+                .help(hide_static_help)
+        )
         .get_matches();
 
     return matches;
@@ -411,7 +1178,10 @@ pub(crate) fn handle_user_input() -> Result<(Config, CrusterMode), CrusterConfig
         match subcmd {
             "interactive" => CrusterMode::INTERACTIVE,
             "dump" => CrusterMode::DUMP(args.clone()),
+            "daemon" => CrusterMode::DAEMON(args.clone()),
             "cli" => CrusterMode::CLI(args.clone()),
+            "project" => CrusterMode::PROJECT(args.clone()),
+            "scope" => CrusterMode::SCOPE(args.clone()),
             _ => unreachable!()
         }
     }
@@ -535,15 +1305,21 @@ pub(crate) fn handle_user_input() -> Result<(Config, CrusterMode), CrusterConfig
     config.tls_cer_name = resolve_path(&workplace, &config.tls_cer_name, false)?;
     config.tls_key_name = resolve_path(&workplace, &config.tls_key_name, false)?;
 
+    // Scope edited in TUI is saved in project and replaces the one from config file, options below still override it
+    if let Some(project) = config.project.as_ref() {
+        if let Some(saved_scope) = crate::project::scope::load(project)? {
+            config.scope = Some(saved_scope);
+        }
+    }
+
     if matches.get_flag("strict-scope") {
         if let Some(scope) = config.scope.as_mut() {
             scope.strict = true;
         }
         else {
             config.scope = Some(Scope {
-                include: None,
-                exclude: None,
-                strict: true
+                strict: true,
+                ..Scope::default()
             });
         }
     }
@@ -580,8 +1356,7 @@ pub(crate) fn handle_user_input() -> Result<(Config, CrusterMode), CrusterConfig
         if let None = &config.scope {
             config.scope = Some(Scope {
                 include: include_scope,
-                exclude: None,
-                strict: false
+                ..Scope::default()
             });
         }
         else {
@@ -592,7 +1367,7 @@ pub(crate) fn handle_user_input() -> Result<(Config, CrusterMode), CrusterConfig
 
     if exclude_scope.is_some() {
         if let None = &config.scope {
-            config.scope = Some(Scope { include: None, exclude: exclude_scope, strict: false });
+            config.scope = Some(Scope { exclude: exclude_scope, ..Scope::default() });
         }
         else {
             let scope_ref = config.scope.as_mut().unwrap();
@@ -600,6 +1375,58 @@ pub(crate) fn handle_user_input() -> Result<(Config, CrusterMode), CrusterConfig
         }
     }
 
+    // Entries of program scope file are added once, so scope saved from TUI does not get them twice,
+    // and after -I/-E, which replace the lists and would drop imported entries otherwise
+    let scope_file = match matches.get_one::<String>("scope-file") {
+        Some(scope_file) => Some(scope_file.to_string()),
+        None => config.scope
+            .as_mut()
+            .and_then(|scope| scope.file.take())
+            .map(|scope_file| {
+                if path::Path::new(&scope_file).is_absolute() { scope_file } else { format!("{}/{}", workplace, scope_file) }
+            })
+    };
+
+    if let Some(scope_file) = scope_file {
+        let scope = config.scope.get_or_insert_with(Scope::default);
+        scope.file = None;
+        crate::scope::import::import(&scope_file, scope)?;
+    }
+
+    // Scope is compiled here once to report broken regexes and rules before anything is started
+    if let Some(scope) = config.scope.as_ref() {
+        ScopeMatcher::try_from(scope)?;
+    }
+
+    if matches.value_source("dedupe") == Some(clap::parser::ValueSource::CommandLine) {
+        let keys = match matches.get_one::<String>("dedupe") {
+            Some(parts) => parts
+                .split(',')
+                .filter(|part| !part.trim().is_empty())
+                .map(|part| part.parse::<DedupeKey>())
+                .collect::<Result<Vec<DedupeKey>, String>>()?,
+            None => noise::DEFAULT_DEDUPE_KEYS.to_vec()
+        };
+
+        config.noise.get_or_insert_with(Noise::default).dedupe = Some(keys);
+    }
+
+    if matches.get_flag("hide-static") {
+        let noise = config.noise.get_or_insert_with(Noise::default);
+        if noise.hide_extensions.is_none() {
+            noise.hide_extensions = Some(noise::DEFAULT_HIDDEN_EXTENSIONS.iter().map(|ext| ext.to_string()).collect());
+        }
+
+        if noise.hide_mime.is_none() {
+            noise.hide_mime = Some(noise::DEFAULT_HIDDEN_MIME.iter().map(|mime| mime.to_string()).collect());
+        }
+    }
+
+    // Daemon handles traffic as dump mode does, but output is written only to file set in config
+    if let CrusterMode::DAEMON(_) = &cmd {
+        config.dump_mode.get_or_insert_with(Dump::default).enabled = true;
+    }
+
     if let CrusterMode::DUMP(subcmd_args) = &cmd {
         if let Some(dm) = config.dump_mode.as_mut() {
             dm.enabled = true;
@@ -629,6 +1456,207 @@ pub(crate) fn handle_user_input() -> Result<(Config, CrusterMode), CrusterConfig
                 dm.color = false;
             }
         }
+
+        if let Some(filter) = subcmd_args.get_one::<String>("filter") {
+            if let Some(dm) = config.dump_mode.as_mut() {
+                dm.filter = Some(filter.clone());
+            }
+        }
+
+        if let Some(format) = subcmd_args.get_one::<String>("format") {
+            if let Some(dm) = config.dump_mode.as_mut() {
+                dm.format = if format == "json" { DumpFormat::Json } else { DumpFormat::Text };
+            }
+        }
+
+        if subcmd_args.get_flag("decode-bodies") {
+            if let Some(dm) = config.dump_mode.as_mut() {
+                dm.decode_bodies = true;
+            }
+        }
+
+        if let Some(ttl) = subcmd_args.get_one::<u64>("uncompleted-ttl") {
+            if let Some(dm) = config.dump_mode.as_mut() {
+                dm.uncompleted_ttl = *ttl;
+            }
+        }
+
+        if let Some(interval) = subcmd_args.get_one::<u64>("cleanup-interval") {
+            if let Some(dm) = config.dump_mode.as_mut() {
+                dm.cleanup_interval = *interval;
+            }
+        }
+
+        if let Some(template) = subcmd_args.get_one::<String>("template") {
+            if let Some(dm) = config.dump_mode.as_mut() {
+                dm.template = Some(template.clone());
+            }
+        }
+
+        if let Some(sections) = subcmd_args.get_one::<String>("sections") {
+            let sections = if sections.trim() == "none" {
+                Vec::default()
+            }
+            else {
+                sections
+                    .split(',')
+                    .map(DumpSection::from_str)
+                    .collect::<Result<Vec<DumpSection>, CrusterConfigError>>()?
+            };
+
+            if let Some(dm) = config.dump_mode.as_mut() {
+                dm.sections = Some(sections);
+            }
+        }
+
+        if let Some(allowed) = matches_to_vec(subcmd_args, "header-allow") {
+            if let Some(dm) = config.dump_mode.as_mut() {
+                dm.headers_allow = Some(allowed);
+            }
+        }
+
+        if let Some(denied) = matches_to_vec(subcmd_args, "header-deny") {
+            if let Some(dm) = config.dump_mode.as_mut() {
+                dm.headers_deny = Some(denied);
+            }
+        }
+
+        if let Some(rules) = matches_to_vec(subcmd_args, "highlight") {
+            let rules = rules
+                .iter()
+                .map(|rule| HighlightRule::from_str(rule))
+                .collect::<Result<Vec<HighlightRule>, CrusterConfigError>>()?;
+
+            if let Some(dm) = config.dump_mode.as_mut() {
+                dm.highlight = Some(rules);
+            }
+        }
+
+        if let Some(dm) = config.dump_mode.as_mut() {
+            if let Some(path) = subcmd_args.get_one::<String>("output") {
+                let output = dm.output.get_or_insert_with(|| DumpOutput::new(path.clone()));
+                output.path = path.clone();
+            }
+
+            let rotation_args = ["rotate-size", "rotate-every", "keep", "no-compress"];
+            let with_rotation_args = rotation_args
+                .iter()
+                .any(|arg| subcmd_args.value_source(arg) == Some(clap::parser::ValueSource::CommandLine));
+
+            match dm.output.as_mut() {
+                Some(output) => {
+                    if let Some(size) = subcmd_args.get_one::<u64>("rotate-size") {
+                        output.max_size = Some(*size);
+                    }
+
+                    if let Some(interval) = subcmd_args.get_one::<u64>("rotate-every") {
+                        output.rotate_every = Some(*interval);
+                    }
+
+                    if let Some(keep) = subcmd_args.get_one::<u32>("keep") {
+                        output.keep = *keep;
+                    }
+
+                    if subcmd_args.get_flag("no-compress") {
+                        output.compress = false;
+                    }
+                },
+                None if with_rotation_args => {
+                    return Err(CrusterConfigError::from("Options to rotate output require output file, set it with '--output'"));
+                },
+                None => {}
+            }
+        }
+    }
+
+    if let Some(template) = config.dump_mode.as_ref().and_then(|dm| dm.template.as_ref()) {
+        DumpTemplate::from_str(template)?;
+    }
+
+    if let Some(dm) = config.dump_mode.as_mut() {
+        if let Some(output) = dm.output.as_mut() {
+            if output.keep == 0 {
+                return Err(CrusterConfigError::from("Count of rotated output files to keep must be positive"));
+            }
+
+            output.path = resolve_path(&workplace, &output.path, false)?;
+            // Escape sequences are not needed in file
+            dm.color = false;
+        }
+    }
+
+    if let CrusterMode::DUMP(subcmd_args) | CrusterMode::DAEMON(subcmd_args) = &cmd {
+        if let Some(address) = subcmd_args.get_one::<String>("api") {
+            let api = config.api.get_or_insert_with(|| Api { address: address.clone(), token: None });
+            api.address = address.clone();
+        }
+
+        if let Some(token) = subcmd_args.get_one::<String>("api-token") {
+            match config.api.as_mut() {
+                Some(api) => api.token = Some(token.clone()),
+                None => return Err(CrusterConfigError::from("API token is set, but API address is not, set it with '--api'"))
+            }
+        }
+    }
+
+    if let Some(api) = config.api.as_ref() {
+        let address: std::net::SocketAddr = api.address
+            .parse()
+            .map_err(|_| CrusterConfigError::from(format!("Invalid API address '{}', expected address with port, i.e. 127.0.0.1:8099", api.address)))?;
+
+        // Anyone who reaches API can send requests from this host, so it is not open without token
+        if !address.ip().is_loopback() && api.token.is_none() {
+            return Err(CrusterConfigError::from(format!("API on non-loopback address '{}' requires token, set it with '--api-token'", api.address)));
+        }
+    }
+
+    // Scripts are loaded by proxy, so they are not created here as other relative paths are
+    for script in config.scripts.iter_mut().flatten() {
+        let expanded = tilde(script).to_string();
+        *script = if path::Path::new(&expanded).is_absolute() || expanded.starts_with("./") {
+            expanded
+        }
+        else {
+            format!("{}/{}", workplace, expanded)
+        };
+
+        if !path::Path::new(script).exists() {
+            return Err(CrusterConfigError::from(format!("Could not find script '{}'", script)));
+        }
+    }
+
+    for hook in config.hooks.iter().flatten() {
+        if hook.command.trim().is_empty() {
+            return Err(CrusterConfigError::from("Hook command must not be empty"));
+        }
+
+        if hook.timeout == 0 {
+            return Err(CrusterConfigError::from(format!("Timeout of hook '{}' must be positive", hook.command)));
+        }
+
+        ScopeMatcher::try_from(&hook.get_scope())?;
+    }
+
+    if let Some(sessions) = config.sessions.as_ref() {
+        // Login macros are repeaters of project
+        if !sessions.is_empty() && config.project.is_none() {
+            return Err(CrusterConfigError::from("Session rules require project with login repeaters, set it with '-P'"));
+        }
+
+        for rule in sessions {
+            rule.check(config.scope.as_ref())?;
+        }
+    }
+
+    for rule in config.dump_mode.iter().flat_map(|dm| dm.highlight.iter().flatten()) {
+        rule.check()?;
+    }
+
+    // Query is checked before proxy is started, not when the first pair is dumped
+    if let Some(filter) = config.dump_mode.as_ref().and_then(|dm| dm.filter.as_ref()) {
+        if let Err(e) = Query::from_str(filter) {
+            return Err(CrusterConfigError::from(e.explain(filter)));
+        }
     }
 
     if let Some(editor) = matches.get_one::<String>("editor") {
@@ -695,6 +1723,26 @@ fn enable_debug(debug_file_path: &str) {
 // }
 
 /// Return such path state, which is accessbile with cruster
+fn matches_to_vec(args: &ArgMatches, name: &str) -> Option<Vec<String>> {
+    return args
+        .get_many::<String>(name)
+        .map(|values| values.map(|value| value.to_string()).collect());
+}
+
+// Options of control API are the same for dump and daemon modes
+fn api_args() -> [clap::Arg; 2] {
+    return [
+        clap::Arg::new("api")
+            .long("api")
+            .value_name("ADDR:PORT")
+            .help("Serve local JSON API to query history, change scope, send repeaters and stream events, i.e. 127.0.0.1:8099. See docs/API.md"),
+        clap::Arg::new("api-token")
+            .long("api-token")
+            .value_name("TOKEN")
+            .help("Token to require in API requests as 'Authorization: Bearer <TOKEN>', random one is made if not set. It must be set for non-loopback address")
+    ];
+}
+
 fn resolve_path(base_path: &str, path: &str, dir: bool) -> Result<String, CrusterConfigError> {
     let fpath = path::Path::new(path);
     if fpath.is_absolute() {
@@ -1,5 +1,7 @@
 pub(crate) mod request_response;
 pub(super) mod events;
+pub(crate) mod hooks;
+pub(crate) mod scripts;
 
 use request_response::{
     HyperRequestWrapper,
@@ -17,6 +19,7 @@ use hudsucker::{
     RequestOrResponse,
 };
 use std::{
+    sync::Arc,
     net::SocketAddr,
     hash::{Hash, Hasher},
     collections::hash_map::DefaultHasher
@@ -30,6 +33,9 @@ use crate::CrusterError;
 use super::siv_ui::put_proxy_data_to_storage;
 use http::{Method, HeaderValue};
 use events::ProxyEvents;
+use hooks::{Hooks, HookVerdict};
+use scripts::Scripts;
+use crate::session::{self, Sessions};
 
 fn get_http_request_hash(client_addr: &SocketAddr, uri: &str, method: &str) -> usize {
     let mut hasher = DefaultHasher::new();
@@ -49,11 +55,19 @@ pub(crate) struct CrusterHandler {
     pub(crate) dump: bool,
     pub(crate) request_hash: usize,
     pub(crate) cursive_sink: CbSink,
+    pub(crate) hooks: Arc<Hooks>,
+    pub(crate) scripts: Arc<Scripts>,
+    pub(crate) sessions: Arc<Sessions>,
+    // Request is kept for hooks, scripts and sessions of response only
+    pub(crate) hooked_request: Option<HyperRequestWrapper>,
+    // Request as client sent it, kept to send it once more if session is expired
+    pub(crate) session_request: Option<HyperRequestWrapper>,
 }
 
 #[derive(Clone)]
 pub(crate) struct CrusterWSHandler {
-    pub(crate) proxy_tx: CrossbeamSender<ProxyEvents>
+    pub(crate) proxy_tx: CrossbeamSender<ProxyEvents>,
+    pub(crate) scripts: Arc<Scripts>,
 }
 
 #[async_trait]
@@ -81,10 +95,32 @@ impl HttpHandler for CrusterHandler {
 
                 self.request_hash = get_http_request_hash(&_ctx.client_addr, &wrapper.uri, &wrapper.method);
                 debug!("HTTP Request with id {}", &self.request_hash);
-                match self.send_request_to_storage(wrapper).await {
-                    Some(ror) => ror,
-                    None => RequestOrResponse::Request(new_req)
+
+                let mut new_req = new_req;
+                let mut tags: Vec<String> = Vec::default();
+                // Request is sent once more from scratch if session is expired, so it is kept as client sent it
+                let original = if self.sessions.is_empty() { None } else { Some(wrapper.clone()) };
+                let dropped = self.process_request(&mut wrapper, &mut new_req, &mut tags).await;
+
+                // Dropped request is not sent and not stored
+                if dropped {
+                    return RequestOrResponse::Response(hooks::make_dropped_response());
+                }
+
+                if self.hooks.on_response_enabled() || !self.scripts.is_empty() || !self.sessions.is_empty() {
+                    self.hooked_request = Some(wrapper.clone());
+                }
+
+                self.session_request = original;
+                if let Some(ror) = self.send_request_to_storage(wrapper).await {
+                    return ror;
+                }
+
+                if !tags.is_empty() {
+                    self.send_tags_to_storage(tags);
                 }
+
+                RequestOrResponse::Request(new_req)
             },
             Err(err) => {
                 self.send_error_message_from_request(err).await
@@ -95,7 +131,58 @@ impl HttpHandler for CrusterHandler {
     async fn handle_response(&mut self, _ctx: &HttpContext, res: Response<Body>) -> Response<Body> {
         debug!("HTTP Response with id {}", &self.request_hash);
         return match HyperResponseWrapper::from_hyper(res).await {
-            Ok((wrapper, new_res)) => {
+            Ok((mut wrapper, mut new_res)) => {
+                let mut tags: Vec<String> = Vec::default();
+                if let (Some(original), Some(sent)) = (self.session_request.take(), self.hooked_request.as_ref()) {
+                    match self.sessions.refresh_expired(sent, &wrapper).await {
+                        Ok(true) => {
+                            match self.resend_with_new_session(original).await {
+                                Ok(Some((request, response))) => {
+                                    match hooks::apply_to_response(&response, &mut new_res) {
+                                        Ok(_) => {
+                                            wrapper = response;
+                                            self.hooked_request = Some(request);
+                                            tags.push("session-refreshed".to_string());
+                                        },
+                                        Err(err) => self.send_hook_errors(vec![err])
+                                    }
+                                },
+                                Ok(None) => {},
+                                Err(err) => self.send_hook_errors(vec![err])
+                            }
+                        },
+                        Ok(false) => {},
+                        Err(err) => self.send_hook_errors(vec![err])
+                    }
+                }
+
+                if let Some(request) = self.hooked_request.take() {
+                    let (verdict, errors) = self.scripts.on_response(&request, &wrapper);
+                    self.send_hook_errors(errors);
+                    let mut dropped = self.apply_response_verdict(verdict, &mut wrapper, &mut new_res, &mut tags);
+
+                    if !dropped {
+                        let (verdict, errors) = self.hooks.process_response(&request, &wrapper).await;
+                        self.send_hook_errors(errors);
+                        dropped = self.apply_response_verdict(verdict, &mut wrapper, &mut new_res, &mut tags);
+                    }
+
+                    // Request is stored already, so it gets response client gets instead of dropped one
+                    if dropped {
+                        match HyperResponseWrapper::from_hyper(hooks::make_dropped_response()).await {
+                            Ok((dropped_wrapper, dropped_res)) => {
+                                wrapper = dropped_wrapper;
+                                new_res = dropped_res;
+                            },
+                            Err(err) => return self.send_error_message_from_response(err, self.request_hash).await
+                        }
+                    }
+                }
+
+                if !tags.is_empty() {
+                    self.send_tags_to_storage(tags);
+                }
+
                 match self.send_response_to_storage(wrapper).await {
                     Some(response) => response,
                     None => new_res
@@ -159,6 +246,117 @@ impl CrusterHandler {
         }
     }
 
+    /// Puts session headers into request, then applies scripts and hooks to it. Returns whether it is dropped
+    async fn process_request(&self, wrapper: &mut HyperRequestWrapper, req: &mut Request<Body>, tags: &mut Vec<String>) -> bool {
+        // Session headers go first, so hooks, i.e. signing ones, get request with token
+        match self.sessions.prepare(&wrapper.uri).await {
+            Ok(headers) => {
+                for (name, value) in headers {
+                    req.headers_mut().insert(name.clone(), value.clone());
+                    wrapper.headers.insert(name, value);
+                }
+            },
+            Err(err) => self.send_hook_errors(vec![err])
+        }
+
+        // Scripts go before hooks, so hooks get request with all changes
+        let (verdict, errors) = self.scripts.on_request(wrapper);
+        self.send_hook_errors(errors);
+        if self.apply_request_verdict(verdict, wrapper, req, tags) {
+            return true;
+        }
+
+        let (verdict, errors) = self.hooks.process_request(wrapper).await;
+        self.send_hook_errors(errors);
+        return self.apply_request_verdict(verdict, wrapper, req, tags);
+    }
+
+    /// Request is prepared from scratch with new token and sent by Cruster itself. Client gets the new response,
+    /// but history keeps the first request. Returns nothing if scripts or hooks drop request this time
+    async fn resend_with_new_session(&self, original: HyperRequestWrapper) -> Result<Option<(HyperRequestWrapper, HyperResponseWrapper)>, CrusterError> {
+        let mut request = original;
+        let mut scratch = Request::default();
+        // Tags are added to pair with the first request already
+        let mut tags: Vec<String> = Vec::default();
+        if self.process_request(&mut request, &mut scratch, &mut tags).await {
+            return Ok(None);
+        }
+
+        let response = session::resend(&request).await?;
+        return Ok(Some((request, response)));
+    }
+
+    /// Puts changes of scripts or hooks into request and returns whether it is dropped
+    fn apply_request_verdict(&self, verdict: HookVerdict<HyperRequestWrapper>, wrapper: &mut HyperRequestWrapper, req: &mut Request<Body>, tags: &mut Vec<String>) -> bool {
+        return match verdict {
+            HookVerdict::Forward(changed, verdict_tags) => {
+                if let Some(changed) = changed {
+                    match hooks::apply_to_request(&changed, req) {
+                        Ok(_) => *wrapper = changed,
+                        Err(err) => self.send_hook_errors(vec![err])
+                    }
+                }
+
+                tags.extend(verdict_tags);
+                false
+            },
+            HookVerdict::Drop(_) => true
+        };
+    }
+
+    /// Puts changes of scripts or hooks into response and returns whether it is dropped
+    fn apply_response_verdict(&self, verdict: HookVerdict<HyperResponseWrapper>, wrapper: &mut HyperResponseWrapper, res: &mut Response<Body>, tags: &mut Vec<String>) -> bool {
+        return match verdict {
+            HookVerdict::Forward(changed, verdict_tags) => {
+                if let Some(changed) = changed {
+                    match hooks::apply_to_response(&changed, res) {
+                        Ok(_) => *wrapper = changed,
+                        Err(err) => self.send_hook_errors(vec![err])
+                    }
+                }
+
+                tags.extend(verdict_tags);
+                false
+            },
+            HookVerdict::Drop(verdict_tags) => {
+                tags.extend(verdict_tags);
+                true
+            }
+        };
+    }
+
+    fn sync_with_ui(&self) {
+        if !self.dump {
+            self.cursive_sink.send(
+                Box::new(
+                    |siv: &mut Cursive| {
+                        put_proxy_data_to_storage(siv);
+                    }
+                )
+            ).expect("FATAL: proxy could not sync with ui, while sending hook results!");
+        }
+    }
+
+    /// Tags are sent while pair waits for response, so they are in place when pair is completed
+    fn send_tags_to_storage(&self, tags: Vec<String>) {
+        if let Err(err) = self.proxy_tx.send(ProxyEvents::Annotated((tags, self.request_hash))) {
+            panic!("FATAL: cannot communicate between threads: {}", err);
+        }
+
+        self.sync_with_ui();
+    }
+
+    /// Failed hooks and scripts are reported, but message goes on, so they do not break traffic
+    fn send_hook_errors(&self, errors: Vec<CrusterError>) {
+        for err in errors {
+            if let Err(send_err) = self.proxy_tx.send(ProxyEvents::Error((err, None))) {
+                panic!("FATAL: cannot communicate between threads: {}", send_err);
+            }
+
+            self.sync_with_ui();
+        }
+    }
+
     async fn send_error_message_from_request(&self, err: CrusterError) -> RequestOrResponse {
         let err_send_result = self.proxy_tx.send(ProxyEvents::Error((err, None)));
         match err_send_result {
@@ -181,6 +379,15 @@ impl CrusterHandler {
 #[async_trait]
 impl WebSocketHandler for CrusterWSHandler {
     async fn handle_message(&mut self, _ctx: &WebSocketContext, msg: Message) -> Option<Message> {
+        let (msg, errors) = self.scripts.on_ws_message(_ctx, msg);
+        for err in errors {
+            if let Err(send_err) = self.proxy_tx.send(ProxyEvents::Error((err, None))) {
+                panic!("FATAL! Could not send error over crossbeam channel: {}", send_err);
+            }
+        }
+
+        // Message dropped by script is not stored
+        let msg = msg?;
         let res = self.proxy_tx.send(
             ProxyEvents::WebSocketMessageSent((_ctx.clone(), msg.clone()))
         );
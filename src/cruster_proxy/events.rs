@@ -9,5 +9,7 @@ pub(crate) enum ProxyEvents {
     RequestSent((request_response::HyperRequestWrapper, usize)),
     ResponseSent((request_response::HyperResponseWrapper, usize)),
     WebSocketMessageSent((WebSocketContext, Message)),
+    // Tags given by hooks to pair of request with this hash, it is sent before response
+    Annotated((Vec<String>, usize)),
     Error((CrusterError, Option<usize>))
 }
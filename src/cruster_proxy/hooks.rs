@@ -0,0 +1,262 @@
+use std::process::Stdio;
+use std::time::Duration;
+
+use hudsucker::hyper::{Body, Request, Response, StatusCode};
+use http::{HeaderValue, Method, Uri};
+use serde::{Serialize, Deserialize};
+use serde_json as json;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use super::request_response::{HyperRequestWrapper, HyperResponseWrapper};
+use crate::config::{Hook, HookStage};
+use crate::http_storage::serializable::{SerializableHTTPRequest, SerializableHTTPResponse};
+use crate::scope::ScopeMatcher;
+use crate::utils::CrusterError;
+
+#[derive(Serialize)]
+struct HookInput<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    request: &'a SerializableHTTPRequest,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response: Option<&'a SerializableHTTPResponse>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+enum HookAction {
+    #[default]
+    Forward,
+    Drop,
+}
+
+// Empty output of hook means the message goes on as is
+#[derive(Deserialize, Default)]
+struct HookOutput {
+    #[serde(default)]
+    action: HookAction,
+    #[serde(default)]
+    request: Option<SerializableHTTPRequest>,
+    #[serde(default)]
+    response: Option<SerializableHTTPResponse>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// What hooks did with message
+pub(crate) enum HookVerdict<T> {
+    // Changed message if any and tags for its pair
+    Forward(Option<T>, Vec<String>),
+    Drop(Vec<String>),
+}
+
+struct CompiledHook {
+    hook: Hook,
+    scope: ScopeMatcher,
+}
+
+impl CompiledHook {
+    /// Hook is started for every message, so it can be any executable without special loop inside
+    async fn run(&self, input: Vec<u8>) -> Result<HookOutput, CrusterError> {
+        let mut child = Command::new(&self.hook.command)
+            .args(&self.hook.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|err| CrusterError::IOError(format!("Could not start hook '{}': {}", self.hook.command, err)))?;
+
+        let mut stdin = child.stdin.take().unwrap();
+        // Input is written along with reading output, so large messages do not stuck in pipes
+        let write_input = async move {
+            let _ = stdin.write_all(&input).await;
+        };
+
+        let (_, output) = tokio::join!(write_input, child.wait_with_output());
+        let output = output?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(CrusterError::UndefinedError(
+                format!("Hook '{}' failed with {}: {}", self.hook.command, output.status, stderr.trim())
+            ));
+        }
+
+        if output.stdout.iter().all(u8::is_ascii_whitespace) {
+            return Ok(HookOutput::default());
+        }
+
+        return json::from_slice(&output.stdout)
+            .map_err(|err| CrusterError::JSONError(format!("Could not parse output of hook '{}': {}", self.hook.command, err)));
+    }
+
+    async fn run_with_timeout(&self, input: Vec<u8>) -> Result<HookOutput, CrusterError> {
+        // Process is killed when its future is dropped by timeout
+        return match tokio::time::timeout(Duration::from_millis(self.hook.timeout), self.run(input)).await {
+            Ok(result) => result,
+            Err(_) => Err(CrusterError::JobDurateTooLongError(
+                format!("Hook '{}' did not answer in {} ms", self.hook.command, self.hook.timeout)
+            ))
+        };
+    }
+
+    fn is_applied(&self, stage: HookStage, uri: &str) -> bool {
+        let fit_stage = self.hook.on == HookStage::Both || self.hook.on == stage;
+        return fit_stage && self.scope.fits(uri);
+    }
+}
+
+/// Hooks from config, they are applied one by one in order they are written,
+/// so every next hook gets message changed by previous ones
+#[derive(Default)]
+pub(crate) struct Hooks {
+    hooks: Vec<CompiledHook>,
+}
+
+impl From<Option<&Vec<Hook>>> for Hooks {
+    fn from(hooks: Option<&Vec<Hook>>) -> Self {
+        let hooks = hooks
+            .into_iter()
+            .flatten()
+            .map(|hook| {
+                CompiledHook {
+                    scope: ScopeMatcher::from_config(Some(&hook.get_scope())),
+                    hook: hook.clone()
+                }
+            })
+            .collect();
+
+        return Hooks { hooks };
+    }
+}
+
+impl Hooks {
+    pub(crate) fn on_response_enabled(&self) -> bool {
+        return self.hooks.iter().any(|hook| hook.hook.on != HookStage::Request);
+    }
+
+    /// Failed hook does not stop message, its error is returned along with verdict of other hooks.
+    /// Verdict has request only if some hook changed it
+    pub(crate) async fn process_request(&self, request: &HyperRequestWrapper) -> (HookVerdict<HyperRequestWrapper>, Vec<CrusterError>) {
+        let mut changed: Option<HyperRequestWrapper> = None;
+        let mut tags: Vec<String> = Vec::default();
+        let mut errors: Vec<CrusterError> = Vec::default();
+
+        for hook in self.hooks.iter().filter(|hook| hook.is_applied(HookStage::Request, &request.uri)) {
+            let serializable = SerializableHTTPRequest::from(changed.as_ref().unwrap_or(request));
+            let input = HookInput { kind: "request", request: &serializable, response: None };
+            let output = match json::to_vec(&input) {
+                Ok(input) => hook.run_with_timeout(input).await,
+                Err(err) => Err(err.into())
+            };
+
+            let output = match output {
+                Ok(output) => output,
+                Err(err) => {
+                    errors.push(err);
+                    continue;
+                }
+            };
+
+            tags.extend(output.tags);
+            if output.action == HookAction::Drop {
+                return (HookVerdict::Drop(tags), errors);
+            }
+
+            if let Some(request) = output.request {
+                match request.try_into() {
+                    Ok(request) => changed = Some(request),
+                    Err(err) => errors.push(err)
+                }
+            }
+        }
+
+        return (HookVerdict::Forward(changed, tags), errors);
+    }
+
+    /// Request is given to hooks to know what response is for, changes of it are ignored
+    pub(crate) async fn process_response(&self, request: &HyperRequestWrapper, response: &HyperResponseWrapper) -> (HookVerdict<HyperResponseWrapper>, Vec<CrusterError>) {
+        let mut changed: Option<HyperResponseWrapper> = None;
+        let mut tags: Vec<String> = Vec::default();
+        let mut errors: Vec<CrusterError> = Vec::default();
+        let serializable_request = SerializableHTTPRequest::from(request);
+
+        for hook in self.hooks.iter().filter(|hook| hook.is_applied(HookStage::Response, &request.uri)) {
+            let serializable = SerializableHTTPResponse::from(changed.as_ref().unwrap_or(response));
+            let input = HookInput { kind: "response", request: &serializable_request, response: Some(&serializable) };
+            let output = match json::to_vec(&input) {
+                Ok(input) => hook.run_with_timeout(input).await,
+                Err(err) => Err(err.into())
+            };
+
+            let output = match output {
+                Ok(output) => output,
+                Err(err) => {
+                    errors.push(err);
+                    continue;
+                }
+            };
+
+            tags.extend(output.tags);
+            if output.action == HookAction::Drop {
+                return (HookVerdict::Drop(tags), errors);
+            }
+
+            if let Some(response) = output.response {
+                match response.try_into() {
+                    Ok(response) => changed = Some(response),
+                    Err(err) => errors.push(err)
+                }
+            }
+        }
+
+        return (HookVerdict::Forward(changed, tags), errors);
+    }
+}
+
+/// Hook could change body without fixing its length, so it is set here if message has it
+fn fix_content_length(headers: &mut http::HeaderMap, body: &[u8]) {
+    if headers.contains_key("content-length") {
+        headers.insert("content-length", HeaderValue::from(body.len()));
+    }
+}
+
+/// Puts changes of wrapper into request, version and extensions of it are kept.
+/// Request is left untouched if changes are invalid
+pub(crate) fn apply_to_request(wrapper: &HyperRequestWrapper, request: &mut Request<Body>) -> Result<(), CrusterError> {
+    let method = Method::from_bytes(wrapper.method.as_bytes())?;
+    let uri = wrapper.uri
+        .parse::<Uri>()
+        .map_err(|err| CrusterError::HTTPBuildingError(format!("Invalid URI '{}' from hook: {}", wrapper.uri, err)))?;
+
+    *request.method_mut() = method;
+    *request.uri_mut() = uri;
+    *request.headers_mut() = wrapper.headers.clone();
+    fix_content_length(request.headers_mut(), &wrapper.body);
+    *request.body_mut() = Body::from(wrapper.body.clone());
+
+    Ok(())
+}
+
+/// Puts changes of wrapper into response, status is taken from code in the beginning of status line
+pub(crate) fn apply_to_response(wrapper: &HyperResponseWrapper, response: &mut Response<Body>) -> Result<(), CrusterError> {
+    let code = wrapper.status.split(' ').next().unwrap_or_default();
+    let status = StatusCode::from_bytes(code.as_bytes())
+        .map_err(|err| CrusterError::HTTPBuildingError(format!("Invalid status '{}' from hook: {}", wrapper.status, err)))?;
+
+    *response.status_mut() = status;
+    *response.headers_mut() = wrapper.headers.clone();
+    fix_content_length(response.headers_mut(), &wrapper.body);
+    *response.body_mut() = Body::from(wrapper.body.clone());
+
+    Ok(())
+}
+
+/// What client gets instead of dropped message
+pub(crate) fn make_dropped_response() -> Response<Body> {
+    let mut response = Response::new(Body::from("Message is dropped by Cruster\n"));
+    *response.status_mut() = StatusCode::BAD_GATEWAY;
+    return response;
+}
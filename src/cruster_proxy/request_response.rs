@@ -80,6 +80,40 @@ impl Display for HyperRequestWrapper {
     }
 }
 
+impl From<&reqwest::Request> for HyperRequestWrapper {
+    fn from(req: &reqwest::Request) -> Self {
+        let uri = req.url().to_string();
+        let method = req.method().to_string();
+        let headers = req.headers().clone();
+
+        let version = match req.version() {
+            reqwest::Version::HTTP_11 => "HTTP/1.1".to_string(),
+            reqwest::Version::HTTP_09 => "HTTP/0.1".to_string(),
+            reqwest::Version::HTTP_10 => "HTTP/1.0".to_string(),
+            reqwest::Version::HTTP_2 => "HTTP/2".to_string(),
+            reqwest::Version::HTTP_3 => "HTTP/2".to_string(),
+            _ => "HTTP/UNKNOWN".to_string()
+        };
+
+        let body: Vec<u8> = match req.body() {
+            Some(body) => {
+                body.as_bytes().unwrap().to_vec()
+            },
+            None => {
+                vec![]
+            }
+        };
+
+        HyperRequestWrapper {
+            uri,
+            method,
+            version,
+            headers,
+            body
+        }
+    }
+}
+
 impl HyperRequestWrapper {
     pub(crate) async fn from_hyper(req: Request<Body>) -> Result<(Self, Request<Body>), CrusterError> {
         let (parts, body) = req.into_parts();
@@ -117,37 +151,7 @@ impl HyperRequestWrapper {
     }
 
     pub(crate) async fn from_reqwest(req: reqwest::Request) -> Result<Self, CrusterError> {
-        let uri = req.url().to_string();
-        let method = req.method().to_string();
-        let headers = req.headers().clone();
-
-        let version = match req.version() {
-            reqwest::Version::HTTP_11 => "HTTP/1.1".to_string(),
-            reqwest::Version::HTTP_09 => "HTTP/0.1".to_string(),
-            reqwest::Version::HTTP_10 => "HTTP/1.0".to_string(),
-            reqwest::Version::HTTP_2 => "HTTP/2".to_string(),
-            reqwest::Version::HTTP_3 => "HTTP/2".to_string(),
-            _ => "HTTP/UNKNOWN".to_string()
-        };
-
-        let body: Vec<u8> = match req.body() {
-            Some(body) => {
-                body.as_bytes().unwrap().to_vec()
-            },
-            None => {
-                vec![]
-            }
-        };
-
-        let wrapper = HyperRequestWrapper {
-            uri,
-            method,
-            version,
-            headers,
-            body
-        };
-
-        Ok(wrapper)
+        Ok(HyperRequestWrapper::from(&req))
     }
 
     pub(crate) fn get_request_path(&self) -> String {
@@ -247,11 +251,16 @@ impl HyperRequestWrapper {
             return true;
         }
 
+        // Header line is built in the same buffer, not to allocate for each header
+        let mut hl = String::with_capacity(128);
         let found_in_headers: bool = self.headers
             .iter()
             .any(|(k, v)| {
-                let hl = format!("{}: {}", k.as_str(), v.as_bytes().to_str_lossy().as_ref());
-                re.find(&hl).is_some()
+                hl.clear();
+                hl.push_str(k.as_str());
+                hl.push_str(": ");
+                hl.push_str(v.as_bytes().to_str_lossy().as_ref());
+                re.is_match(&hl)
             });
         
         if found_in_headers {
@@ -422,11 +431,16 @@ impl HyperResponseWrapper {
             return true;
         }
 
+        // Header line is built in the same buffer, not to allocate for each header
+        let mut hl = String::with_capacity(128);
         let found_in_headers: bool = self.headers
             .iter()
             .any(|(k, v)| {
-                let hl = format!("{}: {}", k.as_str(), v.as_bytes().to_str_lossy().as_ref());
-                re.find(&hl).is_some()
+                hl.clear();
+                hl.push_str(k.as_str());
+                hl.push_str(": ");
+                hl.push_str(v.as_bytes().to_str_lossy().as_ref());
+                re.is_match(&hl)
             });
         
         if found_in_headers {
@@ -0,0 +1,395 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use hudsucker::{tokio_tungstenite::tungstenite::Message, WebSocketContext};
+use http::{HeaderMap, HeaderValue, StatusCode, header::HeaderName};
+use log::debug;
+use rhai::{Blob, CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+
+use super::hooks::HookVerdict;
+use super::request_response::{HyperRequestWrapper, HyperResponseWrapper};
+use crate::utils::CrusterError;
+
+// Changes of script files are checked no more often than this
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+// Script with endless loop must not hang the proxy
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+const ON_REQUEST: &str = "on_request";
+const ON_RESPONSE: &str = "on_response";
+const ON_WS_MESSAGE: &str = "on_ws_message";
+
+/// Request as scripts see it, callbacks change it through 'this'
+#[derive(Clone)]
+struct ScriptRequest {
+    wrapper: HyperRequestWrapper,
+    tags: Vec<String>,
+    dropped: bool,
+}
+
+#[derive(Clone)]
+struct ScriptResponse {
+    wrapper: HyperResponseWrapper,
+    // Copy of request the response is for, its changes are ignored
+    request: ScriptRequest,
+    tags: Vec<String>,
+    dropped: bool,
+}
+
+#[derive(Clone)]
+struct ScriptWsMessage {
+    message: Message,
+    uri: String,
+    from_client: bool,
+    dropped: bool,
+}
+
+fn get_header(headers: &HeaderMap, name: &str) -> Dynamic {
+    return match headers.get(name) {
+        Some(value) => Dynamic::from(String::from_utf8_lossy(value.as_bytes()).to_string()),
+        None => Dynamic::UNIT
+    };
+}
+
+fn set_header(headers: &mut HeaderMap, name: &str, value: &str) -> Result<(), Box<EvalAltResult>> {
+    let name = HeaderName::from_bytes(name.as_bytes()).map_err(|err| format!("Invalid header name '{}': {}", name, err))?;
+    let value = HeaderValue::from_str(value).map_err(|err| format!("Invalid value of header '{}': {}", name, err))?;
+    headers.insert(name, value);
+    Ok(())
+}
+
+// Values of repeated header are joined with comma
+fn get_headers(headers: &HeaderMap) -> Map {
+    let mut map = Map::new();
+    for name in headers.keys() {
+        let value = headers
+            .get_all(name)
+            .iter()
+            .map(|value| String::from_utf8_lossy(value.as_bytes()).to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        map.insert(name.as_str().into(), Dynamic::from(value));
+    }
+
+    return map;
+}
+
+fn make_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    // Standard output belongs to TUI or dump output
+    engine.on_print(|text| debug!("Script: {}", text));
+    engine.on_debug(|text, source, pos| debug!("Script {} at {}: {}", source.unwrap_or_default(), pos, text));
+
+    engine
+        .register_type_with_name::<ScriptRequest>("Request")
+        .register_get_set("method", |req: &mut ScriptRequest| req.wrapper.method.clone(), |req: &mut ScriptRequest, method: String| req.wrapper.method = method)
+        .register_get_set("uri", |req: &mut ScriptRequest| req.wrapper.uri.clone(), |req: &mut ScriptRequest, uri: String| req.wrapper.uri = uri)
+        .register_get_set("version", |req: &mut ScriptRequest| req.wrapper.version.clone(), |req: &mut ScriptRequest, version: String| req.wrapper.version = version)
+        .register_get_set("body", |req: &mut ScriptRequest| String::from_utf8_lossy(&req.wrapper.body).to_string(), |req: &mut ScriptRequest, body: String| req.wrapper.body = body.into_bytes())
+        .register_get_set("body_bytes", |req: &mut ScriptRequest| -> Blob { req.wrapper.body.clone() }, |req: &mut ScriptRequest, body: Blob| req.wrapper.body = body)
+        .register_get("host", |req: &mut ScriptRequest| req.wrapper.get_host())
+        .register_get("path", |req: &mut ScriptRequest| req.wrapper.get_request_path())
+        .register_get("headers", |req: &mut ScriptRequest| get_headers(&req.wrapper.headers))
+        .register_fn("header", |req: &mut ScriptRequest, name: &str| get_header(&req.wrapper.headers, name))
+        .register_fn("set_header", |req: &mut ScriptRequest, name: &str, value: &str| set_header(&mut req.wrapper.headers, name, value))
+        .register_fn("remove_header", |req: &mut ScriptRequest, name: &str| { req.wrapper.headers.remove(name); })
+        .register_fn("tag", |req: &mut ScriptRequest, tag: &str| req.tags.push(tag.to_string()))
+        .register_fn("drop", |req: &mut ScriptRequest| req.dropped = true);
+
+    engine
+        .register_type_with_name::<ScriptResponse>("Response")
+        .register_get_set(
+            "status",
+            |res: &mut ScriptResponse| res.wrapper.status.split(' ').next().unwrap_or_default().parse::<i64>().unwrap_or_default(),
+            |res: &mut ScriptResponse, status: i64| -> Result<(), Box<EvalAltResult>> {
+                let status = u16::try_from(status)
+                    .ok()
+                    .and_then(|status| StatusCode::from_u16(status).ok())
+                    .ok_or_else(|| format!("Invalid status {}", status))?;
+
+                res.wrapper.status = status.to_string();
+                Ok(())
+            }
+        )
+        .register_get_set("version", |res: &mut ScriptResponse| res.wrapper.version.clone(), |res: &mut ScriptResponse, version: String| res.wrapper.version = version)
+        .register_get_set("body", |res: &mut ScriptResponse| String::from_utf8_lossy(&res.wrapper.body).to_string(), |res: &mut ScriptResponse, body: String| res.wrapper.body = body.into_bytes())
+        .register_get_set("body_bytes", |res: &mut ScriptResponse| -> Blob { res.wrapper.body.clone() }, |res: &mut ScriptResponse, body: Blob| res.wrapper.body = body)
+        .register_get("request", |res: &mut ScriptResponse| res.request.clone())
+        .register_get("headers", |res: &mut ScriptResponse| get_headers(&res.wrapper.headers))
+        .register_fn("header", |res: &mut ScriptResponse, name: &str| get_header(&res.wrapper.headers, name))
+        .register_fn("set_header", |res: &mut ScriptResponse, name: &str, value: &str| set_header(&mut res.wrapper.headers, name, value))
+        .register_fn("remove_header", |res: &mut ScriptResponse, name: &str| { res.wrapper.headers.remove(name); })
+        .register_fn("tag", |res: &mut ScriptResponse, tag: &str| res.tags.push(tag.to_string()))
+        .register_fn("drop", |res: &mut ScriptResponse| res.dropped = true);
+
+    engine
+        .register_type_with_name::<ScriptWsMessage>("WsMessage")
+        .register_get_set(
+            "text",
+            |msg: &mut ScriptWsMessage| msg.message.to_text().map(|text| text.to_string()).unwrap_or_default(),
+            |msg: &mut ScriptWsMessage, text: String| msg.message = Message::Text(text)
+        )
+        .register_get_set("bytes", |msg: &mut ScriptWsMessage| -> Blob { msg.message.clone().into_data() }, |msg: &mut ScriptWsMessage, data: Blob| msg.message = Message::Binary(data))
+        .register_get("is_text", |msg: &mut ScriptWsMessage| msg.message.is_text())
+        .register_get("is_binary", |msg: &mut ScriptWsMessage| msg.message.is_binary())
+        .register_get("uri", |msg: &mut ScriptWsMessage| msg.uri.clone())
+        .register_get("from_client", |msg: &mut ScriptWsMessage| msg.from_client)
+        .register_fn("drop", |msg: &mut ScriptWsMessage| msg.dropped = true);
+
+    return engine;
+}
+
+struct LoadedScript {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    ast: AST,
+    callbacks: Vec<String>,
+}
+
+impl LoadedScript {
+    fn compile(engine: &Engine, path: &Path, modified: Option<SystemTime>) -> Result<Self, CrusterError> {
+        let ast = engine
+            .compile_file(path.to_path_buf())
+            .map_err(|err| CrusterError::ScriptError(format!("Could not load script '{}': {}", path.display(), err)))?;
+
+        // Callbacks take message as 'this', not as parameter
+        let callbacks = ast
+            .iter_functions()
+            .filter(|func| func.params.is_empty())
+            .map(|func| func.name.to_string())
+            .collect();
+
+        return Ok(LoadedScript { path: path.to_path_buf(), modified, ast, callbacks });
+    }
+
+    fn has_callback(&self, name: &str) -> bool {
+        return self.callbacks.iter().any(|callback| callback == name);
+    }
+
+    /// Message is changed only if callback succeeded
+    fn call<T: Clone + Send + Sync + 'static>(&self, engine: &Engine, name: &str, message: &mut T) -> Result<(), CrusterError> {
+        let mut this = Dynamic::from(message.clone());
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut this);
+
+        // Result of callback is not used, message is changed in place
+        let _ = engine
+            .call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &self.ast, name, ())
+            .map_err(|err| CrusterError::ScriptError(format!("Script '{}' failed in {}: {}", self.path.display(), name, err)))?;
+
+        if let Some(changed) = this.try_cast::<T>() {
+            *message = changed;
+        }
+
+        Ok(())
+    }
+}
+
+// Directory stands for '*.rhai' files in it, they are taken in order of names
+fn list_script_files(sources: &[String]) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = Vec::default();
+    for source in sources {
+        let path = PathBuf::from(source);
+        if !path.is_dir() {
+            files.push(path);
+            continue;
+        }
+
+        let mut dir_files: Vec<PathBuf> = fs::read_dir(&path)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "rhai"))
+            .collect();
+
+        dir_files.sort();
+        files.extend(dir_files);
+    }
+
+    return files;
+}
+
+fn get_modified(path: &Path) -> Option<SystemTime> {
+    return fs::metadata(path).and_then(|meta| meta.modified()).ok();
+}
+
+/// Rhai scripts from config. They are compiled once and reloaded when files are changed, so
+/// callbacks are run in proxy itself. Scripts are applied in order, every next one gets message changed by previous
+pub(crate) struct Scripts {
+    engine: Engine,
+    sources: Vec<String>,
+    loaded: RwLock<Vec<LoadedScript>>,
+    last_check: Mutex<Instant>,
+}
+
+impl Scripts {
+    /// Broken script stops start, later it is reported and its previous version is kept
+    pub(crate) fn load(sources: Option<&Vec<String>>) -> Result<Self, CrusterError> {
+        let engine = make_engine();
+        let sources = sources.cloned().unwrap_or_default();
+
+        let mut loaded: Vec<LoadedScript> = Vec::default();
+        for path in list_script_files(&sources) {
+            let modified = get_modified(&path);
+            loaded.push(LoadedScript::compile(&engine, &path, modified)?);
+        }
+
+        return Ok(
+            Scripts {
+                engine,
+                sources,
+                loaded: RwLock::new(loaded),
+                last_check: Mutex::new(Instant::now())
+            }
+        );
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        return self.sources.is_empty();
+    }
+
+    fn reload(&self) -> Vec<CrusterError> {
+        {
+            let mut last_check = self.last_check.lock().unwrap();
+            if last_check.elapsed() < RELOAD_INTERVAL {
+                return Vec::default();
+            }
+
+            *last_check = Instant::now();
+        }
+
+        let files: Vec<(PathBuf, Option<SystemTime>)> = list_script_files(&self.sources)
+            .into_iter()
+            .map(|path| {
+                let modified = get_modified(&path);
+                (path, modified)
+            })
+            .collect();
+
+        let unchanged = {
+            let loaded = self.loaded.read().unwrap();
+            loaded.len() == files.len() && loaded
+                .iter()
+                .zip(files.iter())
+                .all(|(script, (path, modified))| &script.path == path && &script.modified == modified)
+        };
+
+        if unchanged {
+            return Vec::default();
+        }
+
+        let mut errors: Vec<CrusterError> = Vec::default();
+        let mut loaded = self.loaded.write().unwrap();
+        let mut previous: Vec<LoadedScript> = loaded.drain(..).collect();
+        for (path, modified) in files {
+            let previous_version = previous
+                .iter()
+                .position(|script| script.path == path)
+                .map(|index| previous.swap_remove(index));
+
+            match previous_version {
+                Some(script) if script.modified == modified => loaded.push(script),
+                _ if modified.is_none() => {
+                    errors.push(CrusterError::ScriptError(format!("Script '{}' is gone", path.display())));
+                },
+                previous_version => {
+                    match LoadedScript::compile(&self.engine, &path, modified) {
+                        Ok(script) => {
+                            debug!("Script '{}' is reloaded", path.display());
+                            loaded.push(script);
+                        },
+                        Err(err) => {
+                            errors.push(err);
+                            if let Some(mut script) = previous_version {
+                                // Broken file is not compiled again until it is changed
+                                script.modified = modified;
+                                loaded.push(script);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        return errors;
+    }
+
+    fn run<T: Clone + Send + Sync + 'static>(&self, name: &str, message: &mut T, is_dropped: impl Fn(&T) -> bool) -> (bool, Vec<CrusterError>) {
+        let mut errors = self.reload();
+        let mut called = false;
+
+        let loaded = self.loaded.read().unwrap();
+        for script in loaded.iter().filter(|script| script.has_callback(name)) {
+            match script.call(&self.engine, name, message) {
+                Ok(_) => called = true,
+                Err(err) => errors.push(err)
+            }
+
+            if is_dropped(message) {
+                break;
+            }
+        }
+
+        return (called, errors);
+    }
+
+    pub(crate) fn on_request(&self, request: &HyperRequestWrapper) -> (HookVerdict<HyperRequestWrapper>, Vec<CrusterError>) {
+        if self.is_empty() {
+            return (HookVerdict::Forward(None, Vec::default()), Vec::default());
+        }
+
+        let mut message = ScriptRequest { wrapper: request.clone(), tags: Vec::default(), dropped: false };
+        let (called, errors) = self.run(ON_REQUEST, &mut message, |message| message.dropped);
+
+        let verdict = if message.dropped {
+            HookVerdict::Drop(message.tags)
+        }
+        else {
+            HookVerdict::Forward(called.then_some(message.wrapper), message.tags)
+        };
+
+        return (verdict, errors);
+    }
+
+    pub(crate) fn on_response(&self, request: &HyperRequestWrapper, response: &HyperResponseWrapper) -> (HookVerdict<HyperResponseWrapper>, Vec<CrusterError>) {
+        if self.is_empty() {
+            return (HookVerdict::Forward(None, Vec::default()), Vec::default());
+        }
+
+        let request = ScriptRequest { wrapper: request.clone(), tags: Vec::default(), dropped: false };
+        let mut message = ScriptResponse { wrapper: response.clone(), request, tags: Vec::default(), dropped: false };
+        let (called, errors) = self.run(ON_RESPONSE, &mut message, |message| message.dropped);
+
+        let verdict = if message.dropped {
+            HookVerdict::Drop(message.tags)
+        }
+        else {
+            HookVerdict::Forward(called.then_some(message.wrapper), message.tags)
+        };
+
+        return (verdict, errors);
+    }
+
+    /// Only text and binary messages are given to scripts, None means message is dropped
+    pub(crate) fn on_ws_message(&self, ctx: &WebSocketContext, message: Message) -> (Option<Message>, Vec<CrusterError>) {
+        if self.is_empty() || !(message.is_text() || message.is_binary()) {
+            return (Some(message), Vec::default());
+        }
+
+        let (uri, from_client) = match ctx {
+            WebSocketContext::ClientToServer { dst, .. } => (dst.to_string(), true),
+            WebSocketContext::ServerToClient { src, .. } => (src.to_string(), false),
+        };
+
+        let mut message = ScriptWsMessage { message, uri, from_client, dropped: false };
+        let (_, errors) = self.run(ON_WS_MESSAGE, &mut message, |message| message.dropped);
+
+        return ((!message.dropped).then_some(message.message), errors);
+    }
+}
@@ -0,0 +1,76 @@
+use std::fs;
+use std::time::Duration;
+use clap::ArgMatches;
+
+use crate::utils::CrusterError;
+
+pub(crate) const DEFAULT_GRACE_PERIOD: u64 = 10;
+
+pub(crate) struct DaemonSettings {
+    pub(crate) pid_file: Option<String>,
+    // How long to wait for in-flight exchanges on shutdown
+    pub(crate) grace_period: Duration,
+}
+
+impl Default for DaemonSettings {
+    fn default() -> Self {
+        DaemonSettings {
+            pid_file: None,
+            grace_period: Duration::from_secs(DEFAULT_GRACE_PERIOD)
+        }
+    }
+}
+
+impl TryFrom<&ArgMatches> for DaemonSettings {
+    type Error = CrusterError;
+    fn try_from(args: &ArgMatches) -> Result<Self, Self::Error> {
+        let mut settings = DaemonSettings::default();
+
+        if let Some(pid_file) = args.get_one::<String>("pid-file") {
+            settings.pid_file = Some(pid_file.clone());
+        }
+
+        if let Some(grace_period) = args.get_one::<u64>("grace-period") {
+            settings.grace_period = Duration::from_secs(*grace_period);
+        }
+
+        return Ok(settings);
+    }
+}
+
+/// PID file is removed when it is dropped, so it is gone after graceful shutdown
+pub(crate) struct PidFile {
+    path: String,
+}
+
+impl PidFile {
+    pub(crate) fn create(path: &str) -> Result<Self, CrusterError> {
+        fs::write(path, format!("{}\n", std::process::id()))?;
+        return Ok(PidFile { path: path.to_string() });
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Resolves on SIGINT (Ctrl+C) or SIGTERM, what CI runners and service managers send to stop process
+pub(crate) async fn termination_signal() -> Result<(), CrusterError> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut sigterm = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = sigterm.recv() => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
+
+    Ok(())
+}
@@ -1,11 +1,27 @@
+pub(crate) mod template;
+pub(crate) mod output;
+
 use bstr::ByteSlice;
+use regex::Regex;
+use http::HeaderMap;
 use std::borrow::Cow;
 use std::time;
-use colored::Colorize;
+use std::fmt::Write;
+use std::str::FromStr;
+use colored::{Color, Colorize};
 use crossbeam_channel::Receiver;
-use hudsucker::WebSocketContext;
+use tokio::sync::broadcast;
+use hudsucker::{
+    WebSocketContext,
+    tokio_tungstenite::tungstenite::Message
+};
+use serde::Serialize;
+use serde_json as json;
 
+use template::DumpTemplate;
+use output::DumpSink;
 use crate::{
+    api::{ApiChannels, ApiCommand},
     cruster_proxy::{
         events::ProxyEvents,
         request_response::{
@@ -13,16 +29,48 @@ use crate::{
             HyperResponseWrapper
         }
     },
-    config::Config,
-    http_storage::HTTPStorage,
+    config::{Config, DumpFormat, DumpOutput, DumpSection, DEFAULT_UNCOMPLETED_TTL, DEFAULT_CLEANUP_INTERVAL},
+    http_storage::{HTTPStorage, RequestResponsePair, serializable::SerializableProxyData},
+    noise::NoiseFilter,
+    query::Query,
     utils::CrusterError,
-    scope
+    scope::ScopeMatcher
 };
 
 pub(crate) trait DumpMode {
     fn dump_mode_enabled(&self) -> bool;
     fn get_verbosity(&self) -> u8;
     fn with_color(&self) -> bool;
+    fn get_format(&self) -> DumpFormat;
+    fn with_decoded_bodies(&self) -> bool;
+    fn get_uncompleted_ttl(&self) -> u64;
+    fn get_cleanup_interval(&self) -> u64;
+    fn get_output(&self) -> Option<&DumpOutput>;
+}
+
+#[derive(Serialize)]
+struct WebSocketRecord {
+    // 'client_to_server' or 'server_to_client'
+    direction: &'static str,
+    src: String,
+    dst: String,
+    // Unix time in seconds when message was captured
+    timestamp: u64,
+    // 'text', 'binary', 'ping', 'pong', 'close' or 'frame'
+    kind: &'static str,
+    data: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    decoded_data: Option<String>,
+}
+
+/// Object printed in JSON format, HTTP exchange has the same fields as stored one
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum DumpRecord {
+    Http(Box<SerializableProxyData>),
+    Websocket(WebSocketRecord),
+    // Only for API subscribers, errors of dump are written to stderr
+    Error { message: String },
 }
 
 impl DumpMode for Config {
@@ -50,12 +98,164 @@ impl DumpMode for Config {
             false
         };
     }   
+
+    fn get_format(&self) -> DumpFormat {
+        return self.dump_mode
+            .as_ref()
+            .map(|dm| dm.format)
+            .unwrap_or_default();
+    }
+
+    fn with_decoded_bodies(&self) -> bool {
+        return self.dump_mode
+            .as_ref()
+            .is_some_and(|dm| dm.decode_bodies);
+    }
+
+    fn get_uncompleted_ttl(&self) -> u64 {
+        return self.dump_mode
+            .as_ref()
+            .map(|dm| dm.uncompleted_ttl)
+            .unwrap_or(DEFAULT_UNCOMPLETED_TTL);
+    }
+
+    fn get_cleanup_interval(&self) -> u64 {
+        return self.dump_mode
+            .as_ref()
+            .map(|dm| dm.cleanup_interval)
+            .unwrap_or(DEFAULT_CLEANUP_INTERVAL);
+    }
+
+    fn get_output(&self) -> Option<&DumpOutput> {
+        return self.dump_mode
+            .as_ref()
+            .and_then(|dm| dm.output.as_ref());
+    }
+}
+
+/// What is printed for every exchange in text format
+struct TextLayout {
+    template: Option<DumpTemplate>,
+    request_headers: bool,
+    response_headers: bool,
+    request_body: bool,
+    response_body: bool,
+    headers_allow: Vec<Regex>,
+    headers_deny: Vec<Regex>,
+}
+
+// Header name with '*' wildcards, i.e. 'Accept-*'
+fn header_pattern(name: &str) -> Regex {
+    let pattern = name
+        .split('*')
+        .map(regex::escape)
+        .collect::<Vec<String>>()
+        .join(".*");
+
+    return Regex::new(&format!("(?i)^{}$", pattern)).unwrap();
+}
+
+impl From<&Config> for TextLayout {
+    fn from(config: &Config) -> Self {
+        let verbosity = config.get_verbosity();
+        let dm = config.dump_mode.as_ref().unwrap();
+
+        // Template is already checked while config was parsed
+        let template = dm.template
+            .as_ref()
+            .and_then(|template| DumpTemplate::from_str(template).ok());
+
+        let patterns = |names: Option<&Vec<String>>| -> Vec<Regex> {
+            return names
+                .map(|names| names.iter().map(|name| header_pattern(name)).collect())
+                .unwrap_or_default();
+        };
+
+        let shown = |section: DumpSection, min_verbosity: u8| -> bool {
+            return match dm.sections.as_ref() {
+                Some(sections) => sections.contains(&section),
+                None => verbosity >= min_verbosity
+            };
+        };
+
+        return TextLayout {
+            template,
+            request_headers: shown(DumpSection::RequestHeaders, 2),
+            response_headers: shown(DumpSection::ResponseHeaders, 1),
+            request_body: shown(DumpSection::RequestBody, 4),
+            response_body: shown(DumpSection::ResponseBody, 3),
+            headers_allow: patterns(dm.headers_allow.as_ref()),
+            headers_deny: patterns(dm.headers_deny.as_ref()),
+        };
+    }
 }
 
-fn print_request(wrapper: &HyperRequestWrapper, hash: usize, config: &super::config::Config) {
-    let verbosity = config.get_verbosity();
-    let first_line = format!("{} {} {}", &wrapper.method, &wrapper.uri, &wrapper.version);
+impl TextLayout {
+    fn is_header_shown(&self, name: &str, section: bool) -> bool {
+        if self.headers_allow.iter().any(|re| re.is_match(name)) {
+            return true;
+        }
+
+        return section && !self.headers_deny.iter().any(|re| re.is_match(name));
+    }
 
+    // Exchanges are separated with empty line when there is more than one line for each
+    fn is_multiline(&self) -> bool {
+        return self.request_headers
+            || self.response_headers
+            || self.request_body
+            || self.response_body
+            || !self.headers_allow.is_empty();
+    }
+}
+
+fn write_headers(out: &mut String, headers: &HeaderMap, prefix: &str, section: bool, layout: &TextLayout) {
+    let mut keys_list: Vec<&str> = headers
+        .keys()
+        .map(|k| {
+            k.as_str()
+        })
+        .filter(|k| layout.is_header_shown(k, section))
+        .collect();
+
+    if keys_list.is_empty() && !section {
+        return;
+    }
+
+    let mut printable = String::default();
+    keys_list.sort();
+    for key in keys_list {
+        let v_iter = headers
+            .get_all(key)
+            .iter()
+            .map(|val| {
+                val.as_bytes().to_str_lossy()
+            })
+            .collect::<Vec<Cow<str>>>()
+            .join("; ");
+
+        printable = format!(
+            "{}{} {}: {}\r\n",
+            printable,
+            prefix,
+            key,
+            v_iter
+        );
+    }
+
+    out.push_str(&printable);
+    let _ = writeln!(out, "{}", prefix);
+}
+
+// Highlighted lines are colored only if colors are enabled
+fn paint(line: String, highlight: Option<Color>) -> String {
+    return match highlight {
+        Some(color) => line.color(color).to_string(),
+        None => line
+    };
+}
+
+fn write_request(out: &mut String, wrapper: &HyperRequestWrapper, hash: usize, config: &super::config::Config, layout: &TextLayout, highlight: Option<Color>) {
     let prefix = if config.with_color() {
         let hash = hash.to_string().bright_black();
         let direction = format!("{}{}", "--".green(), ">".bright_green());
@@ -67,56 +267,25 @@ fn print_request(wrapper: &HyperRequestWrapper, hash: usize, config: &super::con
         format!("http {} -->", hash)
     };
 
-    println!("{} {}", &prefix, first_line);
-
-    if verbosity >= 2 {
-        let mut headers = String::default();
-        let mut keys_list: Vec<&str> = wrapper.headers
-            .keys()
-            .into_iter()
-            .map(|k| {
-                k.as_str()
-            })
-            .collect();
-
-        keys_list.sort();
-        for key in keys_list {
-            let v_iter = wrapper.headers
-                .get_all(key)
-                .iter()
-                .map(|val| {
-                    val.as_bytes().to_str_lossy()
-                })
-                .collect::<Vec<Cow<str>>>()
-                .join("; ");
-
-            headers = format!(
-                "{}{} {}: {}\r\n",
-                headers,
-                &prefix,
-                key,
-                v_iter
-            );
-        }
-
-        print!("{}", headers);
-        println!("{}", &prefix);
+    // Template line replaces first lines of request and response
+    if layout.template.is_none() {
+        let first_line = paint(format!("{} {} {}", &wrapper.method, &wrapper.uri, &wrapper.version), highlight);
+        let _ = writeln!(out, "{} {}", &prefix, first_line);
     }
 
-    if verbosity >= 4 {
+    write_headers(out, &wrapper.headers, &prefix, layout.request_headers, layout);
+
+    if layout.request_body {
         let body = wrapper.body.to_str_lossy();
-        println!("{} {}", &prefix, body);
+        let _ = writeln!(out, "{} {}", &prefix, body);
     }
 
-    if config.get_verbosity() != 0 {
-        println!("");
+    if layout.template.is_none() && layout.is_multiline() {
+        out.push('\n');
     }
 }
 
-fn print_response(wrapper: &HyperResponseWrapper, hash: usize, config: &super::config::Config) {
-    let verbosity = config.get_verbosity();
-    let first_line = format!("{} {}", &wrapper.version, &wrapper.status);
-
+fn write_response(out: &mut String, wrapper: &HyperResponseWrapper, hash: usize, config: &super::config::Config, layout: &TextLayout, highlight: Option<Color>) {
     let prefix = if config.with_color() {
         let hash = hash.to_string().bright_black();
         let direction = format!("{}{}", "<".bright_green(), "==".green());
@@ -128,53 +297,34 @@ fn print_response(wrapper: &HyperResponseWrapper, hash: usize, config: &super::c
         format!("{} {} {}", "http", hash, "<==")
     };
 
-    println!("{} {}", &prefix, first_line);
-
-    if verbosity >= 1 {
-        let mut headers = String::default();
-        let mut keys_list: Vec<&str> = wrapper.headers
-            .keys()
-            .into_iter()
-            .map(|k| {
-                k.as_str()
-            })
-            .collect();
+    if layout.template.is_none() {
+        let first_line = paint(format!("{} {}", &wrapper.version, &wrapper.status), highlight);
+        let _ = writeln!(out, "{} {}", &prefix, first_line);
+    }
 
-        keys_list.sort();
-        for key in keys_list {
-            let v_iter = wrapper.headers
-                .get_all(key)
-                .iter()
-                .map(|val| {
-                    val.as_bytes().to_str_lossy()
-                })
-                .collect::<Vec<Cow<str>>>()
-                .join("; ");
-
-            headers = format!(
-                "{}{} {}: {}\r\n",
-                headers,
-                &prefix,
-                key,
-                v_iter
-            );
-        }
+    write_headers(out, &wrapper.headers, &prefix, layout.response_headers, layout);
 
-        print!("{}", headers);
-        println!("{}", &prefix);
+    if layout.response_body {
+        let body = wrapper.body.to_str_lossy();
+        let _ = writeln!(out, "{} {}", &prefix, body);
     }
 
-    if verbosity >= 3 {
-        let body = wrapper.body.to_str_lossy();
-        println!("{} {}", &prefix, body);
+    if layout.is_multiline() {
+        out.push('\n');
     }
+}
 
-    if config.get_verbosity() != 0 {
-        println!("");
+fn write_pair(out: &mut String, pair: &RequestResponsePair, config: &super::config::Config, layout: &TextLayout, highlight: Option<Color>) {
+    let highlight = highlight.filter(|_| config.with_color());
+    if let Some(template) = layout.template.as_ref() {
+        let _ = writeln!(out, "{}", paint(template.render(pair), highlight));
     }
+
+    write_request(out, pair.request.as_ref().unwrap(), pair.index, config, layout, highlight);
+    write_response(out, pair.response.as_ref().unwrap(), pair.index, config, layout, highlight);
 }
 
-fn print_ws_message(msg: &[u8], ctx: &WebSocketContext, config: &super::config::Config) {
+fn write_ws_message(out: &mut String, msg: &[u8], ctx: &WebSocketContext, config: &super::config::Config) {
     match ctx {
         WebSocketContext::ClientToServer { src, dst, .. } => {
             let printable_mes = msg.to_str_lossy();
@@ -192,11 +342,11 @@ fn print_ws_message(msg: &[u8], ctx: &WebSocketContext, config: &super::config::
             };
 
             if verbosity >= 3 {
-                println!("{} {}", &prefix, printable_mes);
+                let _ = writeln!(out, "{} {}", &prefix, printable_mes);
             }
             else {
                 let limit = if printable_mes.len() < 30 { printable_mes.len() } else { 30 };
-                println!("{} {}...", &prefix, &printable_mes[.. limit]);
+                let _ = writeln!(out, "{} {}...", &prefix, &printable_mes[.. limit]);
             }
         },
         WebSocketContext::ServerToClient { src, dst, .. } => {
@@ -215,16 +365,83 @@ fn print_ws_message(msg: &[u8], ctx: &WebSocketContext, config: &super::config::
             };
 
             if verbosity >= 3 {
-                println!("{} {}", &prefix, printable_mes);
+                let _ = writeln!(out, "{} {}", &prefix, printable_mes);
             }
             else {
                 let limit = if printable_mes.len() < 30 { printable_mes.len() } else { 30 };
-                println!("{} {}...", &prefix, &printable_mes[.. limit]);
+                let _ = writeln!(out, "{} {}...", &prefix, &printable_mes[.. limit]);
             }
         }
     }
 }
 
+fn write_pair_json(out: &mut String, pair: &RequestResponsePair, config: &super::config::Config) -> Result<(), CrusterError> {
+    let record = DumpRecord::Http(Box::new(SerializableProxyData::try_from(pair)?));
+    let mut jsn = json::to_value(&record)?;
+
+    if config.with_decoded_bodies() {
+        let bodies = [
+            ("request", pair.request.as_ref().map(|req| &req.body)),
+            ("response", pair.response.as_ref().map(|res| &res.body)),
+        ];
+
+        for (part, body) in bodies {
+            let body = match body {
+                Some(body) if !body.is_empty() => body,
+                _ => continue
+            };
+
+            if let Some(obj) = jsn.get_mut(part).and_then(|part| part.as_object_mut()) {
+                obj.insert("decoded_body".to_string(), json::Value::from(body.to_str_lossy().to_string()));
+            }
+        }
+    }
+
+    let _ = writeln!(out, "{}", jsn);
+    Ok(())
+}
+
+fn write_ws_message_json(out: &mut String, msg: &Message, ctx: &WebSocketContext, config: &super::config::Config) -> Result<(), CrusterError> {
+    let (direction, src, dst) = match ctx {
+        WebSocketContext::ClientToServer { src, dst, .. } => ("client_to_server", src.to_string(), dst.to_string()),
+        WebSocketContext::ServerToClient { src, dst, .. } => ("server_to_client", src.to_string(), dst.to_string()),
+    };
+
+    let kind = match msg {
+        Message::Text(_) => "text",
+        Message::Binary(_) => "binary",
+        Message::Ping(_) => "ping",
+        Message::Pong(_) => "pong",
+        Message::Close(_) => "close",
+        Message::Frame(_) => "frame",
+    };
+
+    let data = msg.clone().into_data();
+    let record = DumpRecord::Websocket(
+        WebSocketRecord {
+            direction,
+            src,
+            dst,
+            timestamp: time::SystemTime::now()
+                .duration_since(time::UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default(),
+            kind,
+            data: base64::encode(&data),
+            decoded_data: if config.with_decoded_bodies() { Some(data.to_str_lossy().to_string()) } else { None },
+        }
+    );
+
+    let _ = writeln!(out, "{}", json::to_string(&record)?);
+    Ok(())
+}
+
+fn write_error_json(out: &mut String, err: &CrusterError) -> Result<(), CrusterError> {
+    let record = DumpRecord::Error { message: err.to_string() };
+    let _ = writeln!(out, "{}", json::to_string(&record)?);
+    Ok(())
+}
+
 fn print_error(err: CrusterError, need_color: bool) {
     if need_color {
         eprintln!("{} {}", "errr".red(), err);
@@ -234,110 +451,250 @@ fn print_error(err: CrusterError, need_color: bool) {
     }
 }
 
-pub(super) async fn launch_dump(rx: Receiver<ProxyEvents>, config: super::config::Config) {
-    let mut http_storage = HTTPStorage::default();
-    if let Some(proj_path) = config.project.as_ref() {
-        let path = format!("{}/http.jsonl", proj_path);
+struct Dumper {
+    config: Config,
+    layout: TextLayout,
+    http_storage: HTTPStorage,
+    scope_matcher: ScopeMatcher,
+    filter: Option<Query>,
+    highlight: Vec<(Query, Color)>,
+    sink: DumpSink,
+    // Events are published for API subscribers, if API is served
+    events: Option<broadcast::Sender<String>>,
+}
 
-        // Do it to set apropriate next_id in HTTPStorage state
-        if let Err(err) = http_storage.load(&path) {
-            print_error(
-                CrusterError::UndefinedError(
-                    "Could not read stored HTTP data, will start with empty storage".to_string()
-                ),
-                config.with_color()
-            );
-            print_error(err, config.with_color());
-        };
-        http_storage.clear(true).unwrap();
+impl Dumper {
+    fn new(config: Config, sink: DumpSink, events: Option<broadcast::Sender<String>>) -> Self {
+        let mut http_storage = HTTPStorage::default();
+        if let Some(noise) = config.noise.as_ref() {
+            http_storage.set_noise_filter(NoiseFilter::from(noise));
+        }
 
-        http_storage.keep_open(&path).unwrap();
-    }
-    else {
-        print_error(
-            CrusterError::UndefinedError("No storage defined, traffic will not be saved!".to_string()),
-            config.with_color()
-        )
-    }
+        if let Some(proj_path) = config.project.as_ref() {
+            let path = format!("{}/http.jsonl", proj_path);
+
+            // Do it to set apropriate next_id in HTTPStorage state
+            if let Err(err) = http_storage.load(&path) {
+                print_error(
+                    CrusterError::UndefinedError(
+                        "Could not read stored HTTP data, will start with empty storage".to_string()
+                    ),
+                    config.with_color()
+                );
+                print_error(err, config.with_color());
+            };
+            http_storage.clear(true).unwrap();
 
-    // Remove uncompleted requests older than 5 minutes everu 10 minutes
-    let mut time_mark = time::SystemTime::now();
-    let cycle_duration = time::Duration::new(600, 0);
+            http_storage.keep_open(&path).unwrap();
+        }
+        else {
+            print_error(
+                CrusterError::UndefinedError("No storage defined, traffic will not be saved!".to_string()),
+                config.with_color()
+            )
+        }
 
-    let mut inc_scope = Vec::new();
-    let mut exc_scope = Vec::new();
+        let scope_matcher = ScopeMatcher::from_config(config.scope.as_ref());
 
-    if let Some(cruster_scope) = config.scope.as_ref() {
-        inc_scope = if let Some(inc) = cruster_scope.include.as_ref() {
-            scope::make_re_list(inc.as_slice())
-        } else {
-            Vec::new()
-        };
+        // Query is already checked while config was parsed
+        let filter = config.dump_mode
+            .as_ref()
+            .and_then(|dm| dm.filter.as_ref())
+            .and_then(|filter| Query::from_str(filter).ok());
+
+        // Rules are already checked while config was parsed
+        let highlight = config.dump_mode
+            .iter()
+            .flat_map(|dm| dm.highlight.iter().flatten())
+            .filter_map(|rule| {
+                let query = Query::from_str(&rule.query).ok()?;
+                let color = Color::from_str(&rule.color).ok()?;
+                Some((query, color))
+            })
+            .collect();
 
-        exc_scope = if let Some(exc) = cruster_scope.exclude.as_ref() {
-            scope::make_re_list(exc.as_slice())
-        } else {
-            Vec::new()
-        };
+        let layout = TextLayout::from(&config);
+        return Dumper { config, layout, http_storage, scope_matcher, filter, highlight, sink, events };
     }
 
-    loop {
-        let event = rx.try_recv();
-        if let Err(_) = event {
-            continue;
-        }
-
-        match event.unwrap() {
+    fn handle_event(&mut self, event: ProxyEvents) {
+        let config = &self.config;
+        // Output of event is written at once, so it is not split between rotated files
+        let mut out = String::default();
+        match event {
             ProxyEvents::RequestSent((wrapper, hash)) => {
                 if let Some(cruster_scope) = config.scope.as_ref() {
-                    if ! cruster_scope.strict || scope::fit(&wrapper.uri, &inc_scope, &exc_scope) {
-                        let _ = http_storage.put_request(wrapper, hash);        
+                    if ! cruster_scope.strict || self.scope_matcher.fits(&wrapper.uri) {
+                        let _ = self.http_storage.put_request(wrapper, hash);
                     }
                 } else {
-                    let _ = http_storage.put_request(wrapper, hash);
+                    let _ = self.http_storage.put_request(wrapper, hash);
                 }
             },
             ProxyEvents::ResponseSent((wrapper, hash)) => {
-                let id = http_storage.put_response(wrapper, &hash);
+                let id = self.http_storage.put_response(wrapper, &hash);
                 if let Some(id) = id {
-                    let pair = http_storage.get_by_id(id).unwrap();
+                    // Pair is gone if it was noise and strict filter removed it
+                    if self.http_storage.get_by_id(id).is_none() {
+                        return;
+                    }
 
-                    if scope::fit(&pair.request.as_ref().unwrap().uri, &inc_scope, &exc_scope) {
-                        print_request(pair.request.as_ref().unwrap(), id, &config);
-                        print_response(pair.response.as_ref().unwrap(), id, &config);
+                    // Pair is stored before it is printed, because it gets another ID if its one is taken in file meanwhile
+                    let (id, flushed) = match self.http_storage.flush_by_id(id) {
+                        Ok(id) => (id, true),
+                        Err(err) => {
+                            print_error(err, config.with_color());
+                            (id, false)
+                        }
+                    };
+
+                    let pair = match self.http_storage.get_by_id(id) {
+                        Some(pair) => pair,
+                        None => return
+                    };
+
+                    let in_scope = |uri: &str| self.scope_matcher.fits(uri);
+                    let fit_scope = in_scope(&pair.request.as_ref().unwrap().uri);
+                    // Subscribers get everything in scope, dump filter is only for output
+                    if fit_scope && ! self.http_storage.is_noise(pair) {
+                        self.publish(|out| write_pair_json(out, pair, config));
                     }
 
-                    if let Err(err) = http_storage.flush_by_id(id) {
-                        print_error(err, config.with_color());
+                    let fit_filter = self.filter.as_ref().is_none_or(|query| query.matches(pair, &in_scope));
+                    if fit_scope && fit_filter && ! self.http_storage.is_noise(pair) {
+                        match config.get_format() {
+                            DumpFormat::Text => {
+                                let highlight = self.highlight
+                                    .iter()
+                                    .find(|(query, _)| query.matches(pair, &in_scope))
+                                    .map(|(_, color)| *color);
+
+                                write_pair(&mut out, pair, config, &self.layout, highlight);
+                            },
+                            DumpFormat::Json => {
+                                if let Err(err) = write_pair_json(&mut out, pair, config) {
+                                    print_error(err, config.with_color());
+                                }
+                            }
+                        }
                     }
-                    else {
-                        if let Err(err) = http_storage.remove_by_id(id, false) {
+
+                    if flushed {
+                        if let Err(err) = self.http_storage.remove_by_id(id, false) {
                             print_error(err, config.with_color());
                         }
                     }
                 }
             },
+            ProxyEvents::Annotated((tags, hash)) => {
+                self.http_storage.annotate(tags, &hash);
+            },
             ProxyEvents::WebSocketMessageSent((_ctx, _msg)) => {
-                let m = _msg.into_data();
-                print_ws_message(m.as_slice(), &_ctx, &config);
+                self.publish(|out| write_ws_message_json(out, &_msg, &_ctx, config));
+                if config.get_format() == DumpFormat::Json {
+                    if let Err(err) = write_ws_message_json(&mut out, &_msg, &_ctx, config) {
+                        print_error(err, config.with_color());
+                    }
+                }
+                else {
+                    let m = _msg.into_data();
+                    write_ws_message(&mut out, m.as_slice(), &_ctx, config);
+                }
             },
             ProxyEvents::Error((err, hash)) => {
+                self.publish(|out| write_error_json(out, &err));
                 print_error(err, config.with_color());
-                
+
                 if let Some(hash) = hash {
-                    if let Err(err) = http_storage.remove_uncompleted(hash) {
+                    if let Err(err) = self.http_storage.remove_uncompleted(hash) {
                         print_error(err, config.with_color());
                     }
                 }
             }
         }
 
-        let now = time::SystemTime::now();
-        if now.duration_since(time_mark).unwrap() > cycle_duration {
-            time_mark = now;
-            if let Err(err) = http_storage.remove_uncompleted_older_than(time::Duration::new(300, 0)) {
-                print_error(err, config.with_color());
+        if !out.is_empty() {
+            if let Err(err) = self.sink.write(&out) {
+                print_error(err, self.config.with_color());
+            }
+        }
+    }
+
+    /// Event is serialized only if somebody is subscribed to events
+    fn publish(&self, write_json: impl FnOnce(&mut String) -> Result<(), CrusterError>) {
+        let events = match self.events.as_ref() {
+            Some(events) if events.receiver_count() > 0 => events,
+            _ => return
+        };
+
+        let mut event = String::default();
+        match write_json(&mut event) {
+            Ok(_) => { let _ = events.send(event); },
+            Err(err) => print_error(err, self.config.with_color())
+        }
+    }
+
+    fn handle_api_command(&mut self, command: ApiCommand) {
+        match command {
+            ApiCommand::SetScope(scope) => {
+                // Scope is checked by API before it is sent
+                self.scope_matcher = ScopeMatcher::from_config(Some(&scope));
+                self.config.scope = Some(scope);
             }
         }
     }
-}
\ No newline at end of file
+
+    /// Requests which did not get response for TTL are dropped, so storage does not grow while dump runs for hours
+    fn remove_stale_requests(&mut self) {
+        let ttl = time::Duration::from_secs(self.config.get_uncompleted_ttl());
+        if let Err(err) = self.http_storage.remove_uncompleted_older_than(ttl) {
+            print_error(err, self.config.with_color());
+        }
+    }
+}
+
+/// Blocks until proxy is gone or shutdown is requested, so it is run in a separate thread. Thread sleeps
+/// while there are no events and wakes up only to remove stale requests
+pub(super) fn launch_dump(rx: Receiver<ProxyEvents>, shutdown: Receiver<()>, config: Config, sink: DumpSink, api: Option<ApiChannels>) {
+    let cleanup_interval = time::Duration::from_secs(config.get_cleanup_interval().max(1));
+    let cleanup_ticker = crossbeam_channel::tick(cleanup_interval);
+    let (api_commands, events) = match api {
+        Some(api) => (api.commands, Some(api.events)),
+        None => (crossbeam_channel::never(), None)
+    };
+
+    let mut dumper = Dumper::new(config, sink, events);
+
+    loop {
+        crossbeam_channel::select! {
+            recv(rx) -> event => match event {
+                Ok(event) => dumper.handle_event(event),
+                Err(_) => break
+            },
+            recv(cleanup_ticker) -> _ => dumper.remove_stale_requests(),
+            recv(api_commands) -> command => {
+                if let Ok(command) = command {
+                    dumper.handle_api_command(command);
+                }
+            },
+            recv(shutdown) -> _ => {
+                // Events which are already sent by proxy are not lost
+                for event in rx.try_iter() {
+                    dumper.handle_event(event);
+                }
+
+                break;
+            },
+        }
+    }
+
+    // Completed pairs are already flushed, so only output is left
+    dumper.sink.flush();
+    if let Err(err) = dumper.http_storage.sync() {
+        print_error(err, dumper.config.with_color());
+    }
+
+    if let Err(err) = dumper.http_storage.store_seen_counters() {
+        print_error(err, dumper.config.with_color());
+    }
+}
@@ -0,0 +1,114 @@
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use log::Record;
+use log4rs::append::Append;
+use log4rs::append::rolling_file::{LogFile, RollingFileAppender};
+use log4rs::append::rolling_file::policy::compound::CompoundPolicy;
+use log4rs::append::rolling_file::policy::compound::roll::fixed_window::FixedWindowRoller;
+use log4rs::append::rolling_file::policy::compound::trigger::Trigger;
+use log4rs::encode::pattern::PatternEncoder;
+
+use crate::config::DumpOutput;
+use crate::utils::CrusterError;
+
+/// Rolls file over when it grows over size or when interval is elapsed, whatever comes first
+#[derive(Debug)]
+struct RotationTrigger {
+    max_size: Option<u64>,
+    interval: Option<Duration>,
+    next_rotation: Mutex<SystemTime>,
+}
+
+impl RotationTrigger {
+    fn new(max_size: Option<u64>, interval: Option<Duration>) -> Self {
+        let next_rotation = interval
+            .map(|interval| SystemTime::now() + interval)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+
+        return RotationTrigger { max_size, interval, next_rotation: Mutex::new(next_rotation) };
+    }
+}
+
+impl Trigger for RotationTrigger {
+    fn trigger(&self, file: &LogFile) -> anyhow::Result<bool> {
+        if self.max_size.is_some_and(|max_size| file.len_estimate() > max_size) {
+            return Ok(true);
+        }
+
+        if let Some(interval) = self.interval {
+            let now = SystemTime::now();
+            let mut next_rotation = self.next_rotation.lock().unwrap();
+            if now >= *next_rotation {
+                *next_rotation = now + interval;
+                return Ok(true);
+            }
+        }
+
+        return Ok(false);
+    }
+}
+
+/// Where dump output is written: stdout or file rotated with log4rs. Daemon without output file discards it
+pub(crate) enum DumpSink {
+    Stdout,
+    File(RollingFileAppender),
+    Discard,
+}
+
+impl TryFrom<Option<&DumpOutput>> for DumpSink {
+    type Error = CrusterError;
+    fn try_from(output: Option<&DumpOutput>) -> Result<Self, Self::Error> {
+        let output = match output {
+            Some(output) => output,
+            None => return Ok(DumpSink::Stdout)
+        };
+
+        // Rotated files are 'dump.log.1', 'dump.log.2' and so on, the first one is the newest
+        let pattern = if output.compress {
+            format!("{}.{{}}.gz", output.path)
+        }
+        else {
+            format!("{}.{{}}", output.path)
+        };
+
+        let roller = FixedWindowRoller::builder()
+            .base(1)
+            .build(&pattern, output.keep)
+            .map_err(|err| CrusterError::ConfigError(format!("Could not set rotation of '{}': {}", output.path, err)))?;
+
+        let trigger = RotationTrigger::new(output.max_size, output.rotate_every.map(Duration::from_secs));
+        let policy = CompoundPolicy::new(Box::new(trigger), Box::new(roller));
+        let appender = RollingFileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}")))
+            .build(&output.path, Box::new(policy))?;
+
+        return Ok(DumpSink::File(appender));
+    }
+}
+
+impl DumpSink {
+    /// Text is written as is, so it must end with new line
+    pub(crate) fn write(&self, text: &str) -> Result<(), CrusterError> {
+        match self {
+            DumpSink::Stdout => print!("{}", text),
+            DumpSink::File(appender) => {
+                appender
+                    .append(&Record::builder().args(format_args!("{}", text)).build())
+                    .map_err(|err| CrusterError::IOError(err.to_string()))?;
+            },
+            DumpSink::Discard => {}
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn flush(&self) {
+        match self {
+            DumpSink::Stdout => { let _ = std::io::stdout().flush(); },
+            DumpSink::File(appender) => appender.flush(),
+            DumpSink::Discard => {}
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::time;
+use std::str::FromStr;
+
+use crate::{
+    http_storage::RequestResponsePair,
+    project::format_timestamp,
+    query::PairField,
+    utils::CrusterError
+};
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Text(String),
+    Field(PairField),
+    // Milliseconds from request to response
+    Duration,
+    // When request was captured
+    Time,
+    Tags,
+}
+
+/// Line printed for every exchange in dump mode, i.e. `{id} {method} {host}{path} -> {status} {length}b {duration}ms`.
+/// Placeholders are fields of filter queries plus `duration`, `time` and `tags`, `{{` and `}}` are braces
+#[derive(Debug, Clone)]
+pub(crate) struct DumpTemplate {
+    segments: Vec<Segment>,
+}
+
+impl FromStr for DumpTemplate {
+    type Err = CrusterError;
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let mut segments: Vec<Segment> = Vec::default();
+        let mut text = String::default();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                },
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                },
+                '{' => {
+                    let name: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                    let segment = match name.trim() {
+                        "duration" => Segment::Duration,
+                        "time" => Segment::Time,
+                        "tags" => Segment::Tags,
+                        field => match PairField::parse(field) {
+                            Some(field) => Segment::Field(field),
+                            None => {
+                                return Err(
+                                    CrusterError::ConfigError(format!("Unknown placeholder '{{{}}}' in dump template '{}'", name, template))
+                                );
+                            }
+                        }
+                    };
+
+                    if !text.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut text)));
+                    }
+
+                    segments.push(segment);
+                },
+                '}' => {
+                    return Err(CrusterError::ConfigError(format!("Unmatched '}}' in dump template '{}', use '}}}}' for brace", template)));
+                },
+                _ => text.push(c)
+            }
+        }
+
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+
+        return Ok(DumpTemplate { segments });
+    }
+}
+
+impl DumpTemplate {
+    pub(crate) fn render(&self, pair: &RequestResponsePair) -> String {
+        let mut line = String::default();
+        for segment in self.segments.iter() {
+            match segment {
+                Segment::Text(text) => line.push_str(text),
+                Segment::Field(field) => line.push_str(&field.value(pair)),
+                Segment::Duration => {
+                    // Pair is dumped as soon as response is received, so it is time of response
+                    let duration = pair.timestamp
+                        .and_then(|ts| time::SystemTime::now().duration_since(ts).ok())
+                        .map(|duration| duration.as_millis().to_string())
+                        .unwrap_or_default();
+
+                    line.push_str(&duration);
+                },
+                Segment::Time => {
+                    let timestamp = pair.timestamp
+                        .and_then(|ts| ts.duration_since(time::UNIX_EPOCH).ok())
+                        .map(|duration| format_timestamp(duration.as_secs()))
+                        .unwrap_or_default();
+
+                    line.push_str(&timestamp);
+                },
+                Segment::Tags => line.push_str(&pair.tags.join(",")),
+            }
+        }
+
+        return line;
+    }
+}
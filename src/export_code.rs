@@ -0,0 +1,359 @@
+use bstr::ByteSlice;
+use std::fmt::Display;
+
+use crate::cruster_proxy::request_response::HyperRequestWrapper;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum CodeLang {
+    Curl,
+    Python,
+    Raw,
+    PowerShell
+}
+
+impl TryFrom<&str> for CodeLang {
+    type Error = String;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "curl" => Ok(CodeLang::Curl),
+            "python" | "py" => Ok(CodeLang::Python),
+            "raw" | "http" => Ok(CodeLang::Raw),
+            "powershell" | "pwsh" => Ok(CodeLang::PowerShell),
+            _ => Err(format!("Unknown language '{}', must be one of [curl,python,raw,powershell]", value))
+        }
+    }
+}
+
+impl Display for CodeLang {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodeLang::Curl => write!(f, "curl"),
+            CodeLang::Python => write!(f, "python"),
+            CodeLang::Raw => write!(f, "raw"),
+            CodeLang::PowerShell => write!(f, "powershell")
+        }
+    }
+}
+
+impl CodeLang {
+    pub(crate) fn all() -> [CodeLang; 4] {
+        return [CodeLang::Curl, CodeLang::Python, CodeLang::Raw, CodeLang::PowerShell];
+    }
+}
+
+/// Headers which are computed by clients themselves and would break the request if copied as is
+const SKIPPED_HEADERS: [&str; 2] = ["content-length", "transfer-encoding"];
+
+/// Body is considered textual if it is valid UTF-8 without control characters, except tabs and line breaks
+fn is_text(bytes: &[u8]) -> bool {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => {
+            s.chars().all(|c| { !c.is_control() || c == '\t' || c == '\r' || c == '\n' })
+        },
+        Err(_) => false
+    }
+}
+
+/// Values of repeating headers, joined the way servers expect them
+fn merged_headers(request: &HyperRequestWrapper, skip: &[&str]) -> Vec<(String, Vec<u8>)> {
+    let mut result: Vec<(String, Vec<u8>)> = Vec::with_capacity(request.headers.keys_len());
+    for key in request.headers.keys() {
+        if skip.contains(&key.as_str()) {
+            continue;
+        }
+
+        let separator: &[u8] = if key.as_str() == "cookie" { b"; " } else { b", " };
+        let value = request.headers
+            .get_all(key)
+            .iter()
+            .map(|v| v.as_bytes())
+            .collect::<Vec<&[u8]>>()
+            .join(separator);
+
+        result.push((key.to_string(), value));
+    }
+
+    return result;
+}
+
+// ---------------------------------------------------------------------------------------------- //
+
+fn shell_quote(bytes: &[u8]) -> String {
+    if is_text(bytes) {
+        let s = bytes.to_str_lossy();
+        return format!("'{}'", s.replace('\'', r#"'\''"#));
+    }
+
+    // ANSI-C quoting, supported by bash and zsh
+    let mut result = String::from("$'");
+    for b in bytes {
+        match b {
+            b'\\' => result.push_str(r"\\"),
+            b'\'' => result.push_str(r"\'"),
+            0x20..=0x7e => result.push(*b as char),
+            _ => result.push_str(&format!("\\x{:02x}", b))
+        }
+    }
+
+    result.push('\'');
+    return result;
+}
+
+/// `printf` format string producing exactly `bytes`, used for bodies `curl` cannot take as an argument
+fn printf_quote(bytes: &[u8]) -> String {
+    let mut result = String::from("'");
+    for b in bytes {
+        match b {
+            b'\\' => result.push_str(r"\\"),
+            b'%' => result.push_str("%%"),
+            b'\'' => result.push_str(r"\047"),
+            0x20..=0x7e => result.push(*b as char),
+            _ => result.push_str(&format!("\\{:03o}", b))
+        }
+    }
+
+    result.push('\'');
+    return result;
+}
+
+fn to_curl(request: &HyperRequestWrapper) -> String {
+    let mut lines: Vec<String> = Vec::with_capacity(request.headers.len() + 3);
+    lines.push(format!("curl -i -s -k -X {}", shell_quote(request.method.as_bytes())));
+
+    for (key, value) in request.headers.iter() {
+        if SKIPPED_HEADERS.contains(&key.as_str()) {
+            continue;
+        }
+
+        let mut header = key.as_str().as_bytes().to_vec();
+        header.extend_from_slice(b": ");
+        header.extend_from_slice(value.as_bytes());
+        lines.push(format!("    -H {}", shell_quote(&header)));
+    }
+
+    let prefix = if request.body.is_empty() {
+        String::default()
+    }
+    else if request.body.contains(&0) {
+        lines.push("    --data-binary @-".to_string());
+        format!("printf {} | ", printf_quote(&request.body))
+    }
+    else {
+        // Unlike '--data-binary', it does not take body starting with '@' as file name
+        lines.push(format!("    --data-raw {}", shell_quote(&request.body)));
+        String::default()
+    };
+
+    lines.push(format!("    {}", shell_quote(request.uri.as_bytes())));
+    return format!("{}{}\n", prefix, lines.join(" \\\n"));
+}
+
+// ---------------------------------------------------------------------------------------------- //
+
+fn python_str(s: &str) -> String {
+    let mut result = String::from("'");
+    for c in s.chars() {
+        match c {
+            '\\' => result.push_str(r"\\"),
+            '\'' => result.push_str(r"\'"),
+            '\n' => result.push_str(r"\n"),
+            '\r' => result.push_str(r"\r"),
+            '\t' => result.push_str(r"\t"),
+            c if c.is_control() => result.push_str(&format!("\\x{:02x}", c as u32)),
+            c => result.push(c)
+        }
+    }
+
+    result.push('\'');
+    return result;
+}
+
+fn python_bytes(bytes: &[u8]) -> String {
+    let mut result = String::from("b'");
+    for b in bytes {
+        match b {
+            b'\\' => result.push_str(r"\\"),
+            b'\'' => result.push_str(r"\'"),
+            b'\n' => result.push_str(r"\n"),
+            b'\r' => result.push_str(r"\r"),
+            b'\t' => result.push_str(r"\t"),
+            0x20..=0x7e => result.push(*b as char),
+            _ => result.push_str(&format!("\\x{:02x}", b))
+        }
+    }
+
+    result.push('\'');
+    return result;
+}
+
+/// `requests` encodes `str` headers as Latin-1, so only ASCII values are written as `str`
+fn python_value(bytes: &[u8]) -> String {
+    if bytes.is_ascii() {
+        python_str(&bytes.to_str_lossy())
+    }
+    else {
+        python_bytes(bytes)
+    }
+}
+
+fn to_python(request: &HyperRequestWrapper) -> String {
+    let mut code = String::from("import requests\n\n");
+    code.push_str(&format!("url = {}\n", python_str(&request.uri)));
+
+    code.push_str("headers = {\n");
+    for (key, value) in merged_headers(request, &SKIPPED_HEADERS) {
+        code.push_str(&format!("    {}: {},\n", python_str(&key), python_value(&value)));
+    }
+    code.push_str("}\n");
+
+    let data = if request.body.is_empty() {
+        "None".to_string()
+    }
+    else if is_text(&request.body) && request.body.is_ascii() {
+        python_str(&request.body.to_str_lossy())
+    }
+    else if is_text(&request.body) {
+        // `requests` encodes `str` body as Latin-1, which fails for other characters
+        format!("{}.encode()", python_str(&request.body.to_str_lossy()))
+    }
+    else {
+        python_bytes(&request.body)
+    };
+
+    code.push_str(&format!("data = {}\n\n", data));
+    code.push_str(
+        &format!(
+            "response = requests.request({}, url, headers=headers, data=data, verify=False, allow_redirects=False)\n",
+            python_str(&request.method)
+        )
+    );
+    code.push_str("print(response.status_code)\nprint(response.text)\n");
+
+    return code;
+}
+
+// ---------------------------------------------------------------------------------------------- //
+
+fn to_raw(request: &HyperRequestWrapper) -> Vec<u8> {
+    // netcat speaks HTTP/1.x only
+    let version = if request.version.starts_with("HTTP/1") { request.version.as_str() } else { "HTTP/1.1" };
+    // URI without path, like 'http://example.com?a=b', still keeps its query
+    let path = match request.uri.parse::<http::Uri>().ok().and_then(|uri| uri.path_and_query().cloned()) {
+        Some(path_and_query) => {
+            match path_and_query.query() {
+                Some(query) => format!("{}?{}", path_and_query.path(), query),
+                None => path_and_query.path().to_string()
+            }
+        },
+        None => {
+            match (request.get_request_path_without_query(), request.get_query()) {
+                (Ok(path), Some(query)) => format!("{}{}", path, query),
+                (Ok(path), None) => path,
+                (Err(_), _) => request.get_request_path()
+            }
+        }
+    };
+
+    let mut raw: Vec<u8> = Vec::with_capacity(request.body.len() + 1000);
+    raw.extend_from_slice(format!("{} {} {}\r\n", &request.method, path, version).as_bytes());
+
+    for (key, value) in request.headers.iter() {
+        if SKIPPED_HEADERS.contains(&key.as_str()) {
+            continue;
+        }
+
+        raw.extend_from_slice(key.as_str().as_bytes());
+        raw.extend_from_slice(b": ");
+        raw.extend_from_slice(value.as_bytes());
+        raw.extend_from_slice(b"\r\n");
+    }
+
+    // Body is stored de-chunked, so its length is always known
+    if !request.body.is_empty() || request.headers.contains_key("content-length") {
+        raw.extend_from_slice(format!("content-length: {}\r\n", request.body.len()).as_bytes());
+    }
+
+    raw.extend_from_slice(b"\r\n");
+    raw.extend_from_slice(&request.body);
+
+    return raw;
+}
+
+// ---------------------------------------------------------------------------------------------- //
+
+fn powershell_str(bytes: &[u8]) -> String {
+    // Single-quoted strings in PowerShell have no escapes except doubled quote
+    return format!("'{}'", bytes.to_str_lossy().replace('\'', "''"));
+}
+
+fn powershell_bytes(bytes: &[u8]) -> String {
+    let bytes = bytes
+        .iter()
+        .map(|b| format!("0x{:02x}", b))
+        .collect::<Vec<String>>()
+        .join(",");
+
+    return format!("[byte[]]@({})", bytes);
+}
+
+/// Header values are sent by PowerShell as Latin-1, so other bytes are given as Latin-1 string to be sent as is
+fn powershell_value(bytes: &[u8]) -> String {
+    if bytes.is_ascii() {
+        return powershell_str(bytes);
+    }
+
+    return format!("([System.Text.Encoding]::GetEncoding('iso-8859-1').GetString({}))", powershell_bytes(bytes));
+}
+
+fn to_powershell(request: &HyperRequestWrapper) -> String {
+    // These headers are restricted in Windows PowerShell and must be passed with dedicated parameters
+    let skipped = ["content-length", "transfer-encoding", "host", "connection", "content-type", "user-agent"];
+
+    let mut code = String::from("$headers = @{\n");
+    for (key, value) in merged_headers(request, &skipped) {
+        code.push_str(&format!("    {} = {}\n", powershell_str(key.as_bytes()), powershell_value(&value)));
+    }
+    code.push_str("}\n");
+
+    let mut command = format!(
+        "Invoke-WebRequest -UseBasicParsing -Uri {} -Method {} -Headers $headers",
+        powershell_str(request.uri.as_bytes()),
+        powershell_str(request.method.as_bytes())
+    );
+
+    if let Some(content_type) = request.headers.get("content-type") {
+        command.push_str(&format!(" -ContentType {}", powershell_value(content_type.as_bytes())));
+    }
+
+    if let Some(user_agent) = request.headers.get("user-agent") {
+        command.push_str(&format!(" -UserAgent {}", powershell_value(user_agent.as_bytes())));
+    }
+
+    if !request.body.is_empty() {
+        if is_text(&request.body) {
+            code.push_str(&format!("$body = {}\n", powershell_str(&request.body)));
+        }
+        else {
+            code.push_str(&format!("$body = {}\n", powershell_bytes(&request.body)));
+        }
+
+        command.push_str(" -Body $body");
+    }
+
+    code.push_str(&command);
+    code.push('\n');
+
+    return code;
+}
+
+// ---------------------------------------------------------------------------------------------- //
+
+/// Returns bytes, because raw HTTP must keep binary body as is
+pub(crate) fn export_request(request: &HyperRequestWrapper, lang: CodeLang) -> Vec<u8> {
+    match lang {
+        CodeLang::Curl => to_curl(request).into_bytes(),
+        CodeLang::Python => to_python(request).into_bytes(),
+        CodeLang::Raw => to_raw(request),
+        CodeLang::PowerShell => to_powershell(request).into_bytes()
+    }
+}
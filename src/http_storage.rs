@@ -1,10 +1,13 @@
 pub(crate) mod serializable;
+pub(crate) mod index;
 
-use std::{fs::File, cmp::max};
-use std::collections::HashMap;
+use std::cmp::max;
+use std::collections::{HashMap, HashSet};
 use std::time;
+use regex::Regex;
 
-use crate::{siv_ui::ProxyDataForTable, utils::CrusterError};
+use index::SearchIndex;
+use crate::{siv_ui::ProxyDataForTable, utils::CrusterError, project::files::RecordAppender, noise::NoiseFilter, query::Query};
 use super::cruster_proxy::request_response::{HyperRequestWrapper, HyperResponseWrapper};
 
 #[derive(Clone, Debug)]
@@ -14,6 +17,29 @@ pub(super) struct RequestResponsePair {
     // must be named 'id' actually
     pub(super) index: usize,
     pub(super) timestamp: Option<time::SystemTime>,
+    // How many times the same request went through proxy, duplicates are counted here instead of being stored
+    pub(super) seen: usize,
+    // Labels to find pair with 'tag:' in filter query
+    pub(super) tags: Vec<String>,
+}
+
+impl RequestResponsePair {
+    pub(crate) fn is_match(&self, re: &Regex) -> bool {
+        let request_matched = self.request
+            .as_ref()
+            .is_some_and(|request| request.serach_with_re(re));
+
+        return request_matched || self.response.as_ref().is_some_and(|response| response.serach_with_re(re));
+    }
+}
+
+/// What storage did with request came from proxy
+pub(crate) enum StoredRequest {
+    New(ProxyDataForTable),
+    // Request duplicates the kept pair with this ID
+    Duplicate(usize),
+    // Request is noise and strict filter does not store it
+    Dropped
 }
 
 pub(super) struct HTTPStorageIterator<'a> {
@@ -36,7 +62,22 @@ pub(crate) struct HTTPStorage {
     next_id: usize,
 
     // File that could be open in dump mode to write there data on-the-fly
-    file: Option<File>
+    file: Option<RecordAppender>,
+
+    // Deduplication and static content rules, if configured
+    noise: Option<NoiseFilter>,
+
+    // Tokens of stored pairs, to search them without matching each one
+    index: SearchIndex,
+
+    // IDs of pairs which are in file already, the ones not found there on storing are added by other processes
+    stored_ids: HashSet<usize>,
+
+    // IDs of pairs of file which were not loaded (out of strict scope), they are stored as they are
+    skipped_ids: HashSet<usize>,
+
+    // Reference 'pair_id: count' of duplicates of pairs already written to file in dump mode
+    unstored_seen: HashMap<usize, usize>
 }
 
 impl Default for HTTPStorage {
@@ -46,7 +87,12 @@ impl Default for HTTPStorage {
             context_reference: HashMap::new(),
             seq_reference: vec![None; 2000],
             next_id: 0,
-            file: None
+            file: None,
+            noise: None,
+            index: SearchIndex::default(),
+            stored_ids: HashSet::new(),
+            skipped_ids: HashSet::new(),
+            unstored_seen: HashMap::new()
         }
     }
 }
@@ -73,9 +119,42 @@ impl<'a> IntoIterator for &'a HTTPStorage {
 }
 
 impl HTTPStorage {
-    pub(crate) fn put_request(&mut self, request: HyperRequestWrapper, addr: usize) -> ProxyDataForTable {
+    pub(crate) fn set_noise_filter(&mut self, noise: NoiseFilter) {
+        self.noise = Some(noise);
+    }
+
+    pub(crate) fn put_request(&mut self, request: HyperRequestWrapper, addr: usize) -> StoredRequest {
         let index = self.next_id.clone();
 
+        let duplicate_of = match self.noise.as_mut() {
+            Some(noise) => {
+                if noise.strict && noise.is_noise_request(&request) {
+                    return StoredRequest::Dropped;
+                }
+
+                noise.find_duplicate(&request, index)
+            },
+            None => None
+        };
+
+        if let Some(kept_id) = duplicate_of {
+            if let Some(kept) = self.get_mut_by_id(kept_id) {
+                kept.seen += 1;
+                return StoredRequest::Duplicate(kept_id);
+            }
+
+            // In dump mode kept pair is flushed and removed already, so duplicate is counted to update the file later
+            if self.file.is_some() {
+                *self.unstored_seen.entry(kept_id).or_insert(0) += 1;
+                return StoredRequest::Duplicate(kept_id);
+            }
+
+            // Kept pair was removed, so this one is kept instead
+            if let Some(noise) = self.noise.as_mut() {
+                noise.keep(&request, index);
+            }
+        }
+
         let table_record = ProxyDataForTable {
             id: self.next_id.clone(),
             hostname: request.get_host(),
@@ -83,20 +162,42 @@ impl HTTPStorage {
             method: request.method.clone(),
             status_code: String::default(),
             response_length: 0,
+            seen: 1,
         };
 
+        self.index.add_request(index, &request);
         let pair = RequestResponsePair {
             request: Some(request),
             response: None,
             index,
-            timestamp: Some(time::SystemTime::now())
+            timestamp: Some(time::SystemTime::now()),
+            seen: 1,
+            tags: Vec::new()
         };
 
         self.insert_with_id(pair);
         self.context_reference.insert(addr, index);
-        return table_record;
+        return StoredRequest::New(table_record);
+    }
+
+    /// Adds tags to pair of request which is still waiting for response
+    pub(crate) fn annotate(&mut self, tags: Vec<String>, addr: &usize) {
+        let id = match self.context_reference.get(addr) {
+            Some(id) => *id,
+            None => return
+        };
+
+        if let Some(pair) = self.get_mut_by_id(id) {
+            for tag in tags {
+                if !pair.tags.contains(&tag) {
+                    pair.tags.push(tag);
+                }
+            }
+        }
     }
 
+    /// Returns ID of the pair which got response. Pair could be removed right away, if response is noise
+    /// and filter is strict, so caller must not expect to find it in storage
     pub(crate) fn put_response(&mut self, response: HyperResponseWrapper, addr: &usize) -> Option<usize> {
         let id = if let Some(index) = self.context_reference.remove(addr) {
             index
@@ -105,14 +206,82 @@ impl HTTPStorage {
             return None;
         };
 
-        let possible_pair = self.get_mut_by_id(id);
-        return match possible_pair {
+        let strict_noise = self.noise
+            .as_ref()
+            .is_some_and(|noise| noise.strict && noise.is_noise_response(&response));
+
+        let possible_pair = self.seq_reference
+            .get(id)
+            .cloned()
+            .flatten()
+            .map(|index| &mut self.storage[index]);
+
+        let result = match possible_pair {
             Some(pair) => {
+                self.index.add_response(id, &response);
                 pair.response = Some(response);
                 Some(id)
             },
             None => None
         };
+
+        if result.is_some() && strict_noise {
+            self.remove_by_id(id, true).ok()?;
+        }
+
+        return result;
+    }
+
+    /// Checks whether pair is static content which should be hidden
+    pub(crate) fn is_noise(&self, pair: &RequestResponsePair) -> bool {
+        let noise = match self.noise.as_ref() {
+            Some(noise) => noise,
+            None => return false
+        };
+
+        let noisy_request = pair.request
+            .as_ref()
+            .is_some_and(|request| noise.is_noise_request(request));
+
+        let noisy_response = pair.response
+            .as_ref()
+            .is_some_and(|response| noise.is_noise_response(response));
+
+        return noisy_request || noisy_response;
+    }
+
+    /// Finds pairs matching regex in the same way as filter does, looking up candidates in index first
+    pub(crate) fn search(&self, re: &Regex) -> Vec<&RequestResponsePair> {
+        let mut found: Vec<&RequestResponsePair> = match self.index.candidates(re) {
+            Some(ids) => ids
+                .into_iter()
+                .filter_map(|id| self.get_by_id(id))
+                .collect(),
+            None => self.storage.iter().collect()
+        };
+
+        found.retain(|pair| pair.is_match(re));
+        found.sort_by_key(|pair| pair.index);
+        return found;
+    }
+
+    /// Pairs matching filter query, sorted by ID
+    pub(crate) fn query(&self, query: &Query, in_scope: &dyn Fn(&str) -> bool) -> Vec<&RequestResponsePair> {
+        let mut found: Vec<&RequestResponsePair> = match query.required_regex() {
+            Some(re) => self.search(re),
+            None => self.storage.iter().collect()
+        };
+
+        found.retain(|pair| query.matches(pair, in_scope));
+        found.sort_by_key(|pair| pair.index);
+        return found;
+    }
+
+    fn forget_noise(&mut self, id: usize) {
+        let request = self.get_by_id(id).and_then(|pair| pair.request.clone());
+        if let (Some(noise), Some(request)) = (self.noise.as_mut(), request) {
+            noise.forget(&request, id);
+        }
     }
 
     pub(crate) fn get_by_id(&self, id: usize) -> Option<&RequestResponsePair> {
@@ -141,6 +310,46 @@ impl HTTPStorage {
         }
     }
 
+    /// Gives pair another ID, i.e. when the ID is taken by pair which another process stored meanwhile
+    fn renumber(&mut self, id: usize, new_id: usize) {
+        let position = match self.seq_reference.get(id).cloned().flatten() {
+            Some(position) => position,
+            None => return
+        };
+
+        if new_id >= self.seq_reference.len() {
+            self.seq_reference.resize((new_id + 1) * 2, None);
+        }
+
+        self.seq_reference[id] = None;
+        self.seq_reference[new_id] = Some(position);
+        self.next_id = max(self.next_id, new_id + 1);
+
+        // Pair could still wait for response
+        for pair_id in self.context_reference.values_mut() {
+            if *pair_id == id {
+                *pair_id = new_id;
+            }
+        }
+
+        let pair = &mut self.storage[position];
+        pair.index = new_id;
+        let (request, response) = (pair.request.clone(), pair.response.clone());
+
+        self.index.remove(id);
+        if let Some(request) = request.as_ref() {
+            self.index.add_request(new_id, request);
+            if let Some(noise) = self.noise.as_mut() {
+                noise.forget(request, id);
+                noise.remember(request, new_id);
+            }
+        }
+
+        if let Some(response) = response.as_ref() {
+            self.index.add_response(new_id, response);
+        }
+    }
+
     fn insert_with_id(&mut self, mut pair: RequestResponsePair) {
         let id = self.next_id;
         pair.index = id;
@@ -191,6 +400,12 @@ impl HTTPStorage {
             self.remove_by_id(idx, force_uncompleted)?;
         }
 
+        if let Some(noise) = self.noise.as_mut() {
+            noise.forget_all();
+        }
+
+        self.index.clear();
+
         Ok(())
     }
 
@@ -260,6 +475,10 @@ impl HTTPStorage {
             );
         };
 
+        // Uncompleted pair must not suppress its duplicates
+        self.forget_noise(id);
+        self.index.remove(id);
+
         if self.len() == 1 || index == self.len() - 1 {
             let _ = self.storage.pop();
             self.seq_reference[id] = None;
@@ -327,6 +546,7 @@ impl HTTPStorage {
 
             let _ = self.storage.pop();
             self.seq_reference[id] = None;
+            self.index.remove(id);
 
             return Ok(());
         }
@@ -0,0 +1,308 @@
+use std::collections::{HashMap, HashSet};
+
+use bstr::ByteSlice;
+use regex::Regex;
+
+use crate::cruster_proxy::request_response::{HyperRequestWrapper, HyperResponseWrapper};
+
+/// Inverted index of tokens met in stored traffic: URLs, header values and bodies.
+/// It is used to find candidates for a filter regex, so only they are matched with it, not the whole storage
+#[derive(Default)]
+pub(crate) struct SearchIndex {
+    // token -> IDs of pairs containing it
+    postings: HashMap<String, HashSet<usize>>,
+    // ID of pair -> its tokens, needed to remove pair from index
+    tokens_of: HashMap<usize, HashSet<String>>,
+}
+
+// Position of token in literal decides how it can be matched with tokens of index,
+// i.e. the first token of literal 'dmin/lo' could be the end of token 'admin' and the last one - the start of 'login'
+enum TokenPosition {
+    Whole,
+    Start,
+    End,
+    Inside,
+}
+
+fn is_separator(c: char) -> bool {
+    return !c.is_alphanumeric();
+}
+
+fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    return text
+        .split(is_separator)
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_lowercase());
+}
+
+/// Extracts literals which must be present in any text matched by regex. Returns None,
+/// if pattern has constructions making it impossible to say for sure, so index cannot be used
+fn required_literals(pattern: &str) -> Option<Vec<String>> {
+    // Alternation makes any literal optional
+    if pattern.contains('|') {
+        return None;
+    }
+
+    let mut literals: Vec<String> = Vec::new();
+    let mut current = String::new();
+    // Number of literals found before each open group
+    let mut groups: Vec<usize> = Vec::new();
+    let mut chars = pattern.chars().peekable();
+
+    fn flush(current: &mut String, literals: &mut Vec<String>) {
+        if !current.is_empty() {
+            literals.push(std::mem::take(current));
+        }
+    }
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                match chars.next() {
+                    Some(escaped) if escaped.is_ascii_punctuation() => current.push(escaped),
+                    // Code points and unicode classes have arguments, which must not be taken as literal
+                    Some('x' | 'u' | 'U' | 'p' | 'P') | None => return None,
+                    Some(_) => flush(&mut current, &mut literals)
+                }
+            },
+            '*' | '?' => {
+                // Previous symbol is optional
+                current.pop();
+                flush(&mut current, &mut literals);
+            },
+            '{' => {
+                current.pop();
+                flush(&mut current, &mut literals);
+                for next in chars.by_ref() {
+                    if next == '}' {
+                        break;
+                    }
+                }
+            },
+            '[' => {
+                flush(&mut current, &mut literals);
+                // Classes can be nested, i.e. '[a[bc]]' or POSIX '[[:alpha:]]', so class ends when depth is back to 0
+                let mut depth = 1;
+                // ']' right after '[' or '[^' is a literal, not the end
+                let mut first = true;
+                while let Some(next) = chars.next() {
+                    match next {
+                        '\\' => { chars.next(); },
+                        '[' => {
+                            depth += 1;
+                            first = true;
+                            continue;
+                        },
+                        ']' if !first => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        },
+                        _ => {}
+                    }
+
+                    first = next == '^' && first;
+                }
+
+                if depth > 0 {
+                    return None;
+                }
+            },
+            '(' => {
+                flush(&mut current, &mut literals);
+                if chars.peek() == Some(&'?') {
+                    let mut flags = String::new();
+                    let mut is_group = false;
+                    for next in chars.by_ref() {
+                        match next {
+                            ':' | '>' => { is_group = true; break; },
+                            ')' => break,
+                            _ => flags.push(next)
+                        }
+                    }
+
+                    // Whitespaces and comments are ignored in verbose mode
+                    if flags.contains('x') {
+                        return None;
+                    }
+
+                    if !is_group {
+                        continue;
+                    }
+                }
+
+                groups.push(literals.len());
+            },
+            ')' => {
+                flush(&mut current, &mut literals);
+                let start = groups.pop()?;
+                if matches!(chars.peek(), Some('*' | '?' | '{')) {
+                    literals.truncate(start);
+                }
+            },
+            '+' | '.' | '^' | '$' => flush(&mut current, &mut literals),
+            _ => current.push(c)
+        }
+    }
+
+    flush(&mut current, &mut literals);
+    return Some(literals);
+}
+
+impl SearchIndex {
+    fn add_text(&mut self, id: usize, text: &str) {
+        let tokens = self.tokens_of.entry(id).or_default();
+        for token in tokenize(text) {
+            if tokens.insert(token.clone()) {
+                self.postings.entry(token).or_default().insert(id);
+            }
+        }
+    }
+
+    pub(crate) fn add_request(&mut self, id: usize, request: &HyperRequestWrapper) {
+        self.add_text(id, &request.method);
+        self.add_text(id, &request.uri);
+        self.add_text(id, &request.version);
+        for (name, value) in request.headers.iter() {
+            self.add_text(id, name.as_str());
+            self.add_text(id, value.as_bytes().to_str_lossy().as_ref());
+        }
+
+        self.add_text(id, request.body.to_str_lossy().as_ref());
+    }
+
+    pub(crate) fn add_response(&mut self, id: usize, response: &HyperResponseWrapper) {
+        self.add_text(id, &response.version);
+        self.add_text(id, &response.status);
+        for (name, value) in response.headers.iter() {
+            self.add_text(id, name.as_str());
+            self.add_text(id, value.as_bytes().to_str_lossy().as_ref());
+        }
+
+        // Body is already decoded by proxy according to 'Content-Encoding'
+        self.add_text(id, response.body.to_str_lossy().as_ref());
+    }
+
+    pub(crate) fn remove(&mut self, id: usize) {
+        if let Some(tokens) = self.tokens_of.remove(&id) {
+            for token in tokens {
+                if let Some(ids) = self.postings.get_mut(&token) {
+                    ids.remove(&id);
+                    if ids.is_empty() {
+                        self.postings.remove(&token);
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.postings.clear();
+        self.tokens_of.clear();
+    }
+
+    fn ids_of_token(&self, token: &str, position: TokenPosition) -> HashSet<usize> {
+        if let TokenPosition::Whole = position {
+            return self.postings.get(token).cloned().unwrap_or_default();
+        }
+
+        let mut ids: HashSet<usize> = HashSet::default();
+        for (indexed, indexed_ids) in self.postings.iter() {
+            let fit = match position {
+                TokenPosition::Start => indexed.starts_with(token),
+                TokenPosition::End => indexed.ends_with(token),
+                _ => indexed.contains(token)
+            };
+
+            if fit {
+                ids.extend(indexed_ids.iter());
+            }
+        }
+
+        return ids;
+    }
+
+    /// Returns IDs of pairs which could match regex, they still must be checked with it.
+    /// Returns None if regex has no literals to look up, so every pair is a candidate
+    pub(crate) fn candidates(&self, re: &Regex) -> Option<HashSet<usize>> {
+        let literals = required_literals(re.as_str())?;
+        let mut result: Option<HashSet<usize>> = None;
+
+        for literal in literals {
+            let literal = literal.to_lowercase();
+            let tokens: Vec<&str> = literal
+                .split(is_separator)
+                .filter(|token| !token.is_empty())
+                .collect();
+
+            let bounded_left = literal.starts_with(is_separator);
+            let bounded_right = literal.ends_with(is_separator);
+
+            for (i, token) in tokens.iter().enumerate() {
+                let left = bounded_left || i > 0;
+                let right = bounded_right || i + 1 < tokens.len();
+                let position = match (left, right) {
+                    (true, true) => TokenPosition::Whole,
+                    (true, false) => TokenPosition::Start,
+                    (false, true) => TokenPosition::End,
+                    (false, false) => TokenPosition::Inside,
+                };
+
+                let ids = self.ids_of_token(token, position);
+                result = Some(match result {
+                    Some(found) => found.intersection(&ids).cloned().collect(),
+                    None => ids
+                });
+
+                if result.as_ref().is_some_and(|found| found.is_empty()) {
+                    return result;
+                }
+            }
+        }
+
+        return result;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::required_literals;
+
+    fn owned(literals: &[&str]) -> Option<Vec<String>> {
+        return Some(literals.iter().map(|literal| literal.to_string()).collect());
+    }
+
+    #[test]
+    fn plain_literals() {
+        assert_eq!(required_literals("admin/login"), owned(&["admin/login"]));
+        assert_eq!(required_literals("abc.def"), owned(&["abc", "def"]));
+        assert_eq!(required_literals("ab?c"), owned(&["a", "c"]));
+        assert_eq!(required_literals("a|b"), None);
+    }
+
+    #[test]
+    fn simple_class_is_skipped() {
+        assert_eq!(required_literals("x[abc]yz"), owned(&["x", "yz"]));
+        assert_eq!(required_literals("x[]a]yz"), owned(&["x", "yz"]));
+        assert_eq!(required_literals("x[^]a]yz"), owned(&["x", "yz"]));
+        assert_eq!(required_literals("x[\\]]yz"), owned(&["x", "yz"]));
+    }
+
+    #[test]
+    fn posix_class_is_skipped() {
+        assert_eq!(required_literals("[[:alpha:]]bc"), owned(&["bc"]));
+        assert_eq!(required_literals("a[[:digit:][:alpha:]]bc"), owned(&["a", "bc"]));
+    }
+
+    #[test]
+    fn nested_class_is_skipped() {
+        assert_eq!(required_literals("x[a[bc]]yz"), owned(&["x", "yz"]));
+        assert_eq!(required_literals("x[a[^b[c]]]yz"), owned(&["x", "yz"]));
+    }
+
+    #[test]
+    fn unclosed_class_is_not_used() {
+        assert_eq!(required_literals("x[a[bc]yz"), None);
+    }
+}
@@ -1,14 +1,15 @@
 use base64;
-use regex::Regex;
 use serde_json as json;
 use serde::{Serialize, Deserialize};
 use http::{HeaderMap, header::HeaderName, HeaderValue as HTTPHeaderValue};
 
 use std::{
-    io::{Write, BufReader, BufRead},
+    cmp::max,
+    collections::HashSet,
+    path::Path,
     sync::mpsc::Receiver,
     str::FromStr,
-    fs
+    time
 };
 
 use super::{RequestResponsePair, HTTPStorage};
@@ -17,8 +18,9 @@ use crate::{
         HyperRequestWrapper,
         HyperResponseWrapper
     },
+    project::files::{self, FileLock, RecordAppender, RecordReader},
     utils::CrusterError,
-    scope
+    scope::ScopeMatcher
 };
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -29,7 +31,7 @@ struct Header {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
-struct SerializableHTTPRequest {
+pub(crate) struct SerializableHTTPRequest {
     method: String,
     scheme: String,
     host: String,
@@ -41,7 +43,7 @@ struct SerializableHTTPRequest {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
-struct SerializableHTTPResponse {
+pub(crate) struct SerializableHTTPResponse {
     status: String,
     version: String,
     headers: Vec<Header>,
@@ -52,7 +54,15 @@ struct SerializableHTTPResponse {
 pub(crate) struct SerializableProxyData {
     index: usize,
     request: SerializableHTTPRequest,
-    response: Option<SerializableHTTPResponse>
+    response: Option<SerializableHTTPResponse>,
+    // Unix time in seconds when request was captured, older records have none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timestamp: Option<u64>,
+    // How many times the same request was seen, it is written only for deduplicated pairs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    seen: Option<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>
 }
 
 impl SerializableHTTPRequest {
@@ -159,7 +169,7 @@ impl TryInto<HyperRequestWrapper> for SerializableHTTPRequest {
                 }
             };
 
-            let value = HTTPHeaderValue::from_bytes(value_bytes.as_slice()).unwrap();
+            let value = HTTPHeaderValue::from_bytes(value_bytes.as_slice())?;
             headers.append(name.clone(), value);
         }
 
@@ -265,7 +275,7 @@ impl TryInto<HyperResponseWrapper> for SerializableHTTPResponse {
                 }
             };
 
-            let value = HTTPHeaderValue::from_bytes(value_bytes.as_slice()).unwrap();
+            let value = HTTPHeaderValue::from_bytes(value_bytes.as_slice())?;
             headers.append(name.clone(), value);
         }
 
@@ -310,7 +320,12 @@ impl TryFrom<&RequestResponsePair> for SerializableProxyData {
                     }
                     else {
                         None
-                    }
+                    },
+                    timestamp: pair.timestamp
+                        .and_then(|ts| ts.duration_since(time::UNIX_EPOCH).ok())
+                        .map(|duration| duration.as_secs()),
+                    seen: if pair.seen > 1 { Some(pair.seen) } else { None },
+                    tags: pair.tags.clone()
                 }
             )
         };
@@ -336,103 +351,140 @@ impl TryInto<RequestResponsePair> for SerializableProxyData {
             index: id,
             request: Some(request),
             response,
-            // TODO: need to dump timestamps too
-            timestamp: None
+            timestamp: self.timestamp.map(|ts| time::UNIX_EPOCH + time::Duration::from_secs(ts)),
+            seen: self.seen.unwrap_or(1),
+            tags: self.tags
         };
 
         return Ok(pair);
     }
 }
 
+/// IDs of pairs stored in file
+fn read_stored_ids(path: &str) -> Result<HashSet<usize>, CrusterError> {
+    let mut ids: HashSet<usize> = HashSet::new();
+    for read_result in RecordReader::open(path)? {
+        let record: json::Value = json::from_str(&read_result?)?;
+        if let Some(id) = record.get("index").and_then(|id| id.as_u64()) {
+            ids.insert(id as usize);
+        }
+    }
+
+    return Ok(ids);
+}
+
 impl HTTPStorage {
     // 'Sentinel' used in a case when this method called in separate thread, in one-threaded case it can be None
     // It's needed to interrupt thread after some time expired, because rust threads cannot interrupt themselves 
     // https://internals.rust-lang.org/t/thread-cancel-support/3056
-    pub(crate) fn store(&self, path: &str, sentinel: Option<Receiver<usize>>) -> Result<(), CrusterError> {
+    //
+    // Pairs which other processes (dump mode, CLI) appended to file since it was loaded are merged into storage,
+    // pairs captured here get other IDs if those are taken by merged ones. Returns count of merged pairs
+    pub(crate) fn store(&mut self, path: &str, sentinel: Option<Receiver<usize>>) -> Result<usize, CrusterError> {
         if self.file.is_some() {
             return Err(
                 CrusterError::HTTPStorageAlreadyInUse("Could not call 'store' method because file for HTTP Storage was opened in append mode already".to_string())
             )
         }
 
-        let mut fout = fs::OpenOptions::new().create(true).truncate(true).write(true).open(path)?;
+        // Lock is held from reading the file till rewriting it, so nothing appended meanwhile is lost
+        let lock = FileLock::exclusive(path)?;
+        let mut records: Vec<(usize, String)> = Vec::with_capacity(self.storage.len() + self.skipped_ids.len());
+        let mut merged: usize = 0;
+        if Path::new(path).is_file() {
+            for read_result in RecordReader::open(path)? {
+                let line = read_result?;
+                let record: SerializableProxyData = json::from_str(&line)?;
+                if self.skipped_ids.contains(&record.index) {
+                    records.push((record.index, line));
+                    continue;
+                }
+
+                if self.stored_ids.contains(&record.index) {
+                    continue;
+                }
+
+                // ID could be given here to another pair captured meanwhile
+                if self.get_by_id(record.index).is_some() {
+                    let new_id = self.next_id;
+                    self.renumber(record.index, new_id);
+                }
+
+                self.insert_serializable_into_storage(record)?;
+                merged += 1;
+            }
+        }
+
         for pair in &self.storage {
             let serializable_record = SerializableProxyData::try_from(pair)?;
-            let jsn = json::to_string(&serializable_record)?;
-            let _bytes_written = fout.write(jsn.as_bytes())?;
-            let _one_byte_written = fout.write("\n".as_bytes())?;
+            records.push((pair.index, json::to_string(&serializable_record)?));
+        }
 
-            if let Some(rx) = &sentinel {
-                if let Ok(max_duration) = rx.try_recv() {
-                    return Err(CrusterError::JobDurateTooLongError(
-                        format!("Process of storing proxy data was interrupted, it was running longer that {} seconds.", max_duration)
-                    ));
+        records.sort_by_key(|(id, _)| *id);
+        files::rewrite(&lock, path, |fout| {
+            for (_, jsn) in records.iter() {
+                fout.write_all(jsn.as_bytes())?;
+                fout.write_all("\n".as_bytes())?;
+
+                if let Some(rx) = &sentinel {
+                    if let Ok(max_duration) = rx.try_recv() {
+                        return Err(CrusterError::JobDurateTooLongError(
+                            format!("Process of storing proxy data was interrupted, it was running longer that {} seconds.", max_duration)
+                        ));
+                    }
                 }
             }
-        }
 
-        Ok(())
+            Ok(())
+        })?;
+
+        self.stored_ids = self.storage.iter().map(|pair| pair.index).collect();
+        return Ok(merged);
     }
 
     fn insert_serializable_into_storage(&mut self, record: SerializableProxyData) -> Result<(), CrusterError> {
         let pair: RequestResponsePair = record.try_into()?;
+        if let (Some(noise), Some(request)) = (self.noise.as_mut(), pair.request.as_ref()) {
+            noise.remember(request, pair.index);
+        }
+
+        if let Some(request) = pair.request.as_ref() {
+            self.index.add_request(pair.index, request);
+        }
+
+        if let Some(response) = pair.response.as_ref() {
+            self.index.add_response(pair.index, response);
+        }
+
+        self.stored_ids.insert(pair.index);
         self.insert_with_explicit_id(pair.index, pair);
 
         Ok(())
     }
 
     pub(crate) fn load(&mut self, load_path: &str) -> Result<(), CrusterError> {
-        match std::fs::File::open(load_path) {
-            Ok(fin) => {
-                let reader = BufReader::new(fin);
-                for read_result in reader.lines() {
-                    if let Ok(line) = read_result {
-                        let record: SerializableProxyData = json::from_str(&line)?;
-                        self.insert_serializable_into_storage(record)?;
-                    }
-                }
-            },
-            Err(e) => {
-                return Err(e.into());
-            }
+        // Record which cannot be read is an error, otherwise history would be stored back without it
+        for read_result in RecordReader::open(load_path)? {
+            let record: SerializableProxyData = json::from_str(&read_result?)?;
+            self.insert_serializable_into_storage(record)?;
         }
 
         Ok(())
     }
 
-    pub(crate) fn load_with_strict_scope(&mut self, load_path: &str, include: Option<&Vec<Regex>>, exclude: Option<&Vec<Regex>>) -> Result<(), CrusterError> {
-        match std::fs::File::open(load_path) {
-            Ok(fin) => {
-                let reader = BufReader::new(fin);
-                for read_result in reader.lines() {
-                    if let Ok(line) = read_result {
-                        let record: SerializableProxyData = json::from_str(&line)?;
-                        let string_uri = record.request.get_uri();
-                        let uri = string_uri.as_str();
-
-                        let fit = match (include, exclude) {
-                            (None, None) => {
-                                true
-                            },
-                            (Some(included), None) => {
-                                scope::fit_included(uri, included.as_slice())
-                            },
-                            (None, Some(excluded)) => {
-                                scope::fit_excluded(uri, &excluded)
-                            },
-                            (Some(inc), Some(exc)) => {
-                                scope::fit(uri, &inc, &exc)
-                            }
-                        };
-
-                        if fit {
-                            self.insert_serializable_into_storage(record)?;
-                        }
-                    }
-                }
-            },
-            Err(e) => {
-                return Err(e.into());
+    pub(crate) fn load_with_strict_scope(&mut self, load_path: &str, scope: &ScopeMatcher) -> Result<(), CrusterError> {
+        for read_result in RecordReader::open(load_path)? {
+            let record: SerializableProxyData = json::from_str(&read_result?)?;
+            let string_uri = record.request.get_uri();
+            let uri = string_uri.as_str();
+
+            if scope.fits(uri) {
+                self.insert_serializable_into_storage(record)?;
+            }
+            else {
+                // Pair is not loaded, but it stays in file and its ID stays taken
+                self.skipped_ids.insert(record.index);
+                self.next_id = max(self.next_id, record.index + 1);
             }
         }
 
@@ -440,8 +492,50 @@ impl HTTPStorage {
     }
 
     pub(crate) fn keep_open(&mut self, path: &str) -> Result<(), CrusterError> {
-        let new_file = fs::OpenOptions::new().create(true).append(true).open(path)?;
-        self.file = Some(new_file);
+        self.file = Some(RecordAppender::open(path)?);
+        Ok(())
+    }
+
+    /// Called before exit, so stored pairs are on disk when process is gone
+    pub(crate) fn sync(&self) -> Result<(), CrusterError> {
+        if let Some(file) = self.file.as_ref() {
+            file.sync()?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds duplicates counted in dump mode to 'seen' of stored pairs, file is rewritten, so it is done once before exit
+    pub(crate) fn store_seen_counters(&mut self) -> Result<(), CrusterError> {
+        let path = match self.file.as_ref() {
+            Some(file) if ! self.unstored_seen.is_empty() => file.path().to_string(),
+            _ => return Ok(())
+        };
+
+        let lock = FileLock::exclusive(&path)?;
+        let mut records: Vec<String> = Vec::default();
+        for read_result in RecordReader::open(&path)? {
+            let line = read_result?;
+            let mut record: SerializableProxyData = json::from_str(&line)?;
+            match self.unstored_seen.get(&record.index) {
+                Some(count) => {
+                    record.seen = Some(record.seen.unwrap_or(1) + count);
+                    records.push(json::to_string(&record)?);
+                },
+                None => records.push(line)
+            }
+        }
+
+        files::rewrite(&lock, &path, |fout| {
+            for jsn in records.iter() {
+                fout.write_all(jsn.as_bytes())?;
+                fout.write_all("\n".as_bytes())?;
+            }
+
+            Ok(())
+        })?;
+
+        self.unstored_seen.clear();
         Ok(())
     }
 
@@ -449,25 +543,41 @@ impl HTTPStorage {
     //     self.file = None;
     // }
 
-    fn dump_record(&mut self, id: usize) -> Result<(), CrusterError> {
-        let possible_pair = self.get_by_id(id);
-        if let Some(pair) = possible_pair {
-            let serializable_record = SerializableProxyData::try_from(pair)?;
-            let jsn = json::to_string(&serializable_record)?;
-
-            let fout = if let Some(file) = self.file.as_mut() {
-                file
-            }
-            else {
+    /// Returns ID pair is stored with, it is another one if its ID was taken by another process meanwhile
+    fn dump_record(&mut self, id: usize) -> Result<usize, CrusterError> {
+        let lock = match self.file.as_ref() {
+            Some(file) => file.lock()?,
+            None => {
                 return Err(
                     CrusterError::UndefinedError(
                         "No storage is open, Cruster cannot dump record".to_string()
                     )
                 );
-            };
+            }
+        };
+
+        let mut id = id;
+        if let Some(file) = self.file.as_ref().filter(|file| file.is_changed(&lock).unwrap_or(true)) {
+            // TUI, another dump or CLI wrote to file, so IDs taken there are not given here anymore
+            let taken = read_stored_ids(file.path())?;
+            if let Some(max_taken) = taken.iter().max() {
+                self.next_id = max(self.next_id, max_taken + 1);
+            }
 
-            let _bytes_written = fout.write(jsn.as_bytes())?;
-            let _one_byte_written = fout.write("\n".as_bytes())?;
+            if taken.contains(&id) {
+                let new_id = self.next_id;
+                self.renumber(id, new_id);
+                id = new_id;
+            }
+        }
+
+        let possible_pair = self.get_by_id(id);
+        if let Some(pair) = possible_pair {
+            let serializable_record = SerializableProxyData::try_from(pair)?;
+            let jsn = json::to_string(&serializable_record)?;
+            if let Some(fout) = self.file.as_mut() {
+                fout.append_locked(&lock, &jsn)?;
+            }
         }
         else {
             return Err(
@@ -477,7 +587,7 @@ impl HTTPStorage {
             )
         }
 
-        Ok(())
+        Ok(id)
     }
 
     // pub(crate) fn flush(&mut self) -> Result<(), CrusterError> {
@@ -499,9 +609,10 @@ impl HTTPStorage {
     //     Ok(())
     // }
 
-    pub(crate) fn flush_by_id(&mut self, id: usize) -> Result<(), CrusterError> {
+    /// Returns ID pair is stored with, see `dump_record`
+    pub(crate) fn flush_by_id(&mut self, id: usize) -> Result<usize, CrusterError> {
         if let None = self.file {
-            return Ok(());
+            return Ok(id);
         }
 
         let index = self.seq_reference[id];
@@ -522,8 +633,7 @@ impl HTTPStorage {
             );
         }
 
-        self.dump_record(pair.index)?;
-        Ok(())
+        return self.dump_record(pair.index);
     }
 
     // pub(crate) fn smooth_clear(&mut self) {
@@ -539,3 +649,65 @@ impl HTTPStorage {
     //     }
     // }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+    use serde_json as json;
+
+    use super::SerializableProxyData;
+    use crate::http_storage::{HTTPStorage, RequestResponsePair, StoredRequest};
+
+    fn record(id: usize, path: &str) -> String {
+        return format!(
+            r#"{{"index":{},"request":{{"method":"GET","scheme":"http://","host":"example.com","path":"{}","query":null,"version":"HTTP/1.1","headers":[],"body":null}},"response":null}}"#,
+            id,
+            path
+        );
+    }
+
+    fn stored_paths(path: &str) -> Vec<(usize, String)> {
+        return fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let value: json::Value = json::from_str(line).unwrap();
+                (value["index"].as_u64().unwrap() as usize, value["request"]["path"].as_str().unwrap().to_string())
+            })
+            .collect();
+    }
+
+    #[test]
+    fn store_merges_pairs_appended_meanwhile() {
+        let dir = std::env::temp_dir().join(format!("cruster-store-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("http.jsonl").to_string_lossy().to_string();
+        fs::write(&path, format!("{}\n{}\n", record(0, "/a"), record(1, "/b"))).unwrap();
+
+        let mut storage = HTTPStorage::default();
+        storage.load(&path).unwrap();
+
+        // Pair captured here gets ID 2, and another process appends its own pair with the same ID
+        let parsed: SerializableProxyData = json::from_str(&record(0, "/mine")).unwrap();
+        let pair: RequestResponsePair = parsed.try_into().unwrap();
+        let request = pair.request.unwrap();
+        assert!(matches!(storage.put_request(request, 7), StoredRequest::New(record) if record.id == 2));
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(format!("{}\n", record(2, "/other")).as_bytes()).unwrap();
+
+        assert_eq!(storage.store(&path, None).unwrap(), 1);
+        let expected = [(0, "/a"), (1, "/b"), (2, "/other"), (3, "/mine")]
+            .iter()
+            .map(|(id, path)| (*id, path.to_string()))
+            .collect::<Vec<(usize, String)>>();
+        assert_eq!(stored_paths(&path), expected);
+        assert!(storage.get_by_id(3).is_some_and(|pair| pair.index == 3));
+
+        // Nothing is appended since the last store, so nothing is merged again
+        assert_eq!(storage.store(&path, None).unwrap(), 0);
+        assert_eq!(stored_paths(&path), expected);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
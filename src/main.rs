@@ -6,6 +6,14 @@ mod siv_ui;
 mod scope;
 mod dump;
 mod cli;
+mod export_code;
+mod analysis;
+mod project;
+mod noise;
+mod query;
+mod daemon;
+mod api;
+mod session;
 
 
 #[cfg(feature = "rcgen-ca")]
@@ -16,12 +24,13 @@ use hudsucker::{ProxyBuilder, certificate_authority::OpensslAuthority as HudSuck
 
 use tokio;
 use utils::CrusterError;
-use cursive::{Cursive, CbSink};
-use std::{net::{IpAddr, SocketAddr}, process::exit};
+use cursive::Cursive;
+use std::{future::Future, io::Write, net::{IpAddr, SocketAddr, TcpListener}, process::exit, sync::Arc};
 use crossbeam_channel::Sender as CB_Sender;
-use crossbeam_channel::{unbounded, Sender as CrusterSender, Receiver as CrusterReceiver};
-use cruster_proxy::{CrusterHandler, CrusterWSHandler, events::ProxyEvents};
+use crossbeam_channel::{unbounded, bounded, Sender as CrusterSender, Receiver as CrusterReceiver};
+use cruster_proxy::{CrusterHandler, CrusterWSHandler, events::ProxyEvents, hooks::Hooks, scripts::Scripts};
 use dump::DumpMode;
+use session::Sessions;
 
 // use log::debug;
 
@@ -32,39 +41,37 @@ async fn shutdown_signal() {
 }
 
 async fn start_proxy(
-        socket_addr: SocketAddr,
+        listener: TcpListener,
         ca: HudSuckerCA,
-        tx: CrusterSender<ProxyEvents>,
-        cursive_sink: CbSink,
-        dump: bool
-    ) {
+        http_handler: CrusterHandler,
+        ws_handler: CrusterWSHandler,
+        shutdown: impl Future<Output = ()>
+    ) -> Result<(), CrusterError> {
 
     let proxy = ProxyBuilder::new()
-        .with_addr(socket_addr)
+        .with_listener(listener)
         .with_native_tls_client()
         .with_ca(ca)
-        .with_http_handler(
-            CrusterHandler {
-                proxy_tx: tx.clone(),
-                dump,
-                cursive_sink,
-                request_hash: 0
-            }
-        )
-        .with_websocket_handler(
-            CrusterWSHandler {
-                proxy_tx: tx.clone()
-            }
-        )
+        .with_http_handler(http_handler)
+        .with_websocket_handler(ws_handler)
         .build();
 
-    // TODO: something better than unwrap()
-    proxy.start(shutdown_signal()).await.unwrap();
+    // Proxy stops accepting connections on shutdown and waits for in-flight ones
+    proxy.start(shutdown).await?;
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<(), utils::CrusterError> {
-    let (config, mode) = config::handle_user_input()?;
+    let (mut config, mode) = config::handle_user_input()?;
+
+    // Passphrase of encrypted project is asked before UI takes the terminal
+    if let Some(project_path) = config.project.as_ref() {
+        if let Err(err) = project::crypto::unlock(project_path) {
+            eprintln!("Could not unlock project: {}", err);
+            exit(1);
+        }
+    }
 
     if let config::CrusterMode::CLI(subcmd_args) = mode {
         if let Err(err) = cli::launch(subcmd_args, config).await {
@@ -77,6 +84,28 @@ async fn main() -> Result<(), utils::CrusterError> {
         }
     }
 
+    if let config::CrusterMode::SCOPE(subcmd_args) = mode {
+        if let Err(err) = cli::launch_scope(subcmd_args, config) {
+            let err_str: String = err.into();
+            eprintln!("Error in Cruster scope command: {}", err_str);
+            exit(-1);
+        }
+        else {
+            return Ok(());
+        }
+    }
+
+    if let config::CrusterMode::PROJECT(subcmd_args) = mode {
+        if let Err(err) = cli::launch_project(subcmd_args, config) {
+            let err_str: String = err.into();
+            eprintln!("Error in Cruster project command: {}", err_str);
+            exit(-1);
+        }
+        else {
+            return Ok(());
+        }
+    }
+
     utils::generate_key_and_cer(&config.tls_key_name, &config.tls_cer_name);
     let ca: HudSuckerCA = utils::get_ca(&config.tls_key_name, &config.tls_cer_name)?;
 
@@ -87,48 +116,167 @@ async fn main() -> Result<(), utils::CrusterError> {
         config.port
     ));
 
+    // Listener is bound here, so busy port is reported at once and proxy is ready when it is done
+    let listener = TcpListener::bind(socket_addr)?;
+    listener.set_nonblocking(true)?;
+
+    // API is served only by headless modes, in interactive one TUI owns the traffic
+    let api_listener = match config.api.as_ref() {
+        Some(api) if config.dump_mode_enabled() => Some(api::bind(&api.address)?),
+        _ => None
+    };
+
+    // API is not served without token, random one is made for this run if none is set
+    let generated_api_token = match config.api.as_mut() {
+        Some(api) if api_listener.is_some() => api::generate_token_if_missing(api),
+        _ => None
+    };
+
+    let daemon_settings = if let config::CrusterMode::DAEMON(subcmd_args) = &mode {
+        Some(daemon::DaemonSettings::try_from(subcmd_args)?)
+    }
+    else {
+        None
+    };
+
+    // Output file is opened before proxy is started, so wrong path is reported at once
+    let dump_sink = if config.dump_mode_enabled() {
+        let sink = match config.get_output() {
+            None if daemon_settings.is_some() => dump::output::DumpSink::Discard,
+            output => dump::output::DumpSink::try_from(output)?
+        };
+
+        Some(sink)
+    }
+    else {
+        None
+    };
+
     let (tx, rx): (CrusterSender<ProxyEvents>, CrusterReceiver<ProxyEvents>) = unbounded();
     let siv = Cursive::default();
     let cb_sink: CB_Sender<Box<dyn FnOnce(&mut Cursive)+Send>> = siv.cb_sink().clone();
     let dump_mode = config.dump_mode_enabled();
+    let scripts = Arc::new(Scripts::load(config.scripts.as_ref())?);
+    // Tokens are shared, so login is sent once for proxy, API and TUI repeaters
+    let sessions = Arc::new(Sessions::from(&config));
+    let http_handler = CrusterHandler {
+        proxy_tx: tx.clone(),
+        dump: dump_mode,
+        cursive_sink: cb_sink,
+        request_hash: 0,
+        hooks: Arc::new(Hooks::from(config.hooks.as_ref())),
+        scripts: scripts.clone(),
+        sessions: sessions.clone(),
+        hooked_request: None,
+        session_request: None
+    };
+    let ws_handler = CrusterWSHandler {
+        proxy_tx: tx,
+        scripts
+    };
 
-    tokio::task::spawn(
-        async move {
-            start_proxy(
-                socket_addr,
-                ca,
-                tx,
-                cb_sink,
-                dump_mode
-            ).await
-        }
-    );
-
-    if config.dump_mode_enabled() {
-        let dump_thread = tokio::task::spawn(
+    if let Some(sink) = dump_sink {
+        let settings = daemon_settings.unwrap_or_default();
+        let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+        let mut proxy = tokio::task::spawn(
             async move {
-                dump::launch_dump(rx, config).await;
+                start_proxy(
+                    listener,
+                    ca,
+                    http_handler,
+                    ws_handler,
+                    async { let _ = stop_rx.await; }
+                ).await
             }
         );
 
-        tokio::task::spawn(
-            async move {
+        let mode_name = if let config::CrusterMode::DAEMON(_) = mode { "daemon" } else { "dump" };
+        let api_address = config.api.as_ref().map(|api| api.address.clone());
+        let api = match api_listener {
+            Some(listener) => Some(api::serve(listener, &config, mode_name, sessions.clone())?),
+            None => None
+        };
+
+        // Dump loop blocks on channels, so it is not run on async workers
+        let (shutdown_tx, shutdown_rx) = bounded::<()>(1);
+        let mut dump_thread = tokio::task::spawn_blocking(
+            move || {
+                dump::launch_dump(rx, shutdown_rx, config, sink, api);
+            }
+        );
+
+        let pid_file = match settings.pid_file.as_ref() {
+            Some(path) => Some(daemon::PidFile::create(path)?),
+            None => None
+        };
+
+        if let config::CrusterMode::DAEMON(_) = mode {
+            match (api_address.as_ref(), generated_api_token.as_ref()) {
+                (Some(api_address), Some(token)) => println!("Cruster is ready, proxy is listening on {}, API on {} with token {}, PID: {}", socket_addr, api_address, token, std::process::id()),
+                (Some(api_address), None) => println!("Cruster is ready, proxy is listening on {}, API on {}, PID: {}", socket_addr, api_address, std::process::id()),
+                (None, _) => println!("Cruster is ready, proxy is listening on {}, PID: {}", socket_addr, std::process::id())
+            }
+
+            let _ = std::io::stdout().flush();
+        }
+        else if let (Some(api_address), Some(token)) = (api_address.as_ref(), generated_api_token.as_ref()) {
+            // Stdout is for dump output
+            eprintln!("API on {} requires 'Authorization: Bearer {}'", api_address, token);
+        }
+
+        tokio::select! {
+            // Dump loop ends before signal only if proxy is gone
+            result = &mut dump_thread => {
+                if let Ok(Ok(Err(err))) = tokio::time::timeout(settings.grace_period, proxy).await {
+                    eprintln!("Proxy error: {}", err);
+                }
+
+                if let Err(err) = result {
+                    eprintln!("{}", err);
+                }
+
+                drop(pid_file);
+                exit(1);
+            },
+            signal = daemon::termination_signal() => {
+                if let Err(err) = signal {
+                    eprintln!("Unable to listen for shutdown signal: {}", err);
+                }
+
+                let _ = stop_tx.send(());
+                if tokio::time::timeout(settings.grace_period, &mut proxy).await.is_err() {
+                    eprintln!(
+                        "Some connections are still open after {} seconds, they are dropped",
+                        settings.grace_period.as_secs()
+                    );
+                }
+
+                let _ = shutdown_tx.send(());
                 if let Err(err) = dump_thread.await {
                     eprintln!("{}", err);
+                    drop(pid_file);
                     exit(1);
                 }
             }
-        );
-
-        match tokio::signal::ctrl_c().await {
-            Ok(_) => { exit(0); },
-            Err(err) => {
-                panic!("Unable to listen for shutdown signal: {}", err);
-            }
         }
+
+        drop(pid_file);
+        exit(0);
     }
     else {
-        siv_ui::bootstrap_ui(siv, config, rx);
+        tokio::task::spawn(
+            async move {
+                start_proxy(
+                    listener,
+                    ca,
+                    http_handler,
+                    ws_handler,
+                    shutdown_signal()
+                ).await
+            }
+        );
+
+        siv_ui::bootstrap_ui(siv, config, rx, sessions);
         Ok(())
     }
 }
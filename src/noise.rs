@@ -0,0 +1,185 @@
+use std::fmt;
+use std::str::FromStr;
+use std::hash::{Hash, Hasher};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use serde::{Serialize, Deserialize};
+
+use crate::config::Noise;
+use crate::cruster_proxy::request_response::{HyperRequestWrapper, HyperResponseWrapper};
+
+pub(crate) const DEFAULT_DEDUPE_KEYS: [DedupeKey; 3] = [DedupeKey::Method, DedupeKey::Url, DedupeKey::Body];
+
+// Static content which is rarely interesting: images, fonts and styles
+pub(crate) const DEFAULT_HIDDEN_EXTENSIONS: [&str; 13] = [
+    "png", "jpg", "jpeg", "gif", "webp", "svg", "ico", "bmp",
+    "woff", "woff2", "ttf", "otf", "css"
+];
+pub(crate) const DEFAULT_HIDDEN_MIME: [&str; 3] = ["image/", "font/", "text/css"];
+
+/// Part of request to make key of deduplication with
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum DedupeKey {
+    Method,
+    // Full URL with query
+    Url,
+    // URL without query, so i.e. requests with cache busters are duplicates
+    Path,
+    Body,
+}
+
+impl FromStr for DedupeKey {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s.trim().to_lowercase().as_str() {
+            "method" => Ok(DedupeKey::Method),
+            "url" => Ok(DedupeKey::Url),
+            "path" => Ok(DedupeKey::Path),
+            "body" => Ok(DedupeKey::Body),
+            _ => Err(format!("Unknown deduplication key '{}', possible are: method, url, path, body", s))
+        };
+    }
+}
+
+impl fmt::Display for DedupeKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DedupeKey::Method => "method",
+            DedupeKey::Url => "url",
+            DedupeKey::Path => "path",
+            DedupeKey::Body => "body",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Decides which pairs are noise, i.e. duplicates and static content, to keep storage and table focused
+pub(crate) struct NoiseFilter {
+    dedupe: Vec<DedupeKey>,
+    extensions: Vec<String>,
+    mime: Vec<String>,
+    // If set, hidden pairs are not stored at all, otherwise they are just hidden from table
+    pub(crate) strict: bool,
+    // Hash of dedupe key -> ID of the kept pair
+    kept: HashMap<u64, usize>,
+}
+
+impl From<&Noise> for NoiseFilter {
+    fn from(noise: &Noise) -> Self {
+        NoiseFilter {
+            dedupe: noise.dedupe.clone().unwrap_or_default(),
+            extensions: noise.hide_extensions
+                .as_deref()
+                .unwrap_or_default()
+                .iter()
+                .map(|ext| ext.trim_start_matches('.').to_lowercase())
+                .collect(),
+            mime: noise.hide_mime
+                .as_deref()
+                .unwrap_or_default()
+                .iter()
+                .map(|mime| mime.to_lowercase())
+                .collect(),
+            strict: noise.strict,
+            kept: HashMap::default(),
+        }
+    }
+}
+
+fn url_without_query(uri: &str) -> &str {
+    return uri
+        .split(['?', '#'])
+        .next()
+        .unwrap_or(uri);
+}
+
+impl NoiseFilter {
+    fn dedupe_key(&self, request: &HyperRequestWrapper) -> Option<u64> {
+        if self.dedupe.is_empty() {
+            return None;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        for key in self.dedupe.iter() {
+            match key {
+                DedupeKey::Method => request.method.hash(&mut hasher),
+                DedupeKey::Url => request.uri.hash(&mut hasher),
+                DedupeKey::Path => url_without_query(&request.uri).hash(&mut hasher),
+                DedupeKey::Body => request.body.hash(&mut hasher),
+            }
+        }
+
+        return Some(hasher.finish());
+    }
+
+    /// Returns ID of the kept pair, if request duplicates it, otherwise remembers request as the kept one
+    pub(crate) fn find_duplicate(&mut self, request: &HyperRequestWrapper, id: usize) -> Option<usize> {
+        let key = self.dedupe_key(request)?;
+        return match self.kept.get(&key) {
+            Some(kept_id) => Some(*kept_id),
+            None => {
+                self.kept.insert(key, id);
+                None
+            }
+        };
+    }
+
+    /// Remembers request of stored pair, so its duplicates will be found
+    pub(crate) fn remember(&mut self, request: &HyperRequestWrapper, id: usize) {
+        if let Some(key) = self.dedupe_key(request) {
+            self.kept.entry(key).or_insert(id);
+        }
+    }
+
+    /// Makes request the kept one, replacing the previous, i.e. when it was removed from storage
+    pub(crate) fn keep(&mut self, request: &HyperRequestWrapper, id: usize) {
+        if let Some(key) = self.dedupe_key(request) {
+            self.kept.insert(key, id);
+        }
+    }
+
+    /// Forgets request, i.e. if it was not completed, so the next duplicate is kept instead of it
+    pub(crate) fn forget(&mut self, request: &HyperRequestWrapper, id: usize) {
+        if let Some(key) = self.dedupe_key(request) {
+            if self.kept.get(&key) == Some(&id) {
+                self.kept.remove(&key);
+            }
+        }
+    }
+
+    pub(crate) fn forget_all(&mut self) {
+        self.kept.clear();
+    }
+
+    pub(crate) fn is_noise_request(&self, request: &HyperRequestWrapper) -> bool {
+        if self.extensions.is_empty() {
+            return false;
+        }
+
+        let path = url_without_query(&request.uri);
+        let last_segment = path.rsplit('/').next().unwrap_or(path);
+        return match last_segment.rsplit_once('.') {
+            Some((_, ext)) => {
+                let ext = ext.to_lowercase();
+                self.extensions.iter().any(|hidden| hidden == &ext)
+            },
+            None => false
+        };
+    }
+
+    pub(crate) fn is_noise_response(&self, response: &HyperResponseWrapper) -> bool {
+        if self.mime.is_empty() {
+            return false;
+        }
+
+        return match response.headers.get("content-type").and_then(|value| value.to_str().ok()) {
+            Some(content_type) => {
+                let content_type = content_type.trim().to_lowercase();
+                self.mime.iter().any(|hidden| content_type.starts_with(hidden))
+            },
+            None => false
+        };
+    }
+}
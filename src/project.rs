@@ -0,0 +1,102 @@
+pub(crate) mod files;
+pub(crate) mod crypto;
+pub(crate) mod views;
+pub(crate) mod scope;
+
+use std::fs;
+use std::path;
+use serde_yaml as yml;
+use serde::{Serialize, Deserialize};
+
+use crate::utils::CrusterError;
+use crypto::EncryptionMeta;
+use files::{FileLock, RecordCodec};
+
+pub(crate) const META_FILE_NAME: &str = "project.yaml";
+
+/// Description of project, stored in project directory near to the data
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub(crate) struct ProjectMeta {
+    pub(crate) name: String,
+    pub(crate) description: Option<String>,
+    // Unix time in seconds
+    pub(crate) created: u64,
+    pub(crate) cruster_version: String,
+    // Present only if project data is encrypted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) encryption: Option<EncryptionMeta>,
+    // Algorithm to compress records with, present only if project data is compressed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) compression: Option<String>,
+}
+
+impl ProjectMeta {
+    pub(crate) fn new(name: &str, description: Option<&str>) -> Self {
+        ProjectMeta {
+            name: name.to_string(),
+            description: description.map(|d| d.to_string()),
+            created: unix_now(),
+            cruster_version: env!("CARGO_PKG_VERSION").to_string(),
+            encryption: None,
+            compression: None,
+        }
+    }
+
+    /// Returns `None` if project was not initialized, i.e. it was created by saving data only
+    pub(crate) fn load(project: &str) -> Result<Option<Self>, CrusterError> {
+        let meta_path = format!("{}/{}", project, META_FILE_NAME);
+        if !path::Path::new(&meta_path).is_file() {
+            return Ok(None);
+        }
+
+        let file = fs::File::open(&meta_path)?;
+        let meta: ProjectMeta = yml::from_reader(file)?;
+
+        return Ok(Some(meta));
+    }
+
+    /// Metadata keeps salt and check value of encrypted project, so it is replaced atomically, like data files.
+    /// It is YAML, not records, so it is never compressed or encrypted itself
+    pub(crate) fn store(&self, project: &str) -> Result<(), CrusterError> {
+        let meta_path = format!("{}/{}", project, META_FILE_NAME);
+        let content = yml::to_string(self)?;
+        let lock = FileLock::exclusive(&meta_path)?;
+        files::rewrite_with(&lock, &meta_path, RecordCodec::with_options(&meta_path, false, None), |fout| {
+            fout.write_all(content.as_bytes())?;
+            Ok(())
+        })
+    }
+}
+
+/// Directory of project which the file belongs to
+pub(crate) fn project_of(file_path: &str) -> String {
+    return path::Path::new(file_path)
+        .parent()
+        .map(|dir| dir.to_string_lossy().to_string())
+        .unwrap_or_default();
+}
+
+pub(crate) fn unix_now() -> u64 {
+    return std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+}
+
+/// Formats unix time as 'YYYY-MM-DD hh:mm:ss UTC'
+pub(crate) fn format_timestamp(timestamp: u64) -> String {
+    return match time::OffsetDateTime::from_unix_timestamp(timestamp as i64) {
+        Ok(dt) => {
+            format!(
+                "{}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+                dt.year(),
+                dt.month() as u8,
+                dt.day(),
+                dt.hour(),
+                dt.minute(),
+                dt.second()
+            )
+        },
+        Err(_) => timestamp.to_string()
+    };
+}
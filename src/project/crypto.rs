@@ -0,0 +1,199 @@
+use std::path;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::num::NonZeroU32;
+use std::collections::HashMap;
+use std::io::IsTerminal;
+
+use ring::{aead, pbkdf2, rand::{SecureRandom, SystemRandom}};
+use serde::{Serialize, Deserialize};
+
+use super::{ProjectMeta, META_FILE_NAME, project_of};
+use crate::utils::CrusterError;
+
+pub(crate) const PASSPHRASE_ENV: &str = "CRUSTER_PASSPHRASE";
+const CIPHER_NAME: &str = "chacha20-poly1305";
+const KDF_NAME: &str = "pbkdf2-hmac-sha256";
+// OWASP guidance for PBKDF2-HMAC-SHA256, existing projects keep iterations stored in their metadata
+const DEFAULT_ITERATIONS: u32 = 600_000;
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+// Known text sealed with the key, it is used to check passphrase before touching data
+const CHECK_TEXT: &str = "cruster";
+
+/// Parameters of project encryption, stored in project metadata. There is nothing secret here
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub(crate) struct EncryptionMeta {
+    pub(crate) cipher: String,
+    pub(crate) kdf: String,
+    pub(crate) iterations: u32,
+    // base64
+    pub(crate) salt: String,
+    pub(crate) check: String,
+}
+
+impl EncryptionMeta {
+    /// Creates new parameters with random salt and the key derived from passphrase with them
+    pub(crate) fn new(passphrase: &str) -> Result<(Self, ProjectKey), CrusterError> {
+        let mut salt = [0_u8; SALT_LEN];
+        SystemRandom::new()
+            .fill(&mut salt)
+            .map_err(|_| CrusterError::EncryptionError("Could not generate salt".to_string()))?;
+
+        let mut meta = EncryptionMeta {
+            cipher: CIPHER_NAME.to_string(),
+            kdf: KDF_NAME.to_string(),
+            iterations: DEFAULT_ITERATIONS,
+            salt: base64::encode(salt),
+            check: String::default(),
+        };
+
+        let key = ProjectKey::derive(passphrase, &meta)?;
+        meta.check = base64::encode(key.seal(META_FILE_NAME, CHECK_TEXT.as_bytes())?);
+
+        return Ok((meta, key));
+    }
+}
+
+pub(crate) struct ProjectKey {
+    key: aead::LessSafeKey,
+}
+
+impl ProjectKey {
+    fn derive(passphrase: &str, meta: &EncryptionMeta) -> Result<Self, CrusterError> {
+        if meta.cipher != CIPHER_NAME || meta.kdf != KDF_NAME {
+            return Err(
+                CrusterError::EncryptionError(format!("Unsupported encryption '{}' with '{}'", &meta.cipher, &meta.kdf))
+            );
+        }
+
+        let iterations = NonZeroU32::new(meta.iterations)
+            .ok_or_else(|| CrusterError::EncryptionError("Number of KDF iterations must not be 0".to_string()))?;
+
+        let salt = base64::decode(&meta.salt)?;
+        let mut key_bytes = [0_u8; KEY_LEN];
+        pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, iterations, &salt, passphrase.as_bytes(), &mut key_bytes);
+
+        let unbound = aead::UnboundKey::new(&aead::CHACHA20_POLY1305, &key_bytes)
+            .map_err(|_| CrusterError::EncryptionError("Could not create encryption key".to_string()))?;
+
+        return Ok(ProjectKey { key: aead::LessSafeKey::new(unbound) });
+    }
+
+    /// Encrypts record, `file_name` is authenticated too, so records cannot be moved between files.
+    /// Result is nonce followed by ciphertext
+    pub(crate) fn seal(&self, file_name: &str, record: &[u8]) -> Result<Vec<u8>, CrusterError> {
+        let mut nonce = [0_u8; aead::NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| CrusterError::EncryptionError("Could not generate nonce".to_string()))?;
+
+        let mut data = record.to_vec();
+        self.key
+            .seal_in_place_append_tag(aead::Nonce::assume_unique_for_key(nonce), aead::Aad::from(file_name.as_bytes()), &mut data)
+            .map_err(|_| CrusterError::EncryptionError("Could not encrypt record".to_string()))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&data);
+
+        return Ok(sealed);
+    }
+
+    pub(crate) fn open(&self, file_name: &str, sealed: &[u8]) -> Result<Vec<u8>, CrusterError> {
+        if sealed.len() < aead::NONCE_LEN {
+            return Err(CrusterError::EncryptionError("Encrypted record is too short".to_string()));
+        }
+
+        let mut nonce = [0_u8; aead::NONCE_LEN];
+        nonce.copy_from_slice(&sealed[..aead::NONCE_LEN]);
+        let mut data = sealed[aead::NONCE_LEN..].to_vec();
+        let plain = self.key
+            .open_in_place(aead::Nonce::assume_unique_for_key(nonce), aead::Aad::from(file_name.as_bytes()), &mut data)
+            .map_err(|_| CrusterError::EncryptionError("Could not decrypt record, it is damaged or key is wrong".to_string()))?;
+
+        return Ok(plain.to_vec());
+    }
+}
+
+// Keys of unlocked projects by their canonical paths, so passphrase is asked once per process
+fn keys() -> &'static Mutex<HashMap<path::PathBuf, Arc<ProjectKey>>> {
+    static KEYS: OnceLock<Mutex<HashMap<path::PathBuf, Arc<ProjectKey>>>> = OnceLock::new();
+    return KEYS.get_or_init(|| Mutex::new(HashMap::default()));
+}
+
+fn canonical(project: &str) -> path::PathBuf {
+    let project = if project.is_empty() { "." } else { project };
+    return path::Path::new(project)
+        .canonicalize()
+        .unwrap_or_else(|_| path::PathBuf::from(project));
+}
+
+pub(crate) fn remember(project: &str, key: ProjectKey) -> Arc<ProjectKey> {
+    let key = Arc::new(key);
+    keys().lock().unwrap().insert(canonical(project), key.clone());
+    return key;
+}
+
+/// Takes passphrase from environment or asks user for it, if there is a terminal to ask in
+pub(crate) fn read_passphrase(prompt: &str, confirm: bool) -> Result<String, CrusterError> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+
+    if !std::io::stdin().is_terminal() {
+        return Err(
+            CrusterError::EncryptionError(format!("Passphrase is required, set it with {} environment variable", PASSPHRASE_ENV))
+        );
+    }
+
+    let passphrase = rpassword::prompt_password(prompt)?;
+    if passphrase.is_empty() {
+        return Err(CrusterError::EncryptionError("Passphrase must not be empty".to_string()));
+    }
+
+    if confirm && rpassword::prompt_password("Repeat passphrase: ")? != passphrase {
+        return Err(CrusterError::EncryptionError("Passphrases do not match".to_string()));
+    }
+
+    return Ok(passphrase);
+}
+
+fn unlock_with(project: &str, interactive: bool) -> Result<Option<Arc<ProjectKey>>, CrusterError> {
+    // Metadata is checked first, since project could be decrypted after it was unlocked
+    let encryption = match ProjectMeta::load(project)?.and_then(|meta| meta.encryption) {
+        Some(encryption) => encryption,
+        None => return Ok(None)
+    };
+
+    if let Some(key) = keys().lock().unwrap().get(&canonical(project)) {
+        return Ok(Some(key.clone()));
+    }
+
+    let passphrase = if interactive {
+        read_passphrase(&format!("Passphrase for project '{}': ", project), false)?
+    }
+    else {
+        std::env::var(PASSPHRASE_ENV).map_err(|_| {
+            CrusterError::EncryptionError(
+                format!("Project at '{}' is encrypted, but it was not unlocked and {} is not set", project, PASSPHRASE_ENV)
+            )
+        })?
+    };
+
+    let key = ProjectKey::derive(&passphrase, &encryption)?;
+    if key.open(META_FILE_NAME, &base64::decode(&encryption.check)?).is_err() {
+        return Err(CrusterError::EncryptionError(format!("Wrong passphrase for project at '{}'", project)));
+    }
+
+    return Ok(Some(remember(project, key)));
+}
+
+/// Returns key of project if it is encrypted, asking passphrase if it was not unlocked yet
+pub(crate) fn unlock(project: &str) -> Result<Option<Arc<ProjectKey>>, CrusterError> {
+    return unlock_with(project, true);
+}
+
+/// Returns key of project which the file belongs to, without asking anything,
+/// since it can be called when terminal is used by UI
+pub(crate) fn key_for_file(file_path: &str) -> Result<Option<Arc<ProjectKey>>, CrusterError> {
+    return unlock_with(&project_of(file_path), false);
+}
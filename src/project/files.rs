@@ -0,0 +1,872 @@
+use fs2::FileExt;
+use log::warn;
+use std::sync::Arc;
+use std::collections::VecDeque;
+use serde_json as json;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+
+use super::{ProjectMeta, project_of};
+use super::crypto::{self, ProjectKey};
+use crate::utils::CrusterError;
+
+// Project files are JSON Lines: every record is a single line, so a record without
+// line break at the end of file is either being written right now or was torn by crash.
+//
+// Writers (appending and rewriting) hold exclusive advisory lock on '<file>.lock',
+// so they never interleave. Full rewrites go to '<file>.tmp' which is renamed over
+// original file, so readers do not need locks: they see either old or new content.
+//
+// If project is encrypted, every record is encrypted separately (see 'RecordCodec'), so appending stays cheap
+// and torn records are detected the same way. If project is compressed, records are packed into binary frames
+// instead of lines, so records written together are compressed together and payload is not base64-encoded:
+//
+//     FRAME_MAGIC | flags (1 byte) | length of payload (u32 LE) | payload | length of payload (u32 LE) | FRAME_END
+//
+// Payload is records joined with line breaks, compressed and encrypted after that. Text lines never contain NUL,
+// so frames are told from lines by the first byte, and length at the end lets to check the last frame of file.
+
+const LOCK_SUFFIX: &str = ".lock";
+const TMP_SUFFIX: &str = ".tmp";
+// The only algorithm of compression, it is stored in project metadata
+pub(crate) const COMPRESSION_ALGORITHM: &str = "deflate";
+const COMPRESSED_FLAG: char = 'z';
+const ENCRYPTED_FLAG: char = 'e';
+const FRAME_MAGIC: &[u8; 4] = b"\0CRF";
+const FRAME_END: &[u8; 2] = b"\0\n";
+const FRAME_HEADER_SIZE: usize = 9;
+const FRAME_TRAILER_SIZE: usize = 6;
+const FRAME_COMPRESSED: u8 = 1;
+const FRAME_ENCRYPTED: u8 = 2;
+// Records written together are packed into frames of about this size before compression
+const FRAME_BLOCK_SIZE: usize = 1024 * 1024;
+// Block size used to look for the last line break when torn record is cut off
+const TAIL_BLOCK_SIZE: u64 = 64 * 1024;
+
+/// Exclusive advisory lock of project file, it is released on drop.
+/// Locks are not reentrant, so one operation must take one lock and pass it further.
+pub(crate) struct FileLock {
+    file: File,
+}
+
+impl FileLock {
+    pub(crate) fn exclusive(path: &str) -> Result<Self, CrusterError> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .open(format!("{}{}", path, LOCK_SUFFIX))?;
+
+        FileExt::lock_exclusive(&file)?;
+        return Ok(FileLock { file });
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+/// Turns records into lines and frames of project file and back, compressing and encrypting them
+/// as project metadata says. Plain record is a JSON object as is, encrypted one is
+/// 'e:<base64 of payload>'. Compressed records are written in frames, older versions wrote them
+/// as lines with flag 'z' before 'e', such lines are still read.
+pub(crate) struct RecordCodec {
+    path: String,
+    file_name: String,
+    compress: bool,
+    key: Option<Arc<ProjectKey>>,
+    // Records which are not encrypted are read from encrypted project only while it is encrypted or decrypted
+    allow_plain: bool,
+    // Whether project is encrypted, metadata is read when the first record which is not encrypted is met
+    project_encrypted: Option<bool>,
+}
+
+impl RecordCodec {
+    /// Codec to read file, key is looked up only when encrypted record is met
+    pub(crate) fn new(path: &str) -> Self {
+        return RecordCodec::with_options(path, false, None);
+    }
+
+    /// Codec to read file while project is encrypted or decrypted, so records of both kinds are met there
+    pub(crate) fn for_conversion(path: &str) -> Self {
+        let mut codec = RecordCodec::new(path);
+        codec.allow_plain = true;
+        return codec;
+    }
+
+    /// Codec to write file, records are compressed and encrypted if project is compressed and encrypted
+    pub(crate) fn for_writing(path: &str) -> Result<Self, CrusterError> {
+        let compress = ProjectMeta::load(&project_of(path))?
+            .is_some_and(|meta| meta.compression.is_some());
+
+        return Ok(RecordCodec::with_options(path, compress, crypto::key_for_file(path)?));
+    }
+
+    pub(crate) fn with_options(path: &str, compress: bool, key: Option<Arc<ProjectKey>>) -> Self {
+        let file_name = std::path::Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        RecordCodec {
+            path: path.to_string(),
+            file_name,
+            compress,
+            key,
+            allow_plain: false,
+            project_encrypted: None,
+        }
+    }
+
+    fn is_plain(&self) -> bool {
+        return !self.compress && self.key.is_none();
+    }
+
+    /// Line of record without line break, compressed records are not written as lines
+    fn encode_line(&self, record: &str) -> Result<String, CrusterError> {
+        return match self.key.as_ref() {
+            Some(key) => Ok(format!("{}:{}", ENCRYPTED_FLAG, base64::encode(key.seal(&self.file_name, record.as_bytes())?))),
+            None => Ok(record.to_string())
+        };
+    }
+
+    /// Frame with records joined with line breaks
+    fn encode_frame(&self, block: &[u8]) -> Result<Vec<u8>, CrusterError> {
+        let mut flags: u8 = 0;
+        let mut payload = block.to_vec();
+        if self.compress {
+            let mut encoder = DeflateEncoder::new(Vec::with_capacity(payload.len() / 2), Compression::default());
+            encoder.write_all(&payload)?;
+            payload = encoder.finish()?;
+            flags |= FRAME_COMPRESSED;
+        }
+
+        if let Some(key) = self.key.as_ref() {
+            payload = key.seal(&self.file_name, &payload)?;
+            flags |= FRAME_ENCRYPTED;
+        }
+
+        let length = u32::try_from(payload.len())
+            .map_err(|_| CrusterError::UndefinedError(format!("Record is too large to be stored in '{}'", &self.path)))?
+            .to_le_bytes();
+
+        let mut frame: Vec<u8> = Vec::with_capacity(FRAME_HEADER_SIZE + payload.len() + FRAME_TRAILER_SIZE);
+        frame.extend_from_slice(FRAME_MAGIC);
+        frame.push(flags);
+        frame.extend_from_slice(&length);
+        frame.extend_from_slice(&payload);
+        frame.extend_from_slice(&length);
+        frame.extend_from_slice(FRAME_END);
+
+        return Ok(frame);
+    }
+
+    /// Records as they are written to file: lines with line breaks or frames, if records are compressed
+    fn encode_records<S: AsRef<str>>(&self, records: &[S]) -> Result<Vec<u8>, CrusterError> {
+        let mut encoded: Vec<u8> = Vec::default();
+        if !self.compress {
+            for record in records {
+                encoded.extend_from_slice(self.encode_line(record.as_ref())?.as_bytes());
+                encoded.push(b'\n');
+            }
+
+            return Ok(encoded);
+        }
+
+        let mut block: Vec<u8> = Vec::default();
+        for record in records {
+            if !block.is_empty() {
+                block.push(b'\n');
+            }
+
+            block.extend_from_slice(record.as_ref().as_bytes());
+            if block.len() >= FRAME_BLOCK_SIZE {
+                encoded.extend(self.encode_frame(&block)?);
+                block.clear();
+            }
+        }
+
+        if !block.is_empty() {
+            encoded.extend(self.encode_frame(&block)?);
+        }
+
+        return Ok(encoded);
+    }
+
+    /// Records of encrypted project are authenticated, so the ones which are not encrypted are not trusted:
+    /// anyone who can write to file could add them
+    fn check_plain_allowed(&mut self) -> Result<(), CrusterError> {
+        if self.allow_plain {
+            return Ok(());
+        }
+
+        if self.project_encrypted.is_none() {
+            let encrypted = ProjectMeta::load(&project_of(&self.path))?
+                .is_some_and(|meta| meta.encryption.is_some());
+            self.project_encrypted = Some(encrypted);
+        }
+
+        if self.project_encrypted == Some(true) {
+            return Err(
+                CrusterError::EncryptionError(
+                    format!("'{}' contains record which is not encrypted, though project is encrypted. If encryption was interrupted, finish it with 'cruster project encrypt'", &self.path)
+                )
+            );
+        }
+
+        return Ok(());
+    }
+
+    /// Flags and base64 of payload, if line is encoded record
+    fn split_encoded(line: &str) -> Option<(&str, &str)> {
+        return line
+            .split_once(':')
+            .filter(|(flags, _)| !flags.is_empty() && flags.chars().all(|c| c == COMPRESSED_FLAG || c == ENCRYPTED_FLAG));
+    }
+
+    /// How many bytes base64 adds to payload of encoded record, 0 for plain one
+    pub(crate) fn base64_overhead(line: &str) -> usize {
+        return match RecordCodec::split_encoded(line) {
+            Some((_, encoded)) => {
+                let padding = encoded.bytes().rev().take_while(|b| *b == b'=').count();
+                encoded.len() - (encoded.len() / 4 * 3).saturating_sub(padding)
+            },
+            None => 0
+        };
+    }
+
+    fn decode(&mut self, line: &str) -> Result<String, CrusterError> {
+        let (flags, encoded) = match RecordCodec::split_encoded(line) {
+            Some((flags, encoded)) => (flags, encoded),
+            None => {
+                self.check_plain_allowed()?;
+                return Ok(line.to_string());
+            }
+        };
+
+        if !flags.contains(ENCRYPTED_FLAG) {
+            self.check_plain_allowed()?;
+        }
+
+        let mut payload = base64::decode(encoded)?;
+        for flag in flags.chars().rev() {
+            payload = if flag == ENCRYPTED_FLAG { self.open(payload)? } else { inflate(&payload)? };
+        }
+
+        return Ok(String::from_utf8(payload)?);
+    }
+
+    /// Records of frame joined with line breaks
+    fn decode_frame(&mut self, flags: u8, payload: Vec<u8>) -> Result<String, CrusterError> {
+        let mut payload = payload;
+        if flags & FRAME_ENCRYPTED != 0 {
+            payload = self.open(payload)?;
+        }
+        else {
+            self.check_plain_allowed()?;
+        }
+
+        if flags & FRAME_COMPRESSED != 0 {
+            payload = inflate(&payload)?;
+        }
+
+        return Ok(String::from_utf8(payload)?);
+    }
+
+    fn open(&mut self, payload: Vec<u8>) -> Result<Vec<u8>, CrusterError> {
+        if self.key.is_none() {
+            self.key = crypto::key_for_file(&self.path)?;
+        }
+
+        return match self.key.as_ref() {
+            Some(key) => key.open(&self.file_name, &payload),
+            None => Err(
+                CrusterError::EncryptionError(format!("'{}' contains encrypted records, but project is not encrypted", &self.path))
+            )
+        };
+    }
+}
+
+fn inflate(payload: &[u8]) -> Result<Vec<u8>, CrusterError> {
+    let mut decompressed: Vec<u8> = Vec::with_capacity(payload.len() * 4);
+    DeflateDecoder::new(payload).read_to_end(&mut decompressed)?;
+    return Ok(decompressed);
+}
+
+/// Writer which encodes every written line with codec, lines of compressed records are packed into frames
+struct EncodingWriter<'a> {
+    inner: &'a mut dyn Write,
+    codec: RecordCodec,
+    // Line being written or, if records are compressed, block of lines
+    line: Vec<u8>,
+}
+
+impl EncodingWriter<'_> {
+    fn write_line(&mut self) -> io::Result<()> {
+        let record = String::from_utf8(std::mem::take(&mut self.line))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        let encoded = self.codec
+            .encode_line(&record)
+            .map_err(|err| io::Error::other(err.to_string()))?;
+
+        return self.inner.write_all(encoded.as_bytes());
+    }
+
+    fn write_frame(&mut self) -> io::Result<()> {
+        let block = std::mem::take(&mut self.line);
+        let records = block.strip_suffix(b"\n").unwrap_or(&block);
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let frame = self.codec
+            .encode_frame(records)
+            .map_err(|err| io::Error::other(err.to_string()))?;
+
+        return self.inner.write_all(&frame);
+    }
+}
+
+impl Write for EncodingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.codec.is_plain() {
+            return self.inner.write(buf);
+        }
+
+        if self.codec.compress {
+            self.line.extend_from_slice(buf);
+            if self.line.len() >= FRAME_BLOCK_SIZE && self.line.ends_with(b"\n") {
+                self.write_frame()?;
+            }
+
+            return Ok(buf.len());
+        }
+
+        let mut rest = buf;
+        while let Some(pos) = rest.iter().position(|b| *b == b'\n') {
+            self.line.extend_from_slice(&rest[..pos]);
+            self.write_line()?;
+            self.inner.write_all(b"\n")?;
+            rest = &rest[pos + 1..];
+        }
+
+        self.line.extend_from_slice(rest);
+        return Ok(buf.len());
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.codec.compress {
+            self.write_frame()?;
+        }
+        else if !self.line.is_empty() {
+            self.write_line()?;
+        }
+
+        return self.inner.flush();
+    }
+}
+
+/// Replaces content of file with everything written by `write`, atomically for readers.
+/// Caller must hold the lock, usually since it has read the data it is going to write.
+pub(crate) fn rewrite<F>(lock: &FileLock, path: &str, write: F) -> Result<(), CrusterError>
+where
+    F: FnOnce(&mut dyn Write) -> Result<(), CrusterError>
+{
+    return rewrite_with(lock, path, RecordCodec::for_writing(path)?, write);
+}
+
+/// The same as `rewrite`, but lines are encoded with provided codec
+pub(crate) fn rewrite_with<F>(_lock: &FileLock, path: &str, codec: RecordCodec, write: F) -> Result<(), CrusterError>
+where
+    F: FnOnce(&mut dyn Write) -> Result<(), CrusterError>
+{
+    let tmp_path = format!("{}{}", path, TMP_SUFFIX);
+    let tmp_file = fs::OpenOptions::new().create(true).truncate(true).write(true).open(&tmp_path)?;
+    let mut file_writer = BufWriter::new(tmp_file);
+    let mut writer = EncodingWriter {
+        inner: &mut file_writer,
+        codec,
+        line: Vec::default(),
+    };
+
+    let result = write(&mut writer)
+        .and_then(|_| writer.flush().map_err(CrusterError::from));
+
+    drop(writer);
+    let result = result
+        .and_then(|_| file_writer.flush().map_err(CrusterError::from))
+        .and_then(|_| file_writer.get_ref().sync_all().map_err(CrusterError::from));
+
+    if let Err(err) = result {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(err);
+    }
+
+    drop(file_writer);
+    fs::rename(&tmp_path, path)?;
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_replaced(file: &File, path: &str) -> Result<bool, CrusterError> {
+    use std::os::unix::fs::MetadataExt;
+
+    let opened = file.metadata()?;
+    return match fs::metadata(path) {
+        Ok(current) => Ok(opened.ino() != current.ino() || opened.dev() != current.dev()),
+        Err(_) => Ok(true)
+    };
+}
+
+#[cfg(not(unix))]
+fn is_replaced(_file: &File, _path: &str) -> Result<bool, CrusterError> {
+    // There is no cheap way to compare files here, so the file is just reopened every time
+    return Ok(true);
+}
+
+/// Cuts off the last record or frame if it is not complete, it could be left only by crashed writer
+fn cut_torn_tail(file: &mut File) -> Result<(), CrusterError> {
+    let len = file.metadata()?.len();
+    let mut end = len;
+    while end > 0 && !is_complete_before(file, end)? {
+        end = match rfind_line_break(file, end - 1)? {
+            Some(pos) => pos + 1,
+            None => 0
+        };
+    }
+
+    if end < len {
+        warn!("Torn record of {} bytes is cut off", len - end);
+        file.set_len(end)?;
+    }
+
+    Ok(())
+}
+
+/// Whether line or frame ends right before `end`
+fn is_complete_before(file: &mut File, end: u64) -> Result<bool, CrusterError> {
+    let mut tail = [0_u8; FRAME_TRAILER_SIZE];
+    let tail_len = end.min(FRAME_TRAILER_SIZE as u64) as usize;
+    file.seek(SeekFrom::Start(end - tail_len as u64))?;
+    file.read_exact(&mut tail[..tail_len])?;
+    let tail = &tail[..tail_len];
+    if !tail.ends_with(b"\n") {
+        return Ok(false);
+    }
+
+    // Line never ends with NUL, so it is the end of frame, if length at its end matches its header
+    if tail.ends_with(FRAME_END) {
+        if tail_len < FRAME_TRAILER_SIZE {
+            return Ok(false);
+        }
+
+        let length = u32::from_le_bytes([tail[0], tail[1], tail[2], tail[3]]) as u64;
+        let start = match end.checked_sub(FRAME_TRAILER_SIZE as u64 + length + FRAME_HEADER_SIZE as u64) {
+            Some(start) => start,
+            None => return Ok(false)
+        };
+
+        let mut header = [0_u8; FRAME_HEADER_SIZE];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut header)?;
+        return Ok(&header[..4] == FRAME_MAGIC && header[5..9] == tail[..4]);
+    }
+
+    // Line break could be a byte of torn frame as well, so the line must look like a record
+    let start = match rfind_line_break(file, end - 1)? {
+        Some(pos) => pos + 1,
+        None => 0
+    };
+
+    let mut line: Vec<u8> = vec![0; (end - 1 - start) as usize];
+    file.seek(SeekFrom::Start(start))?;
+    file.read_exact(&mut line)?;
+
+    let line = match std::str::from_utf8(&line) {
+        Ok(line) if !line.contains('\0') => line.trim(),
+        _ => return Ok(false)
+    };
+
+    return Ok(line.is_empty() || line.starts_with('{') || RecordCodec::split_encoded(line).is_some());
+}
+
+/// Position of the last line break before `end`
+fn rfind_line_break(file: &mut File, end: u64) -> Result<Option<u64>, CrusterError> {
+    let mut end = end;
+    let mut block: Vec<u8> = Vec::with_capacity(TAIL_BLOCK_SIZE as usize);
+    while end > 0 {
+        let start = end.saturating_sub(TAIL_BLOCK_SIZE);
+        block.resize((end - start) as usize, 0);
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut block)?;
+
+        if let Some(pos) = block.iter().rposition(|b| *b == b'\n') {
+            return Ok(Some(start + pos as u64));
+        }
+
+        end = start;
+    }
+
+    return Ok(None);
+}
+
+/// Appends records to project file, keeping the file open between appends
+pub(crate) struct RecordAppender {
+    path: String,
+    file: File,
+    codec: RecordCodec,
+    // Size of file after the last append, other size means that another process wrote to file
+    len: u64,
+    // Whether file is checked for torn tail since it was opened, it is checked again only if another process wrote to it
+    tail_checked: bool,
+}
+
+impl RecordAppender {
+    pub(crate) fn open(path: &str) -> Result<Self, CrusterError> {
+        let file = fs::OpenOptions::new().create(true).read(true).append(true).open(path)?;
+        let appender = RecordAppender {
+            path: path.to_string(),
+            len: file.metadata()?.len(),
+            file,
+            codec: RecordCodec::for_writing(path)?,
+            tail_checked: false,
+        };
+
+        return Ok(appender);
+    }
+
+    pub(crate) fn lock(&self) -> Result<FileLock, CrusterError> {
+        return FileLock::exclusive(&self.path);
+    }
+
+    pub(crate) fn path(&self) -> &str {
+        return &self.path;
+    }
+
+    /// Whether another process appended to file or rewrote it since the last append, caller must hold the lock
+    pub(crate) fn is_changed(&self, _lock: &FileLock) -> Result<bool, CrusterError> {
+        if is_replaced(&self.file, &self.path)? {
+            return Ok(true);
+        }
+
+        return Ok(self.file.metadata()?.len() != self.len);
+    }
+
+    /// Caller must hold the lock, so it can check the file before appending
+    pub(crate) fn append_locked(&mut self, lock: &FileLock, record: &str) -> Result<(), CrusterError> {
+        return self.append_all_locked(lock, &[record]);
+    }
+
+    /// Records appended together are compressed together, if project is compressed
+    pub(crate) fn append_all_locked<S: AsRef<str>>(&mut self, _lock: &FileLock, records: &[S]) -> Result<(), CrusterError> {
+        // File could be rewritten (so replaced) by another process since the last append
+        // and it could be encrypted or decrypted by the way
+        if is_replaced(&self.file, &self.path)? {
+            self.file = fs::OpenOptions::new().create(true).read(true).append(true).open(&self.path)?;
+            self.codec = RecordCodec::for_writing(&self.path)?;
+            self.tail_checked = false;
+        }
+
+        if !self.tail_checked || self.file.metadata()?.len() != self.len {
+            cut_torn_tail(&mut self.file)?;
+            self.tail_checked = true;
+        }
+
+        let encoded = self.codec.encode_records(records)?;
+        self.file.write_all(&encoded)?;
+        self.file.flush()?;
+        self.len = self.file.metadata()?.len();
+
+        Ok(())
+    }
+
+    /// Appended records are already flushed, it waits for OS to write them to disk
+    pub(crate) fn sync(&self) -> Result<(), CrusterError> {
+        self.file.sync_all()?;
+        Ok(())
+    }
+}
+
+/// Line or frame of file
+enum Unit {
+    Line(String),
+    // Line without line break at the end of file
+    UnterminatedLine(String),
+    Frame(u8, Vec<u8>),
+    // Frame which is shorter than its header says, it is being written or was torn
+    TornFrame,
+    End,
+}
+
+/// Reads records from project file line by line (or frame by frame), skipping empty lines and torn last record
+pub(crate) struct RecordReader {
+    reader: BufReader<File>,
+    path: String,
+    codec: RecordCodec,
+    // Position of the next line or frame in file
+    position: u64,
+    // Records of frame which is read already
+    pending: VecDeque<String>,
+}
+
+impl RecordReader {
+    pub(crate) fn open(path: &str) -> Result<Self, CrusterError> {
+        return RecordReader::open_with(path, RecordCodec::new(path));
+    }
+
+    pub(crate) fn open_with(path: &str, codec: RecordCodec) -> Result<Self, CrusterError> {
+        let reader = RecordReader {
+            reader: BufReader::new(File::open(path)?),
+            path: path.to_string(),
+            codec,
+            position: 0,
+            pending: VecDeque::default(),
+        };
+
+        return Ok(reader);
+    }
+
+    /// Checks if file at path of reader is not the one that is opened, i.e. it was rewritten
+    pub(crate) fn is_replaced(&self) -> bool {
+        return is_replaced(self.reader.get_ref(), &self.path).unwrap_or(false);
+    }
+
+    /// Returns the next record if it is written completely, otherwise the same record is tried again on the next call,
+    /// so records can be read while file is appended
+    pub(crate) fn next_complete(&mut self) -> Result<Option<String>, CrusterError> {
+        loop {
+            if let Some(record) = self.pending.pop_front() {
+                return Ok(Some(record));
+            }
+
+            match self.read_unit()? {
+                Unit::Line(line) => {
+                    if !line.trim().is_empty() {
+                        return Ok(Some(self.codec.decode(&line)?));
+                    }
+                },
+                Unit::Frame(flags, payload) => self.read_frame(flags, payload)?,
+                Unit::UnterminatedLine(_) | Unit::TornFrame => {
+                    self.reader.seek(SeekFrom::Start(self.position))?;
+                    return Ok(None);
+                },
+                Unit::End => return Ok(None)
+            }
+        }
+    }
+
+    fn read_frame(&mut self, flags: u8, payload: Vec<u8>) -> Result<(), CrusterError> {
+        let records = self.codec.decode_frame(flags, payload)?;
+        self.pending.extend(
+            records
+                .split('\n')
+                .filter(|record| !record.trim().is_empty())
+                .map(|record| record.to_string())
+        );
+
+        Ok(())
+    }
+
+    /// Bytes of lines taken by base64 of encrypted (and compressed in older versions) records, beyond their payload
+    pub(crate) fn base64_overhead(mut self) -> Result<u64, CrusterError> {
+        let mut overhead: u64 = 0;
+        loop {
+            match self.read_unit()? {
+                Unit::Line(line) | Unit::UnterminatedLine(line) => overhead += RecordCodec::base64_overhead(&line) as u64,
+                Unit::Frame(_, _) => {},
+                Unit::TornFrame | Unit::End => return Ok(overhead)
+            }
+        }
+    }
+
+    /// Position is moved past the unit only if it is complete
+    fn read_unit(&mut self) -> Result<Unit, CrusterError> {
+        let first = match self.reader.fill_buf()?.first() {
+            Some(first) => *first,
+            None => return Ok(Unit::End)
+        };
+
+        if first != FRAME_MAGIC[0] {
+            let mut buf: Vec<u8> = Vec::default();
+            let len = self.reader.read_until(b'\n', &mut buf)?;
+            if !buf.ends_with(b"\n") {
+                return Ok(Unit::UnterminatedLine(String::from_utf8_lossy(&buf).to_string()));
+            }
+
+            self.position += len as u64;
+            let line = String::from_utf8(buf)?;
+            return Ok(Unit::Line(line.trim_end_matches(['\r', '\n']).to_string()));
+        }
+
+        let mut header: Vec<u8> = Vec::with_capacity(FRAME_HEADER_SIZE);
+        (&mut self.reader).take(FRAME_HEADER_SIZE as u64).read_to_end(&mut header)?;
+        if header.len() < FRAME_HEADER_SIZE {
+            return Ok(Unit::TornFrame);
+        }
+
+        if &header[..4] != FRAME_MAGIC {
+            return Err(self.corrupted());
+        }
+
+        let flags = header[4];
+        let length = u32::from_le_bytes([header[5], header[6], header[7], header[8]]);
+        let mut payload: Vec<u8> = Vec::default();
+        (&mut self.reader).take(length as u64).read_to_end(&mut payload)?;
+        let mut trailer: Vec<u8> = Vec::with_capacity(FRAME_TRAILER_SIZE);
+        (&mut self.reader).take(FRAME_TRAILER_SIZE as u64).read_to_end(&mut trailer)?;
+        if payload.len() < length as usize || trailer.len() < FRAME_TRAILER_SIZE {
+            return Ok(Unit::TornFrame);
+        }
+
+        if trailer[..4] != header[5..9] || &trailer[4..] != FRAME_END {
+            return Err(self.corrupted());
+        }
+
+        self.position += (FRAME_HEADER_SIZE + payload.len() + FRAME_TRAILER_SIZE) as u64;
+        return Ok(Unit::Frame(flags, payload));
+    }
+
+    fn corrupted(&self) -> CrusterError {
+        return CrusterError::UndefinedError(format!("'{}' is corrupted at byte {}", &self.path, self.position));
+    }
+}
+
+impl Iterator for RecordReader {
+    type Item = Result<String, CrusterError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.pending.pop_front() {
+                return Some(Ok(record));
+            }
+
+            let unit = match self.read_unit() {
+                Ok(unit) => unit,
+                Err(err) => return Some(Err(err))
+            };
+
+            let (line, terminated) = match unit {
+                Unit::Line(line) => (line, true),
+                Unit::UnterminatedLine(line) => (line, false),
+                Unit::Frame(flags, payload) => {
+                    if let Err(err) = self.read_frame(flags, payload) {
+                        return Some(Err(err));
+                    }
+
+                    continue;
+                },
+                Unit::TornFrame => {
+                    warn!("Torn record at the end of '{}' is skipped", &self.path);
+                    return None;
+                },
+                Unit::End => return None
+            };
+
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.trim().is_empty() {
+                if !terminated {
+                    return None;
+                }
+
+                continue;
+            }
+
+            let record = match self.codec.decode(line) {
+                Ok(record) => record,
+                Err(_) if !terminated => {
+                    warn!("Torn record at the end of '{}' is skipped", &self.path);
+                    return None;
+                },
+                Err(err) => return Some(Err(err))
+            };
+
+            // Unterminated last line is a record being written or torn one, unless it is complete
+            if !terminated && json::from_str::<json::Value>(&record).is_err() {
+                warn!("Torn record at the end of '{}' is skipped", &self.path);
+                return None;
+            }
+
+            return Some(Ok(record));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+
+    use super::{rewrite_with, FileLock, RecordAppender, RecordCodec, RecordReader};
+
+    fn record(id: usize) -> String {
+        return format!(r#"{{"index":{},"path":"/{}"}}"#, id, "a".repeat(id));
+    }
+
+    fn read_all(path: &str) -> Vec<String> {
+        return RecordReader::open(path).unwrap().map(|record| record.unwrap()).collect();
+    }
+
+    #[test]
+    fn compressed_records_are_framed_and_torn_frame_is_cut() {
+        let dir = std::env::temp_dir().join(format!("cruster-frames-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("http.jsonl").to_string_lossy().to_string();
+
+        // Plain line written before project was compressed is read together with frames
+        fs::write(&path, format!("{}\n", record(0))).unwrap();
+        let lock = FileLock::exclusive(&path).unwrap();
+        let codec = RecordCodec::with_options(&path, true, None);
+        let frame = codec.encode_records(&[record(1), record(2)]).unwrap();
+        fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(&frame).unwrap();
+        assert_eq!(read_all(&path), vec![record(0), record(1), record(2)]);
+
+        rewrite_with(&lock, &path, RecordCodec::with_options(&path, true, None), |fout| {
+            for id in 0..3 {
+                fout.write_all(record(id).as_bytes())?;
+                fout.write_all(b"\n")?;
+            }
+
+            Ok(())
+        }).unwrap();
+
+        // All records are packed into one frame without base64
+        let content = fs::read(&path).unwrap();
+        assert!(content.starts_with(super::FRAME_MAGIC));
+        let length = u32::from_le_bytes([content[5], content[6], content[7], content[8]]) as usize;
+        assert_eq!(content.len(), super::FRAME_HEADER_SIZE + length + super::FRAME_TRAILER_SIZE);
+        assert_eq!(read_all(&path), vec![record(0), record(1), record(2)]);
+
+        // Frame being written is not read till it is complete
+        let frame = codec.encode_records(&[record(3)]).unwrap();
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&frame[..frame.len() - 3]).unwrap();
+        assert_eq!(read_all(&path), vec![record(0), record(1), record(2)]);
+
+        let mut reader = RecordReader::open(&path).unwrap();
+        for id in 0..3 {
+            assert_eq!(reader.next_complete().unwrap(), Some(record(id)));
+        }
+
+        assert_eq!(reader.next_complete().unwrap(), None);
+        file.write_all(&frame[frame.len() - 3..]).unwrap();
+        assert_eq!(reader.next_complete().unwrap(), Some(record(3)));
+
+        // Torn frame left by crashed writer is cut off before appending
+        file.write_all(&frame[..frame.len() - 1]).unwrap();
+        let mut appender = RecordAppender::open(&path).unwrap();
+        appender.codec = RecordCodec::with_options(&path, true, None);
+        appender.append_locked(&lock, &record(4)).unwrap();
+        assert_eq!(read_all(&path), vec![record(0), record(1), record(2), record(3), record(4)]);
+
+        drop(lock);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,39 @@
+use std::path;
+use serde_json as json;
+
+use super::crypto;
+use super::files::{self, FileLock, RecordReader};
+use crate::config::Scope;
+use crate::utils::CrusterError;
+
+pub(crate) const SCOPE_FILE_NAME: &str = "scope.jsonl";
+
+/// Scope saved in project from TUI, `None` if it was never edited there
+pub(crate) fn load(project: &str) -> Result<Option<Scope>, CrusterError> {
+    let scope_path = format!("{}/{}", project, SCOPE_FILE_NAME);
+    if !path::Path::new(&scope_path).is_file() {
+        return Ok(None);
+    }
+
+    // Scope is read before any other project data, so passphrase is asked here if needed
+    crypto::unlock(project)?;
+
+    let mut scope: Option<Scope> = None;
+    for record in RecordReader::open(&scope_path)? {
+        scope = Some(json::from_str(&record?)?);
+    }
+
+    return Ok(scope);
+}
+
+pub(crate) fn store(project: &str, scope: &Scope) -> Result<(), CrusterError> {
+    let scope_path = format!("{}/{}", project, SCOPE_FILE_NAME);
+    let lock = FileLock::exclusive(&scope_path)?;
+    files::rewrite(&lock, &scope_path, |fout| {
+        let jsn = json::to_string(scope)?;
+        fout.write_all(jsn.as_bytes())?;
+        fout.write_all(b"\n")?;
+
+        Ok(())
+    })
+}
@@ -0,0 +1,101 @@
+use std::path;
+use serde_json as json;
+use serde::{Serialize, Deserialize};
+
+use super::files::{self, FileLock, RecordReader};
+use crate::utils::CrusterError;
+
+pub(crate) const VIEWS_FILE_NAME: &str = "views.jsonl";
+
+/// Named filter query stored in project
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub(crate) struct SavedView {
+    pub(crate) name: String,
+    pub(crate) query: String,
+    // View applied to table when project was stored last time
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) active: bool,
+}
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ProjectViews {
+    views: Vec<SavedView>,
+}
+
+impl ProjectViews {
+    /// Project without saved views has no file, so there is nothing to load
+    pub(crate) fn load(project: &str) -> Result<Self, CrusterError> {
+        let views_path = format!("{}/{}", project, VIEWS_FILE_NAME);
+        if !path::Path::new(&views_path).is_file() {
+            return Ok(ProjectViews::default());
+        }
+
+        let mut views: Vec<SavedView> = Vec::default();
+        for record in RecordReader::open(&views_path)? {
+            let view: SavedView = json::from_str(&record?)?;
+            views.push(view);
+        }
+
+        return Ok(ProjectViews { views });
+    }
+
+    /// Views are changed by TUI and CLI, so lock must be held from loading till storing, otherwise changes are lost
+    pub(crate) fn lock(project: &str) -> Result<FileLock, CrusterError> {
+        return FileLock::exclusive(&format!("{}/{}", project, VIEWS_FILE_NAME));
+    }
+
+    pub(crate) fn store(&self, lock: &FileLock, project: &str) -> Result<(), CrusterError> {
+        let views_path = format!("{}/{}", project, VIEWS_FILE_NAME);
+        files::rewrite(lock, &views_path, |fout| {
+            for view in self.views.iter() {
+                let jsn = json::to_string(view)?;
+                fout.write_all(jsn.as_bytes())?;
+                fout.write_all(b"\n")?;
+            }
+
+            Ok(())
+        })
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &SavedView> {
+        return self.views.iter();
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&SavedView> {
+        return self.views.iter().find(|view| view.name == name);
+    }
+
+    pub(crate) fn active(&self) -> Option<&SavedView> {
+        return self.views.iter().find(|view| view.active);
+    }
+
+    /// Makes view with the name active, `None` or unknown name resets active view
+    pub(crate) fn set_active(&mut self, name: Option<&str>) {
+        for view in self.views.iter_mut() {
+            view.active = name.is_some_and(|name| name == view.name);
+        }
+    }
+
+    /// Adds view or replaces query of the existing one with the same name
+    pub(crate) fn save(&mut self, name: &str, query: &str) {
+        match self.views.iter_mut().find(|view| view.name == name) {
+            Some(view) => view.query = query.to_string(),
+            None => {
+                self.views.push(
+                    SavedView {
+                        name: name.to_string(),
+                        query: query.to_string(),
+                        active: false
+                    }
+                );
+            }
+        }
+    }
+
+    /// Returns `false` if there is no view with the name
+    pub(crate) fn remove(&mut self, name: &str) -> bool {
+        let len = self.views.len();
+        self.views.retain(|view| view.name != name);
+        return self.views.len() != len;
+    }
+}
@@ -0,0 +1,767 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bstr::ByteSlice;
+use regex::Regex;
+
+use crate::http_storage::RequestResponsePair;
+use crate::utils::CrusterError;
+
+// Query language to filter HTTP pairs, shared by table filter, CLI and dump mode. Example:
+//     method = POST and (res.status >= 400 or res.body ~ "(?i)token") and not req.header.cookie
+// See docs/Query.md for the full description
+
+#[derive(Debug, Clone)]
+pub(crate) struct QueryError {
+    // Position of the problem in query, in characters
+    position: usize,
+    message: String,
+}
+
+impl QueryError {
+    fn new(position: usize, message: impl Into<String>) -> Self {
+        QueryError { position, message: message.into() }
+    }
+
+    /// Error with query itself and a mark under the problem, to print it in terminal
+    pub(crate) fn explain(&self, query: &str) -> String {
+        return format!("{}\n    {}\n    {}^", self, query, " ".repeat(self.position));
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Query error at position {}: {}", self.position + 1, &self.message)
+    }
+}
+
+impl From<QueryError> for CrusterError {
+    fn from(e: QueryError) -> Self {
+        Self::UnacceptableFilter(e.to_string())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------ //
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    LeftParen,
+    RightParen,
+    And,
+    Or,
+    Not,
+    Op(CompareOp),
+    // Quoted string
+    Str(String),
+    // Anything else: fields, numbers, unquoted values, keywords
+    Word(String),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    position: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOp {
+    Eq,
+    NotEq,
+    Match,
+    NotMatch,
+    Greater,
+    GreaterOrEq,
+    Less,
+    LessOrEq,
+}
+
+impl fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            CompareOp::Eq => "=",
+            CompareOp::NotEq => "!=",
+            CompareOp::Match => "~",
+            CompareOp::NotMatch => "!~",
+            CompareOp::Greater => ">",
+            CompareOp::GreaterOrEq => ">=",
+            CompareOp::Less => "<",
+            CompareOp::LessOrEq => "<=",
+        };
+
+        write!(f, "{}", op)
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    return !c.is_whitespace() && !matches!(c, '(' | ')' | '"' | '\'' | '=' | '!' | '<' | '>' | '~' | '&' | '|');
+}
+
+/// Single word without comparison operators, it could be a regex written before query language
+fn is_bare_word(text: &str) -> bool {
+    return !text.is_empty() && !text.contains(|c: char| c.is_whitespace() || matches!(c, '=' | '<' | '>' | '~'));
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, QueryError> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut i = 0_usize;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).cloned();
+        let position = i;
+
+        let (kind, length) = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            },
+            ('(', _) => (TokenKind::LeftParen, 1),
+            (')', _) => (TokenKind::RightParen, 1),
+            ('&', Some('&')) => (TokenKind::And, 2),
+            ('|', Some('|')) => (TokenKind::Or, 2),
+            ('!', Some('=')) => (TokenKind::Op(CompareOp::NotEq), 2),
+            ('!', Some('~')) => (TokenKind::Op(CompareOp::NotMatch), 2),
+            ('!', _) => (TokenKind::Not, 1),
+            ('=', Some('=')) => (TokenKind::Op(CompareOp::Eq), 2),
+            ('=', _) => (TokenKind::Op(CompareOp::Eq), 1),
+            ('~', _) => (TokenKind::Op(CompareOp::Match), 1),
+            ('>', Some('=')) => (TokenKind::Op(CompareOp::GreaterOrEq), 2),
+            ('>', _) => (TokenKind::Op(CompareOp::Greater), 1),
+            ('<', Some('=')) => (TokenKind::Op(CompareOp::LessOrEq), 2),
+            ('<', _) => (TokenKind::Op(CompareOp::Less), 1),
+            ('"' | '\'', _) => {
+                // Only quote itself and backslash are escaped, so regexes can be written as is
+                let mut value = String::new();
+                let mut j = i + 1;
+                let mut closed = false;
+                while j < chars.len() {
+                    match (chars[j], chars.get(j + 1)) {
+                        ('\\', Some(escaped)) if *escaped == c || *escaped == '\\' => {
+                            value.push(*escaped);
+                            j += 2;
+                        },
+                        (quote, _) if quote == c => {
+                            closed = true;
+                            break;
+                        },
+                        (other, _) => {
+                            value.push(other);
+                            j += 1;
+                        }
+                    }
+                }
+
+                if !closed {
+                    return Err(QueryError::new(position, "Quoted string is not closed"));
+                }
+
+                (TokenKind::Str(value), j + 1 - i)
+            },
+            ('&' | '|', _) => {
+                return Err(QueryError::new(position, format!("Unexpected '{}', did you mean '{}{}'?", c, c, c)));
+            },
+            _ => {
+                let mut j = i;
+                while j < chars.len() && is_word_char(chars[j]) {
+                    j += 1;
+                }
+
+                let word: String = chars[i..j].iter().collect();
+                let kind = match word.to_lowercase().as_str() {
+                    "and" => TokenKind::And,
+                    "or" => TokenKind::Or,
+                    "not" => TokenKind::Not,
+                    _ => TokenKind::Word(word)
+                };
+
+                (kind, j - i)
+            }
+        };
+
+        tokens.push(Token { kind, position });
+        i += length;
+    }
+
+    return Ok(tokens);
+}
+
+// ------------------------------------------------------------------------------------------------ //
+
+#[derive(Debug, Clone, PartialEq)]
+enum Field {
+    Method,
+    Host,
+    Path,
+    Query,
+    Url,
+    Status,
+    Length,
+    Id,
+    Seen,
+    RequestHeader(String),
+    ResponseHeader(String),
+    RequestBody,
+    ResponseBody,
+    // Whole request or response as text
+    Request,
+    Response,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        let lowercase = name.to_lowercase();
+        if let Some(header) = lowercase.strip_prefix("req.header.") {
+            return Some(Field::RequestHeader(header.to_string()));
+        }
+
+        if let Some(header) = lowercase.strip_prefix("res.header.") {
+            return Some(Field::ResponseHeader(header.to_string()));
+        }
+
+        let field = match lowercase.as_str() {
+            "method" | "req.method" => Field::Method,
+            "host" | "req.host" => Field::Host,
+            "path" | "req.path" => Field::Path,
+            "query" | "req.query" => Field::Query,
+            "url" | "req.url" => Field::Url,
+            "status" | "res.status" => Field::Status,
+            "length" | "res.length" => Field::Length,
+            "id" => Field::Id,
+            "seen" => Field::Seen,
+            "req.body" => Field::RequestBody,
+            "res.body" => Field::ResponseBody,
+            "req" => Field::Request,
+            "res" => Field::Response,
+            _ => return None
+        };
+
+        return Some(field);
+    }
+
+    fn is_numeric(&self) -> bool {
+        return matches!(self, Field::Status | Field::Length | Field::Id | Field::Seen);
+    }
+
+    /// Values of field in pair, header can have several, missing field has none
+    fn values(&self, pair: &RequestResponsePair) -> Vec<String> {
+        let request = pair.request.as_ref();
+        let response = pair.response.as_ref();
+
+        let value = match self {
+            Field::Method => request.map(|req| req.method.clone()),
+            Field::Host => request.map(|req| req.get_hostname()),
+            Field::Path => request.map(|req| req.get_request_path_without_query().unwrap_or_else(|_| req.get_request_path())),
+            Field::Query => request.and_then(|req| req.get_query()),
+            Field::Url => request.map(|req| req.uri.clone()),
+            Field::Status => response.map(|res| res.status.split(' ').next().unwrap_or_default().to_string()),
+            Field::Length => response.map(|res| res.get_length().to_string()),
+            Field::Id => Some(pair.index.to_string()),
+            Field::Seen => Some(pair.seen.to_string()),
+            Field::RequestBody => request.map(|req| req.body.to_str_lossy().to_string()),
+            Field::ResponseBody => response.map(|res| res.body.to_str_lossy().to_string()),
+            Field::Request => request.map(|req| req.to_string()),
+            Field::Response => response.map(|res| res.to_string()),
+            Field::RequestHeader(name) => {
+                return request
+                    .map(|req| req.headers.get_all(name.as_str()).iter().map(|v| v.as_bytes().to_str_lossy().to_string()).collect())
+                    .unwrap_or_default();
+            },
+            Field::ResponseHeader(name) => {
+                return response
+                    .map(|res| res.headers.get_all(name.as_str()).iter().map(|v| v.as_bytes().to_str_lossy().to_string()).collect())
+                    .unwrap_or_default();
+            }
+        };
+
+        return value.into_iter().collect();
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Number(f64),
+    Text(String),
+    Pattern(Regex),
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Field, CompareOp, Value),
+    // Field is present, i.e. 'req.header.cookie'
+    Exists(Field),
+    // Request URI fits scope
+    InScope,
+    Tag(String),
+    // Regex over the whole pair, like the old filter
+    FullText(Regex),
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    current: usize,
+    // Length of query in characters, position of errors at the end
+    end: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        return self.tokens.get(self.current);
+    }
+
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.current);
+        self.current += 1;
+        return token;
+    }
+
+    fn position(&self) -> usize {
+        return self.peek().map(|token| token.position).unwrap_or(self.end);
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, QueryError> {
+        let mut left = self.parse_and()?;
+        while matches!(self.peek().map(|t| &t.kind), Some(TokenKind::Or)) {
+            self.next();
+            let right = self.parse_and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+
+        return Ok(left);
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, QueryError> {
+        let mut left = self.parse_unary()?;
+        while matches!(self.peek().map(|t| &t.kind), Some(TokenKind::And)) {
+            self.next();
+            let right = self.parse_unary()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+
+        return Ok(left);
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, QueryError> {
+        if matches!(self.peek().map(|t| &t.kind), Some(TokenKind::Not)) {
+            self.next();
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+
+        return self.parse_primary();
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, QueryError> {
+        let position = self.position();
+        let token = match self.next() {
+            Some(token) => token,
+            None => return Err(QueryError::new(position, "Unexpected end of query, expected condition"))
+        };
+
+        match &token.kind {
+            TokenKind::LeftParen => {
+                let expr = self.parse_or()?;
+                let position = self.position();
+                return match self.next().map(|t| &t.kind) {
+                    Some(TokenKind::RightParen) => Ok(expr),
+                    _ => Err(QueryError::new(position, "Expected ')'"))
+                };
+            },
+            TokenKind::Str(text) => {
+                let re = Regex::new(text)
+                    .map_err(|e| QueryError::new(position, format!("Invalid regex: {}", e)))?;
+                return Ok(Expr::FullText(re));
+            },
+            TokenKind::Word(word) => {
+                if let Some(tag) = word.strip_prefix("tag:") {
+                    if tag.is_empty() {
+                        return Err(QueryError::new(position, "Tag name is expected after 'tag:'"));
+                    }
+
+                    return Ok(Expr::Tag(tag.to_string()));
+                }
+
+                return self.parse_condition(word, position);
+            },
+            other => {
+                return Err(QueryError::new(position, format!("Unexpected {}, expected condition", describe(other))));
+            }
+        }
+    }
+
+    fn parse_condition(&mut self, word: &str, position: usize) -> Result<Expr, QueryError> {
+        let field = match Field::parse(word) {
+            Some(field) => field,
+            None => {
+                // Single word is searched in the whole pair, so simple filters still work
+                if self.is_condition_end() {
+                    let re = Regex::new(word)
+                        .map_err(|e| QueryError::new(position, format!("Invalid regex: {}", e)))?;
+                    return Ok(Expr::FullText(re));
+                }
+
+                return Err(
+                    QueryError::new(
+                        position,
+                        format!("Unknown field '{}', possible are: method, host, path, query, url, status, length, id, seen, req.header.<name>, res.header.<name>, req.body, res.body, req, res", word)
+                    )
+                );
+            }
+        };
+
+        let op_position = self.position();
+        let op = match self.peek().map(|t| &t.kind) {
+            Some(TokenKind::Op(op)) => {
+                self.next();
+                *op
+            },
+            Some(TokenKind::Word(keyword)) if keyword.eq_ignore_ascii_case("in") => {
+                self.next();
+                let scope_position = self.position();
+                return match self.next().map(|t| &t.kind) {
+                    Some(TokenKind::Word(scope)) if scope.eq_ignore_ascii_case("scope") => {
+                        if !matches!(field, Field::Host | Field::Url) {
+                            return Err(QueryError::new(position, "Only 'host' and 'url' can be checked with 'in scope'"));
+                        }
+
+                        Ok(Expr::InScope)
+                    },
+                    _ => Err(QueryError::new(scope_position, "Expected 'scope' after 'in'"))
+                };
+            },
+            _ if self.is_condition_end() => return Ok(Expr::Exists(field)),
+            Some(other) => {
+                return Err(QueryError::new(op_position, format!("Expected operator after field, found {}", describe(other))));
+            },
+            None => unreachable!()
+        };
+
+        let value_position = self.position();
+        let raw_value = match self.next().map(|t| &t.kind) {
+            Some(TokenKind::Str(text)) | Some(TokenKind::Word(text)) => text.clone(),
+            _ => return Err(QueryError::new(value_position, format!("Expected value after '{}'", op)))
+        };
+
+        let value = match op {
+            CompareOp::Match | CompareOp::NotMatch => {
+                let re = Regex::new(&raw_value)
+                    .map_err(|e| QueryError::new(value_position, format!("Invalid regex: {}", e)))?;
+                Value::Pattern(re)
+            },
+            CompareOp::Greater | CompareOp::GreaterOrEq | CompareOp::Less | CompareOp::LessOrEq => {
+                if !field.is_numeric() {
+                    return Err(
+                        QueryError::new(op_position, format!("Field '{}' is not a number, it cannot be compared with '{}'", word, op))
+                    );
+                }
+
+                let number = f64::from_str(&raw_value)
+                    .map_err(|_| QueryError::new(value_position, format!("Expected number, found '{}'", &raw_value)))?;
+                Value::Number(number)
+            },
+            CompareOp::Eq | CompareOp::NotEq => {
+                match (field.is_numeric(), f64::from_str(&raw_value)) {
+                    (true, Ok(number)) => Value::Number(number),
+                    (true, Err(_)) => {
+                        return Err(QueryError::new(value_position, format!("Expected number, found '{}'", &raw_value)));
+                    },
+                    _ => Value::Text(raw_value)
+                }
+            }
+        };
+
+        return Ok(Expr::Compare(field, op, value));
+    }
+
+    fn is_condition_end(&self) -> bool {
+        return matches!(self.peek().map(|t| &t.kind), None | Some(TokenKind::And | TokenKind::Or | TokenKind::RightParen));
+    }
+}
+
+fn describe(kind: &TokenKind) -> String {
+    return match kind {
+        TokenKind::LeftParen => "'('".to_string(),
+        TokenKind::RightParen => "')'".to_string(),
+        TokenKind::And => "'and'".to_string(),
+        TokenKind::Or => "'or'".to_string(),
+        TokenKind::Not => "'not'".to_string(),
+        TokenKind::Op(op) => format!("'{}'", op),
+        TokenKind::Str(text) => format!("\"{}\"", text),
+        TokenKind::Word(word) => format!("'{}'", word),
+    };
+}
+
+// ------------------------------------------------------------------------------------------------ //
+
+#[derive(Debug, Clone)]
+pub(crate) struct Query {
+    expr: Expr,
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+    fn from_str(query: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(query)?;
+        let end = query.chars().count();
+        if tokens.is_empty() {
+            return Err(QueryError::new(0, "Query is empty"));
+        }
+
+        let mut parser = Parser { tokens: &tokens, current: 0, end };
+        let expr = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            return Err(
+                QueryError::new(token.position, format!("Unexpected {}, expected 'and', 'or' or end of query", describe(&token.kind)))
+            );
+        }
+
+        return Ok(Query { expr });
+    }
+}
+
+fn compare_numbers(actual: &str, op: CompareOp, expected: f64) -> bool {
+    let actual = match f64::from_str(actual) {
+        Ok(number) => number,
+        Err(_) => return false
+    };
+
+    return match op {
+        CompareOp::Eq => actual == expected,
+        CompareOp::NotEq => actual != expected,
+        CompareOp::Greater => actual > expected,
+        CompareOp::GreaterOrEq => actual >= expected,
+        CompareOp::Less => actual < expected,
+        CompareOp::LessOrEq => actual <= expected,
+        _ => false
+    };
+}
+
+fn compare(actual: &str, op: CompareOp, expected: &Value) -> bool {
+    return match (op, expected) {
+        (_, Value::Number(number)) => compare_numbers(actual, op, *number),
+        (CompareOp::Eq, Value::Text(text)) => actual == text,
+        (CompareOp::NotEq, Value::Text(text)) => actual != text,
+        (CompareOp::Match, Value::Pattern(re)) => re.is_match(actual),
+        (CompareOp::NotMatch, Value::Pattern(re)) => !re.is_match(actual),
+        _ => false
+    };
+}
+
+fn evaluate(expr: &Expr, pair: &RequestResponsePair, in_scope: &dyn Fn(&str) -> bool) -> bool {
+    return match expr {
+        Expr::And(left, right) => evaluate(left, pair, in_scope) && evaluate(right, pair, in_scope),
+        Expr::Or(left, right) => evaluate(left, pair, in_scope) || evaluate(right, pair, in_scope),
+        Expr::Not(inner) => !evaluate(inner, pair, in_scope),
+        Expr::Exists(field) => !field.values(pair).is_empty(),
+        Expr::InScope => pair.request.as_ref().is_some_and(|req| in_scope(&req.uri)),
+        Expr::Tag(tag) => pair.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)),
+        Expr::FullText(re) => pair.is_match(re),
+        Expr::Compare(field, op, value) => {
+            let values = field.values(pair);
+            // Negative operators mean that none of values matches, i.e. for headers met several times
+            match op {
+                CompareOp::NotEq | CompareOp::NotMatch => !values.is_empty() && values.iter().all(|actual| compare(actual, *op, value)),
+                _ => values.iter().any(|actual| compare(actual, *op, value))
+            }
+        }
+    };
+}
+
+fn collect_patterns(expr: &Expr, negated: bool, patterns: &mut Vec<String>) {
+    match expr {
+        Expr::And(left, right) | Expr::Or(left, right) => {
+            collect_patterns(left, negated, patterns);
+            collect_patterns(right, negated, patterns);
+        },
+        Expr::Not(inner) => collect_patterns(inner, !negated, patterns),
+        Expr::FullText(re) if !negated => patterns.push(re.as_str().to_string()),
+        Expr::Compare(_, CompareOp::Match, Value::Pattern(re)) if !negated => patterns.push(re.as_str().to_string()),
+        Expr::Compare(_, CompareOp::Eq, Value::Text(text)) if !negated => patterns.push(regex::escape(text)),
+        _ => {}
+    }
+}
+
+impl Query {
+    /// Parses query, but a single word which is not a valid query (i.e. `admin|login`) or text written as `/regex/`
+    /// is used as regex to search in the whole pair, so filters written before query language still work
+    pub(crate) fn parse_or_regex(text: &str) -> Result<Self, QueryError> {
+        let trimmed = text.trim();
+        if let Some(re) = trimmed.strip_prefix('/').and_then(|rest| rest.strip_suffix('/')) {
+            let re = Regex::new(re)
+                .map_err(|e| QueryError::new(text.find('/').unwrap_or(0), format!("Invalid regex: {}", e)))?;
+            return Ok(Query { expr: Expr::FullText(re) });
+        }
+
+        return match Query::from_str(text) {
+            // Lone word like 'host' or 'path' was searched as text before query language, not checked for presence,
+            // which would be true for every pair. Headers are still checked, as their names are not searched as text
+            Ok(Query { expr: Expr::Exists(field) }) if !matches!(field, Field::RequestHeader(_) | Field::ResponseHeader(_)) => {
+                let re = Regex::new(trimmed)
+                    .map_err(|e| QueryError::new(0, format!("Invalid regex: {}", e)))?;
+                Ok(Query { expr: Expr::FullText(re) })
+            },
+            Ok(query) => Ok(query),
+            // Text with spaces or comparison operators is meant to be a query, so its error is more useful than regex search
+            Err(e) if is_bare_word(trimmed) => match Regex::new(trimmed) {
+                Ok(re) => Ok(Query { expr: Expr::FullText(re) }),
+                Err(_) => Err(e)
+            },
+            Err(e) => Err(e)
+        };
+    }
+
+    /// Regex every matched pair must contain, so the search index can be used to find candidates
+    pub(crate) fn required_regex(&self) -> Option<&Regex> {
+        fn required(expr: &Expr) -> Option<&Regex> {
+            return match expr {
+                Expr::FullText(re) => Some(re),
+                Expr::And(left, right) => required(left).or_else(|| required(right)),
+                _ => None
+            };
+        }
+
+        return required(&self.expr);
+    }
+
+    /// `in_scope` checks URI for 'in scope' conditions
+    pub(crate) fn matches(&self, pair: &RequestResponsePair, in_scope: &dyn Fn(&str) -> bool) -> bool {
+        return evaluate(&self.expr, pair, in_scope);
+    }
+
+    /// Regex to highlight what query looks for in text of pair, if it looks for any text
+    pub(crate) fn highlight_regex(&self) -> Option<Regex> {
+        let mut patterns: Vec<String> = Vec::new();
+        collect_patterns(&self.expr, false, &mut patterns);
+        if patterns.is_empty() {
+            return None;
+        }
+
+        let combined = patterns
+            .iter()
+            .map(|pattern| format!("(?:{})", pattern))
+            .collect::<Vec<String>>()
+            .join("|");
+
+        return Regex::new(&combined).ok();
+    }
+}
+
+/// Field of pair written the same way as in queries, i.e. `host` or `res.header.set-cookie`, used by dump templates
+#[derive(Debug, Clone)]
+pub(crate) struct PairField(Field);
+
+impl PairField {
+    pub(crate) fn parse(name: &str) -> Option<Self> {
+        return Field::parse(name).map(PairField);
+    }
+
+    /// Several values of header are joined with '; ', missing field is empty
+    pub(crate) fn value(&self, pair: &RequestResponsePair) -> String {
+        return self.0.values(pair).join("; ");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{tokenize, CompareOp, Expr, Field, Query, TokenKind, Value};
+
+    fn kinds(query: &str) -> Vec<TokenKind> {
+        return tokenize(query).unwrap().into_iter().map(|token| token.kind).collect();
+    }
+
+    fn word(text: &str) -> TokenKind {
+        return TokenKind::Word(text.to_string());
+    }
+
+    fn error_position(query: &str) -> usize {
+        return Query::from_str(query).unwrap_err().position;
+    }
+
+    #[test]
+    fn operators_and_keywords() {
+        assert_eq!(
+            kinds("status>=400&&!req.body!~a||x==b"),
+            vec![
+                word("status"), TokenKind::Op(CompareOp::GreaterOrEq), word("400"), TokenKind::And, TokenKind::Not,
+                word("req.body"), TokenKind::Op(CompareOp::NotMatch), word("a"), TokenKind::Or,
+                word("x"), TokenKind::Op(CompareOp::Eq), word("b")
+            ]
+        );
+        assert_eq!(kinds("a AND b Or NOT c"), vec![word("a"), TokenKind::And, word("b"), TokenKind::Or, TokenKind::Not, word("c")]);
+    }
+
+    #[test]
+    fn quoted_strings() {
+        assert_eq!(
+            kinds(r#"res.body ~ "\d{4} \"x\"""#),
+            vec![word("res.body"), TokenKind::Op(CompareOp::Match), TokenKind::Str(r#"\d{4} "x""#.to_string())]
+        );
+        assert_eq!(kinds(r"'it\'s'"), vec![TokenKind::Str("it's".to_string())]);
+        assert_eq!(tokenize("path = \"/a").unwrap_err().position, 7);
+        assert_eq!(tokenize("a & b").unwrap_err().position, 2);
+    }
+
+    #[test]
+    fn precedence() {
+        let query = Query::from_str("not a or b and c").unwrap();
+        let Expr::Or(left, right) = query.expr else { panic!("'or' must bind last") };
+        assert!(matches!(*left, Expr::Not(_)));
+        assert!(matches!(*right, Expr::And(_, _)));
+
+        let query = Query::from_str("(a or b) and c").unwrap();
+        assert!(matches!(query.expr, Expr::And(_, _)));
+    }
+
+    #[test]
+    fn conditions() {
+        let query = Query::from_str("res.status >= 400").unwrap();
+        assert!(matches!(query.expr, Expr::Compare(Field::Status, CompareOp::GreaterOrEq, Value::Number(n)) if n == 400.0));
+
+        let query = Query::from_str("method = '200'").unwrap();
+        assert!(matches!(query.expr, Expr::Compare(Field::Method, CompareOp::Eq, Value::Text(ref text)) if text == "200"));
+
+        assert!(matches!(Query::from_str("req.header.Cookie").unwrap().expr, Expr::Exists(Field::RequestHeader(ref name)) if name == "cookie"));
+        assert!(matches!(Query::from_str("url in scope").unwrap().expr, Expr::InScope));
+        assert!(matches!(Query::from_str("tag:signed").unwrap().expr, Expr::Tag(ref tag) if tag == "signed"));
+        assert!(matches!(Query::from_str("admin").unwrap().expr, Expr::FullText(_)));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error_position(""), 0);
+        assert_eq!(error_position("method = GET and res.status >= abc"), 31);
+        assert_eq!(error_position("path > 1"), 5);
+        assert_eq!(error_position("status = abc"), 9);
+        assert_eq!(error_position("path in host"), 8);
+        assert_eq!(error_position("method in scope"), 0);
+        assert_eq!(error_position("(a or b"), 7);
+        assert_eq!(error_position("a b"), 0);
+        assert_eq!(error_position("a ="), 0);
+        assert_eq!(error_position("path ="), 6);
+        assert_eq!(error_position("tag:"), 0);
+    }
+
+    #[test]
+    fn lone_field_is_searched_as_text() {
+        assert!(matches!(Query::parse_or_regex("host").unwrap().expr, Expr::FullText(ref re) if re.as_str() == "host"));
+        assert!(matches!(Query::parse_or_regex(" req ").unwrap().expr, Expr::FullText(ref re) if re.as_str() == "req"));
+        assert!(matches!(Query::parse_or_regex("res.header.server").unwrap().expr, Expr::Exists(Field::ResponseHeader(_))));
+        assert!(matches!(Query::parse_or_regex("admin|login").unwrap().expr, Expr::FullText(_)));
+        assert!(matches!(Query::parse_or_regex("host and path").unwrap().expr, Expr::And(_, _)));
+        assert!(Query::parse_or_regex("(a").is_err());
+    }
+
+    #[test]
+    fn malformed_query_is_not_searched_as_regex() {
+        assert!(Query::parse_or_regex("res.status >= 4OO").is_err());
+        assert!(Query::parse_or_regex("method == GET and").is_err());
+        assert!(Query::parse_or_regex("res.status>=4OO").is_err());
+        assert!(matches!(Query::parse_or_regex("/method == GET and/").unwrap().expr, Expr::FullText(ref re) if re.as_str() == "method == GET and"));
+        assert!(matches!(Query::parse_or_regex(" /a b/ ").unwrap().expr, Expr::FullText(ref re) if re.as_str() == "a b"));
+        assert!(Query::parse_or_regex("/(a/").is_err());
+    }
+}
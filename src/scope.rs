@@ -1,52 +1,495 @@
+pub(crate) mod import;
+
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
 use regex::Regex;
 use log::debug;
+use serde::{Serialize, Deserialize};
+use url::Url;
+
+use crate::config::Scope;
+use crate::utils::CrusterError;
+
+/// Structured scope rule, every set part must fit URL. Written as `[scheme://]host[:port][path]`, i.e.
+/// `https://*.example.com:8443/api`, where host can be exact name, `*.example.com` wildcard, `*` or CIDR range
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Default)]
+#[serde(try_from = "RawScopeRule")]
+pub(crate) struct ScopeRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) scheme: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) port: Option<u16>,
+    // Prefix of path, '*' matches any characters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) path: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FullScopeRule {
+    #[serde(default)]
+    scheme: Option<String>,
+    #[serde(default)]
+    host: Option<String>,
+    #[serde(default)]
+    port: Option<u16>,
+    #[serde(default)]
+    path: Option<String>,
+}
+
+// Rule in config is either a map of parts or a string like 'https://*.example.com/api'
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawScopeRule {
+    Short(String),
+    Full(FullScopeRule),
+}
+
+impl TryFrom<RawScopeRule> for ScopeRule {
+    type Error = String;
+    fn try_from(raw: RawScopeRule) -> Result<Self, Self::Error> {
+        return match raw {
+            RawScopeRule::Short(rule) => {
+                // Error is wrapped by YAML parser, so it goes without general message of config errors
+                ScopeRule::from_str(&rule).map_err(|e| match e {
+                    CrusterError::ConfigError(message) => message,
+                    other => other.to_string()
+                })
+            },
+            RawScopeRule::Full(rule) => {
+                Ok(
+                    ScopeRule {
+                        scheme: rule.scheme,
+                        host: rule.host,
+                        port: rule.port,
+                        path: rule.path,
+                    }
+                )
+            }
+        };
+    }
+}
+
+impl fmt::Display for ScopeRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(scheme) = self.scheme.as_ref() {
+            write!(f, "{}://", scheme)?;
+        }
+
+        write!(f, "{}", self.host.as_deref().unwrap_or("*"))?;
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+
+        if let Some(path) = self.path.as_ref() {
+            write!(f, "{}", path)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for ScopeRule {
+    type Err = CrusterError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rule = ScopeRule::default();
+        let mut rest = s.trim();
+
+        if let Some((scheme, tail)) = rest.split_once("://") {
+            if scheme != "*" {
+                rule.scheme = Some(scheme.to_lowercase());
+            }
+
+            rest = tail;
+        }
+
+        // IPv6 is written in brackets, i.e. '[2001:db8::]/32'
+        let host_end = if rest.starts_with('[') {
+            rest.find(']').map(|i| i + 1).unwrap_or(rest.len())
+        }
+        else {
+            // Wildcard after the start of host belongs to path, i.e. '*.example.com*/logout'
+            rest.char_indices()
+                .find(|(i, c)| *c == ':' || *c == '/' || (*c == '*' && *i > 0))
+                .map(|(i, _)| i)
+                .unwrap_or(rest.len())
+        };
+
+        let mut host = rest[..host_end].trim_start_matches('[').trim_end_matches(']').to_string();
+        rest = &rest[host_end..];
 
-pub(crate) fn make_re_list(str_re: &[String]) -> Vec<Regex> {
-    let result: Vec<Regex> = str_re
-        .iter()
-        .map(|s| { Regex::new(s).expect(&format!("Cannot compile regex from '{}'", s)) })
-        .collect();
+        // Slash after IP address is a prefix length of CIDR range, not a path
+        if host.parse::<IpAddr>().is_ok() {
+            if let Some(tail) = rest.strip_prefix('/') {
+                let length_end = tail.find([':', '/']).unwrap_or(tail.len());
+                if length_end > 0 && tail[..length_end].chars().all(|c| c.is_ascii_digit()) {
+                    host = format!("{}/{}", host, &tail[..length_end]);
+                    rest = &tail[length_end..];
+                }
+            }
+        }
+
+        if !host.is_empty() && host != "*" {
+            rule.host = Some(host.to_lowercase());
+        }
+
+        if let Some(tail) = rest.strip_prefix(':') {
+            let port_end = tail.find('/').unwrap_or(tail.len());
+            let port = &tail[..port_end];
+            if port != "*" {
+                rule.port = Some(
+                    port.parse::<u16>()
+                        .map_err(|_| CrusterError::ConfigError(format!("Invalid port '{}' in scope rule '{}'", port, s)))?
+                );
+            }
+
+            rest = &tail[port_end..];
+        }
+
+        if !rest.is_empty() {
+            rule.path = Some(rest.to_string());
+        }
+
+        // Rule is checked right away, so broken one is reported where it is written
+        CompiledRule::try_from(&rule)?;
+        return Ok(rule);
+    }
+}
 
-    return result;
+enum HostPattern {
+    Exact(String),
+    // Any subdomain of the domain, but not the domain itself
+    Subdomain(String),
+    Any,
+    Network(IpAddr, u8),
 }
 
-fn fit_regex_list(s: &str, res: &[Regex]) -> bool {
-    debug!("URI: {}", s);
-    debug!("Regexes: {:?}", res);
-    let mut fit: bool = false;
-    for re in res {
-        if re.is_match(s) {
-            fit = true;
-            break;
+impl HostPattern {
+    fn parse(host: &str) -> Result<Self, CrusterError> {
+        let host = host.to_lowercase();
+        if host == "*" {
+            return Ok(HostPattern::Any);
         }
+
+        if let Some(domain) = host.strip_prefix("*.") {
+            return Ok(HostPattern::Subdomain(format!(".{}", domain)));
+        }
+
+        if let Some((address, length)) = host.split_once('/') {
+            let address: IpAddr = address
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse()
+                .map_err(|_| CrusterError::ConfigError(format!("Invalid address of CIDR range '{}'", host)))?;
+            let max_length = if address.is_ipv4() { 32 } else { 128 };
+            let length: u8 = length
+                .parse()
+                .ok()
+                .filter(|length| *length <= max_length)
+                .ok_or_else(|| CrusterError::ConfigError(format!("Invalid prefix length of CIDR range '{}'", host)))?;
+
+            return Ok(HostPattern::Network(address, length));
+        }
+
+        if host.contains('*') {
+            return Err(CrusterError::ConfigError(format!("Wildcard is allowed only at the start of host, i.e. '*.example.com', got '{}'", host)));
+        }
+
+        return Ok(HostPattern::Exact(host.trim_start_matches('[').trim_end_matches(']').to_string()));
+    }
+
+    fn fits(&self, host: &str) -> bool {
+        // Host of URL keeps brackets of IPv6 address, i.e. '[::1]'
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        return match self {
+            HostPattern::Any => true,
+            HostPattern::Exact(exact) => {
+                // The same IPv6 address could be written differently, i.e. '::1' and '0:0::1'
+                match (host.parse::<IpAddr>(), exact.parse::<IpAddr>()) {
+                    (Ok(address), Ok(exact)) => address == exact,
+                    _ => host == exact
+                }
+            },
+            HostPattern::Subdomain(domain) => host.ends_with(domain.as_str()),
+            HostPattern::Network(network, length) => {
+                match (host.parse::<IpAddr>(), network) {
+                    (Ok(IpAddr::V4(address)), IpAddr::V4(network)) => {
+                        let mask = u32::MAX.checked_shl(32 - *length as u32).unwrap_or(0);
+                        u32::from(address) & mask == u32::from(*network) & mask
+                    },
+                    (Ok(IpAddr::V6(address)), IpAddr::V6(network)) => {
+                        let mask = u128::MAX.checked_shl(128 - *length as u32).unwrap_or(0);
+                        u128::from(address) & mask == u128::from(*network) & mask
+                    },
+                    _ => false
+                }
+            }
+        };
+    }
+}
+
+struct CompiledRule {
+    scheme: Option<String>,
+    host: Option<HostPattern>,
+    port: Option<u16>,
+    path: Option<Regex>,
+}
+
+impl TryFrom<&ScopeRule> for CompiledRule {
+    type Error = CrusterError;
+    fn try_from(rule: &ScopeRule) -> Result<Self, Self::Error> {
+        let host = match rule.host.as_ref() {
+            Some(host) => Some(HostPattern::parse(host)?),
+            None => None
+        };
+
+        let path = match rule.path.as_ref() {
+            Some(path) => {
+                let pattern = path
+                    .split('*')
+                    .map(regex::escape)
+                    .collect::<Vec<String>>()
+                    .join(".*");
+                Some(Regex::new(&format!("^{}", pattern))?)
+            },
+            None => None
+        };
+
+        return Ok(
+            CompiledRule {
+                scheme: rule.scheme.as_ref().map(|scheme| scheme.to_lowercase()),
+                host,
+                port: rule.port,
+                path,
+            }
+        );
+    }
+}
+
+impl CompiledRule {
+    fn fits(&self, url: &Url) -> bool {
+        let scheme_fits = self.scheme.as_ref().is_none_or(|scheme| scheme == url.scheme());
+        let host_fits = self.host.as_ref().is_none_or(|pattern| url.host_str().is_some_and(|host| pattern.fits(host)));
+        let port_fits = self.port.is_none_or(|port| url.port_or_known_default() == Some(port));
+        let path_fits = self.path.as_ref().is_none_or(|re| re.is_match(url.path()));
+
+        return scheme_fits && host_fits && port_fits && path_fits;
+    }
+}
+
+enum ScopeMatcherKind {
+    Regex(Regex),
+    Rule(CompiledRule),
+}
+
+struct ScopeEntry {
+    // How it is written in config, to explain decisions
+    source: String,
+    kind: ScopeMatcherKind,
+}
+
+impl ScopeEntry {
+    fn fits(&self, uri: &str, url: Option<&Url>) -> bool {
+        return match &self.kind {
+            ScopeMatcherKind::Regex(re) => re.is_match(uri),
+            ScopeMatcherKind::Rule(rule) => url.is_some_and(|url| rule.fits(url)),
+        };
     }
+}
 
-    debug!("Fit: {}", fit);
-    return fit;
+/// Why URI is in scope or out of it
+pub(crate) struct ScopeDecision {
+    pub(crate) in_scope: bool,
+    // None if there are no include rules, so everything is included
+    pub(crate) included_by: Option<String>,
+    pub(crate) excluded_by: Option<String>,
 }
 
-pub(super) fn fit_included(uri: &str, inc: &[Regex]) -> bool {
-    let fit = if inc.len() > 0 {
-        fit_regex_list(uri, inc)
+/// Scope from config compiled once to check URIs: URI is in scope if it fits any of include regexes or rules
+/// (or there are none of them) and fits none of exclude ones
+#[derive(Default)]
+pub(crate) struct ScopeMatcher {
+    include: Vec<ScopeEntry>,
+    exclude: Vec<ScopeEntry>,
+    // URL is parsed only if there are structured rules
+    has_rules: bool,
+}
+
+fn compile_entries(regexes: Option<&Vec<String>>, rules: Option<&Vec<ScopeRule>>) -> Result<Vec<ScopeEntry>, CrusterError> {
+    let mut entries: Vec<ScopeEntry> = Vec::default();
+    for rule in rules.into_iter().flatten() {
+        entries.push(
+            ScopeEntry {
+                source: format!("rule '{}'", rule),
+                kind: ScopeMatcherKind::Rule(CompiledRule::try_from(rule)?),
+            }
+        );
+    }
+
+    for re in regexes.into_iter().flatten() {
+        let compiled = Regex::new(re)
+            .map_err(|e| CrusterError::ConfigError(format!("Cannot compile scope regex '{}': {}", re, e)))?;
+
+        entries.push(
+            ScopeEntry {
+                source: format!("regex '{}'", re),
+                kind: ScopeMatcherKind::Regex(compiled),
+            }
+        );
+    }
+
+    return Ok(entries);
+}
+
+impl TryFrom<&Scope> for ScopeMatcher {
+    type Error = CrusterError;
+    fn try_from(scope: &Scope) -> Result<Self, Self::Error> {
+        let include = compile_entries(scope.include.as_ref(), scope.include_rules.as_ref())?;
+        let exclude = compile_entries(scope.exclude.as_ref(), scope.exclude_rules.as_ref())?;
+        let has_rules = include
+            .iter()
+            .chain(exclude.iter())
+            .any(|entry| matches!(entry.kind, ScopeMatcherKind::Rule(_)));
+
+        return Ok(ScopeMatcher { include, exclude, has_rules });
+    }
+}
+
+impl ScopeMatcher {
+    /// Scope in config is checked while config is parsed, so it compiles here
+    pub(crate) fn from_config(scope: Option<&Scope>) -> Self {
+        return match scope {
+            Some(scope) => ScopeMatcher::try_from(scope).expect("Scope is checked while config is parsed"),
+            None => ScopeMatcher::default()
+        };
+    }
+
+    pub(crate) fn decide(&self, uri: &str) -> ScopeDecision {
+        debug!("URI: {}", uri);
+        let url = if self.has_rules { Url::parse(uri).ok() } else { None };
+
+        let included_by = self.include
+            .iter()
+            .find(|entry| entry.fits(uri, url.as_ref()))
+            .map(|entry| entry.source.clone());
+        let excluded_by = self.exclude
+            .iter()
+            .find(|entry| entry.fits(uri, url.as_ref()))
+            .map(|entry| entry.source.clone());
+
+        let in_scope = (self.include.is_empty() || included_by.is_some()) && excluded_by.is_none();
+        debug!("Fit: {}", in_scope);
+
+        return ScopeDecision { in_scope, included_by, excluded_by };
+    }
+
+    pub(crate) fn fits(&self, uri: &str) -> bool {
+        let url = if self.has_rules { Url::parse(uri).ok() } else { None };
+        let included = self.include.is_empty() || self.include.iter().any(|entry| entry.fits(uri, url.as_ref()));
+
+        return included && !self.exclude.iter().any(|entry| entry.fits(uri, url.as_ref()));
     }
-    else {
-        true
-    };
 
-    return fit;
+    pub(crate) fn has_include(&self) -> bool {
+        return !self.include.is_empty();
+    }
 }
 
-/// Returns `true` if no matches found, `false` otherwise
-pub(super) fn fit_excluded(uri: &str, exc: &[Regex]) -> bool {
-    let fit = if exc.len() > 0 {
-        fit_regex_list(uri, exc)
-    } else {
-        false
-    };
-    
-    return !fit;
+/// Makes check of URI by scope from config, the one used with 'in scope' in filter queries
+pub(crate) fn make_checker(scope: Option<&Scope>) -> impl Fn(&str) -> bool {
+    let matcher = ScopeMatcher::from_config(scope);
+    return move |uri: &str| matcher.fits(uri);
 }
 
-pub(crate) fn fit(uri: &str, inc: &[Regex], exc: &[Regex]) -> bool {
-    return fit_included(uri, inc) && fit_excluded(uri, exc);
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use url::Url;
+
+    use super::{CompiledRule, HostPattern, ScopeRule};
+
+    fn host_fits(pattern: &str, host: &str) -> bool {
+        return HostPattern::parse(pattern).unwrap().fits(host);
+    }
+
+    fn rule_fits(rule: &str, url: &str) -> bool {
+        let rule = ScopeRule::from_str(rule).unwrap();
+        return CompiledRule::try_from(&rule).unwrap().fits(&Url::parse(url).unwrap());
+    }
+
+    fn rule(scheme: Option<&str>, host: Option<&str>, port: Option<u16>, path: Option<&str>) -> ScopeRule {
+        return ScopeRule {
+            scheme: scheme.map(str::to_string),
+            host: host.map(str::to_string),
+            port,
+            path: path.map(str::to_string),
+        };
+    }
+
+    #[test]
+    fn wildcard_does_not_fit_apex() {
+        assert!(host_fits("*.example.com", "a.example.com"));
+        assert!(host_fits("*.example.com", "a.b.example.com"));
+        assert!(!host_fits("*.example.com", "example.com"));
+        assert!(!host_fits("*.example.com", "badexample.com"));
+        assert!(host_fits("example.com", "example.com"));
+        assert!(!host_fits("example.com", "a.example.com"));
+        assert!(host_fits("*", "example.com"));
+        assert!(HostPattern::parse("a.*.example.com").is_err());
+    }
+
+    #[test]
+    fn cidr_ranges() {
+        assert!(host_fits("10.0.0.0/8", "10.1.2.3"));
+        assert!(!host_fits("10.0.0.0/8", "11.0.0.1"));
+        assert!(host_fits("192.168.1.7/32", "192.168.1.7"));
+        assert!(!host_fits("192.168.1.7/32", "192.168.1.8"));
+        assert!(host_fits("0.0.0.0/0", "8.8.8.8"));
+        assert!(!host_fits("10.0.0.0/8", "[::1]"));
+
+        assert!(host_fits("2001:db8::/32", "[2001:db8::1]"));
+        assert!(host_fits("2001:db8::/32", "2001:db8:ffff::1"));
+        assert!(!host_fits("2001:db8::/32", "[2001:db9::1]"));
+        assert!(!host_fits("2001:db8::/32", "10.0.0.1"));
+
+        assert!(HostPattern::parse("10.0.0.0/33").is_err());
+        assert!(HostPattern::parse("2001:db8::/129").is_err());
+        assert!(HostPattern::parse("example.com/8").is_err());
+    }
+
+    #[test]
+    fn bracketed_ipv6() {
+        assert!(host_fits("[::1]", "[::1]"));
+        assert!(host_fits("::1", "[0:0::1]"));
+        assert!(!host_fits("[::1]", "[::2]"));
+        assert!(rule_fits("http://[::1]:8080/api", "http://[::1]:8080/api/users"));
+        assert!(!rule_fits("http://[::1]:8080/api", "http://[::1]:8081/api"));
+        assert!(rule_fits("[2001:db8::]/32", "https://[2001:db8::5]/"));
+    }
+
+    #[test]
+    fn rule_parts_are_parsed() {
+        assert_eq!(ScopeRule::from_str("https://*.Example.com:8443/api").unwrap(), rule(Some("https"), Some("*.example.com"), Some(8443), Some("/api")));
+        assert_eq!(ScopeRule::from_str("example.com/api/*/edit").unwrap(), rule(None, Some("example.com"), None, Some("/api/*/edit")));
+        assert_eq!(ScopeRule::from_str("example.com:*").unwrap(), rule(None, Some("example.com"), None, None));
+        assert_eq!(ScopeRule::from_str("*://*/admin").unwrap(), rule(None, None, None, Some("/admin")));
+        assert_eq!(ScopeRule::from_str("*.example.com*/logout").unwrap(), rule(None, Some("*.example.com"), None, Some("*/logout")));
+        assert_eq!(ScopeRule::from_str("10.0.0.0/8/admin").unwrap(), rule(None, Some("10.0.0.0/8"), None, Some("/admin")));
+        assert_eq!(ScopeRule::from_str("[2001:db8::]/32:443/x").unwrap(), rule(None, Some("2001:db8::/32"), Some(443), Some("/x")));
+        assert_eq!(ScopeRule::from_str("[::1]:8080").unwrap(), rule(None, Some("::1"), Some(8080), None));
+        assert!(ScopeRule::from_str("example.com:99999").is_err());
+        assert!(ScopeRule::from_str("example.com:http").is_err());
+    }
+
+    #[test]
+    fn rule_fits_url() {
+        assert!(rule_fits("https://example.com:443/api", "https://example.com/api/v1"));
+        assert!(!rule_fits("https://example.com:443/api", "https://example.com:8443/api"));
+        assert!(!rule_fits("https://example.com/api", "http://example.com/api"));
+        assert!(!rule_fits("example.com/api", "https://example.com/apx"));
+        assert!(rule_fits("*.example.com*/logout", "https://a.example.com/user/logout"));
+    }
 }
@@ -0,0 +1,451 @@
+use std::fs;
+use std::str::FromStr;
+use regex::Regex;
+use log::debug;
+use serde_json as json;
+
+use super::ScopeRule;
+use crate::config::Scope;
+use crate::utils::CrusterError;
+
+/// Format of file with scope of program
+enum ScopeFileFormat {
+    // Domain or URL per line, '!' at the start excludes it
+    List,
+    // Project options exported from Burp Suite, only 'target.scope' is used
+    Burp,
+    // Assets exported from bug bounty platform, with identifier and in/out scope columns
+    Csv,
+}
+
+enum Imported {
+    Rule(ScopeRule),
+    Regex(String),
+}
+
+// Host wildcards which are not supported by rules, i.e. 'api.*.example.com', are matched with this
+const HOST_WILDCARD_RE: &str = "[^/:?#]*";
+
+// Asset types of platforms exports, which can be checked against URL. Other ones (mobile apps, source code, etc.) are skipped
+const WEB_ASSET_TYPES: [&str; 9] = ["url", "wildcard", "domain", "cidr", "ip_address", "ip", "api", "website", "web"];
+
+const IDENTIFIER_COLUMNS: [&str; 7] = ["identifier", "target", "asset", "url", "domain", "host", "endpoint"];
+const FLAG_COLUMNS: [&str; 5] = ["eligible_for_submission", "in_scope", "in scope", "scope", "status"];
+const TYPE_COLUMNS: [&str; 3] = ["asset_type", "type", "category"];
+
+fn error_message(err: CrusterError) -> String {
+    return match err {
+        CrusterError::ConfigError(message) => message,
+        other => other.to_string()
+    };
+}
+
+fn import_error(path: &str, line: usize, message: &str) -> CrusterError {
+    return CrusterError::ConfigError(format!("Cannot import scope from '{}', line {}: {}", path, line, message));
+}
+
+/// Converts scope entry of program into a rule if rules can express it, into an URL regex otherwise.
+/// Entry is a domain, URL or CIDR range, with wildcards in host and path, i.e. `*.example.com`,
+/// `https://api-*.example.com/v1/*`, `10.0.0.0/8`
+fn convert_entry(entry: &str) -> Result<Imported, String> {
+    let entry = entry.trim();
+    let (scheme, rest) = match entry.split_once("://") {
+        Some((scheme, rest)) => (Some(scheme), rest),
+        None => (None, entry)
+    };
+
+    let host_end = if rest.starts_with('[') {
+        rest.find(']').map(|i| i + 1).unwrap_or(rest.len())
+    }
+    else {
+        rest.find([':', '/']).unwrap_or(rest.len())
+    };
+
+    let host = &rest[..host_end];
+    let is_rule_wildcard = host == "*" || (host.starts_with("*.") && !host[2..].contains('*'));
+    if !host.contains('*') || is_rule_wildcard {
+        return ScopeRule::from_str(entry)
+            .map(Imported::Rule)
+            .map_err(error_message);
+    }
+
+    let scheme_re = match scheme {
+        None | Some("*") => "https?".to_string(),
+        Some(scheme) => regex::escape(&scheme.to_lowercase())
+    };
+
+    let host_re = host
+        .split('*')
+        .map(regex::escape)
+        .collect::<Vec<String>>()
+        .join(HOST_WILDCARD_RE);
+
+    let mut tail = &rest[host_end..];
+    let port_re = match tail.strip_prefix(':') {
+        Some(port_tail) => {
+            let port_end = port_tail.find('/').unwrap_or(port_tail.len());
+            let port = &port_tail[..port_end];
+            tail = &port_tail[port_end..];
+            if port == "*" {
+                r"(:\d+)?".to_string()
+            }
+            else if port.parse::<u16>().is_ok() {
+                format!(":{}", port)
+            }
+            else {
+                return Err(format!("Invalid port '{}' in '{}'", port, entry));
+            }
+        },
+        None => r"(:\d+)?".to_string()
+    };
+
+    let path_re = if tail.is_empty() {
+        "([/?#]|$)".to_string()
+    }
+    else {
+        tail.split('*').map(regex::escape).collect::<Vec<String>>().join(".*")
+    };
+
+    let re = format!("(?i)^{}://{}{}{}", scheme_re, host_re, port_re, path_re);
+    Regex::new(&re).map_err(|e| format!("Cannot make regex for '{}': {}", entry, e))?;
+
+    return Ok(Imported::Regex(re));
+}
+
+// Burp anchors every part separately, but here they are joined into one regex for URL
+fn unanchor(re: &str) -> &str {
+    let re = re.strip_prefix('^').unwrap_or(re);
+    return re.strip_suffix('$').filter(|re| !re.ends_with('\\')).unwrap_or(re);
+}
+
+// Host of Burp rule must not spread over port and path, i.e. '.*\.example\.com' must not fit 'evil.com/a.example.com'
+fn bound_host_wildcards(re: &str) -> String {
+    let mut bounded = String::with_capacity(re.len());
+    let mut chars = re.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            bounded.push(c);
+            if let Some(escaped) = chars.next() {
+                bounded.push(escaped);
+            }
+        }
+        else if c == '.' && matches!(chars.peek(), Some('*') | Some('+')) {
+            bounded.push_str("[^/:?#]");
+        }
+        else {
+            bounded.push(c);
+        }
+    }
+
+    return bounded;
+}
+
+/// Converts item of Burp scope into an URL regex. Simple mode item has only URL prefix,
+/// advanced mode one has protocol and regexes for host, port and file
+fn convert_burp_item(item: &json::Value) -> Result<Option<Imported>, String> {
+    if item.get("enabled").and_then(|enabled| enabled.as_bool()) == Some(false) {
+        return Ok(None);
+    }
+
+    if let Some(prefix) = item.get("prefix").and_then(|prefix| prefix.as_str()) {
+        return convert_entry(prefix).map(Some);
+    }
+
+    let part = |name: &str| -> &str {
+        return item.get(name).and_then(|value| value.as_str()).unwrap_or("");
+    };
+
+    // Schemes with their default ports
+    let schemes: &[(&str, &str)] = match part("protocol").to_lowercase().as_str() {
+        "http" => &[("http", "80")],
+        "https" => &[("https", "443")],
+        _ => &[("http", "80"), ("https", "443")]
+    };
+
+    let host_re = match unanchor(part("host")) {
+        "" => HOST_WILDCARD_RE.to_string(),
+        host => bound_host_wildcards(host)
+    };
+
+    let file_re = match part("file") {
+        "" => "([/?#]|$)".to_string(),
+        file => file.strip_prefix('^').unwrap_or(file).to_string()
+    };
+
+    let origin_re = match unanchor(part("port")) {
+        "" => {
+            let scheme_re = if schemes.len() == 1 { schemes[0].0 } else { "https?" };
+            format!(r"{}://({})(:\d+)?", scheme_re, host_re)
+        },
+        port => {
+            let whole_port = Regex::new(&format!("^(?:{})$", port))
+                .map_err(|e| format!("Cannot make regex of port of Burp scope item {}: {}", item, e))?;
+
+            // Port is not written in URL when it is default one for scheme, so it can be omitted
+            // only if the rule allows default port, otherwise i.e. rule for 8443 would fit 443
+            let variants: Vec<String> = schemes
+                .iter()
+                .map(|(scheme, default_port)| {
+                    let port_re = if whole_port.is_match(default_port) { format!("(:({}))?", port) } else { format!(":({})", port) };
+                    format!("{}://({}){}", scheme, host_re, port_re)
+                })
+                .collect();
+
+            format!("({})", variants.join("|"))
+        }
+    };
+
+    let re = format!("(?i)^{}{}", origin_re, file_re);
+    Regex::new(&re).map_err(|e| format!("Cannot make regex of Burp scope item {}: {}", item, e))?;
+
+    return Ok(Some(Imported::Regex(re)));
+}
+
+fn parse_list(path: &str, content: &str) -> Result<Vec<(bool, Imported)>, CrusterError> {
+    let mut imported: Vec<(bool, Imported)> = Vec::default();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (include, entry) = match line.strip_prefix('!') {
+            Some(entry) => (false, entry),
+            None => (true, line)
+        };
+
+        let converted = convert_entry(entry).map_err(|e| import_error(path, i + 1, &e))?;
+        imported.push((include, converted));
+    }
+
+    return Ok(imported);
+}
+
+fn parse_burp(path: &str, content: &str) -> Result<Vec<(bool, Imported)>, CrusterError> {
+    let options: json::Value = json::from_str(content)
+        .map_err(|e| CrusterError::ConfigError(format!("Cannot import scope from '{}': {}", path, e)))?;
+
+    let scope = options
+        .pointer("/target/scope")
+        .ok_or_else(|| CrusterError::ConfigError(format!("Cannot import scope from '{}': there is no 'target.scope' in Burp options", path)))?;
+
+    let mut imported: Vec<(bool, Imported)> = Vec::default();
+    for (include, key) in [(true, "include"), (false, "exclude")] {
+        let items = scope
+            .get(key)
+            .and_then(|items| items.as_array())
+            .map(|items| items.as_slice())
+            .unwrap_or_default();
+
+        for (i, item) in items.iter().enumerate() {
+            let converted = convert_burp_item(item)
+                .map_err(|e| CrusterError::ConfigError(format!("Cannot import scope from '{}', {} item {}: {}", path, key, i + 1, e)))?;
+
+            if let Some(converted) = converted {
+                imported.push((include, converted));
+            }
+        }
+    }
+
+    return Ok(imported);
+}
+
+/// Splits CSV into rows of fields, quoted fields can contain commas, newlines and doubled quotes
+fn split_csv(content: &str) -> Vec<Vec<String>> {
+    let mut rows: Vec<Vec<String>> = Vec::default();
+    let mut row: Vec<String> = Vec::default();
+    let mut field = String::default();
+    let mut quoted = false;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            },
+            ('"', _) => quoted = !quoted,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            },
+            ('\r', false) => {},
+            _ => field.push(c)
+        }
+    }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    return rows;
+}
+
+fn parse_flag(flag: &str) -> Option<bool> {
+    return match flag.trim().to_lowercase().as_str() {
+        "" | "true" | "yes" | "y" | "1" | "in" | "in scope" | "in-scope" | "in_scope" | "included" => Some(true),
+        "false" | "no" | "n" | "0" | "out" | "out of scope" | "out-of-scope" | "out_of_scope" | "excluded" => Some(false),
+        _ => None
+    };
+}
+
+fn parse_csv(path: &str, content: &str) -> Result<Vec<(bool, Imported)>, CrusterError> {
+    let rows = split_csv(content);
+    let header: Vec<String> = rows
+        .first()
+        .map(|row| row.iter().map(|name| name.trim().to_lowercase()).collect())
+        .unwrap_or_default();
+
+    let find_column = |names: &[&str]| header.iter().position(|column| names.contains(&column.as_str()));
+
+    // File without known header is read as 'identifier[,in/out flag]'
+    let (identifier, flag, asset_type, skip) = match find_column(&IDENTIFIER_COLUMNS) {
+        Some(identifier) => (identifier, find_column(&FLAG_COLUMNS), find_column(&TYPE_COLUMNS), 1),
+        None => (0, Some(1), None, 0)
+    };
+
+    let mut imported: Vec<(bool, Imported)> = Vec::default();
+    for (i, row) in rows.iter().enumerate().skip(skip) {
+        let line = i + 1;
+        let field = |column: Option<usize>| column.and_then(|column| row.get(column)).map(|value| value.trim()).unwrap_or("");
+
+        let entries = field(Some(identifier));
+        if entries.is_empty() {
+            continue;
+        }
+
+        let kind = field(asset_type).to_lowercase();
+        if !kind.is_empty() && !WEB_ASSET_TYPES.contains(&kind.as_str()) {
+            debug!("Asset '{}' of type '{}' is not imported into scope", entries, kind);
+            continue;
+        }
+
+        let include = parse_flag(field(flag))
+            .ok_or_else(|| import_error(path, line, &format!("Unknown in/out scope flag '{}'", field(flag))))?;
+
+        // Platforms sometimes list several domains in one asset
+        for entry in entries.split([',', ' ', '\n']).filter(|entry| !entry.is_empty()) {
+            let converted = convert_entry(entry).map_err(|e| import_error(path, line, &e))?;
+            imported.push((include, converted));
+        }
+    }
+
+    return Ok(imported);
+}
+
+fn detect_format(path: &str, content: &str) -> ScopeFileFormat {
+    let lowercased = path.to_lowercase();
+    if lowercased.ends_with(".json") || content.trim_start().starts_with('{') {
+        return ScopeFileFormat::Burp;
+    }
+
+    if lowercased.ends_with(".csv") {
+        return ScopeFileFormat::Csv;
+    }
+
+    return ScopeFileFormat::List;
+}
+
+fn push_unique<T: PartialEq>(list: &mut Option<Vec<T>>, item: T) -> bool {
+    let list = list.get_or_insert_with(Vec::new);
+    if list.contains(&item) {
+        return false;
+    }
+
+    list.push(item);
+    return true;
+}
+
+/// Adds entries of program scope file into include/exclude rules and regexes of scope.
+/// Entries the scope already has are skipped, returns number of added ones
+pub(crate) fn import(path: &str, scope: &mut Scope) -> Result<usize, CrusterError> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| CrusterError::ConfigError(format!("Cannot read scope file '{}': {}", path, e)))?;
+
+    let imported = match detect_format(path, &content) {
+        ScopeFileFormat::List => parse_list(path, &content)?,
+        ScopeFileFormat::Burp => parse_burp(path, &content)?,
+        ScopeFileFormat::Csv => parse_csv(path, &content)?,
+    };
+
+    let mut added: usize = 0;
+    for (include, entry) in imported {
+        let is_new = match (entry, include) {
+            (Imported::Rule(rule), true) => push_unique(&mut scope.include_rules, rule),
+            (Imported::Rule(rule), false) => push_unique(&mut scope.exclude_rules, rule),
+            (Imported::Regex(re), true) => push_unique(&mut scope.include, re),
+            (Imported::Regex(re), false) => push_unique(&mut scope.exclude, re),
+        };
+
+        if is_new {
+            added += 1;
+        }
+    }
+
+    debug!("{} scope entries are imported from '{}'", added, path);
+    return Ok(added);
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+    use serde_json as json;
+
+    use super::{convert_burp_item, convert_entry, Imported};
+
+    fn burp_regex(item: &str) -> Regex {
+        return match convert_burp_item(&json::from_str(item).unwrap()) {
+            Ok(Some(Imported::Regex(re))) => Regex::new(&re).unwrap(),
+            _ => panic!("Burp item {} is not converted into regex", item)
+        };
+    }
+
+    #[test]
+    fn burp_item_with_default_port() {
+        let re = burp_regex(r#"{"enabled": true, "protocol": "https", "host": "^.*\\.example\\.com$", "port": "^443$", "file": "^/api/.*"}"#);
+        assert!(re.is_match("https://a.example.com/api/users"));
+        assert!(re.is_match("https://a.example.com:443/api/users"));
+        assert!(!re.is_match("https://a.example.com:8443/api/users"));
+        assert!(!re.is_match("http://a.example.com/api/users"));
+        assert!(!re.is_match("https://evil.com/a.example.com/api/users"));
+    }
+
+    #[test]
+    fn burp_item_without_default_port() {
+        let re = burp_regex(r#"{"enabled": true, "protocol": "any", "host": "^example\\.com$", "port": "^8443$", "file": ""}"#);
+        assert!(re.is_match("https://example.com:8443/"));
+        assert!(re.is_match("http://example.com:8443"));
+        assert!(!re.is_match("https://example.com/"));
+        assert!(!re.is_match("https://example.com:443/"));
+
+        let re = burp_regex(r#"{"enabled": true, "protocol": "any", "host": "^example\\.com$", "port": "", "file": ""}"#);
+        assert!(re.is_match("https://example.com/"));
+        assert!(re.is_match("http://example.com:8080/a"));
+        assert!(!re.is_match("https://example.com.evil.com/"));
+    }
+
+    #[test]
+    fn burp_simple_and_disabled_items() {
+        let item = json::from_str(r#"{"enabled": true, "prefix": "https://example.com/app"}"#).unwrap();
+        assert!(matches!(convert_burp_item(&item), Ok(Some(Imported::Rule(_)))));
+
+        let item = json::from_str(r#"{"enabled": false, "host": "^example\\.com$"}"#).unwrap();
+        assert!(matches!(convert_burp_item(&item), Ok(None)));
+    }
+
+    #[test]
+    fn entries_are_rules_or_regexes() {
+        assert!(matches!(convert_entry("*.example.com"), Ok(Imported::Rule(_))));
+        assert!(matches!(convert_entry("https://example.com:8443/api"), Ok(Imported::Rule(_))));
+        let re = match convert_entry("*.test.*.com") {
+            Ok(Imported::Regex(re)) => Regex::new(&re).unwrap(),
+            _ => panic!("Entry is not converted into regex")
+        };
+
+        assert!(re.is_match("https://a.test.x.com/"));
+        assert!(!re.is_match("https://a.test.x.com.evil.org/"));
+        assert!(convert_entry("api.*.example.com:http").is_err());
+    }
+}
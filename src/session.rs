@@ -0,0 +1,272 @@
+use std::str::FromStr;
+use std::path::Path;
+use std::future::Future;
+
+use http::{HeaderValue, header::HeaderName};
+use log::debug;
+use regex::Regex;
+use serde_json as json;
+use tokio::sync::Mutex;
+
+use crate::cli::repeater::RepeaterIterator;
+use crate::config::{Config, Scope, SessionRule};
+use crate::cruster_proxy::request_response::{HyperRequestWrapper, HyperResponseWrapper};
+use crate::http_storage::RequestResponsePair;
+use crate::query::Query;
+use crate::scope::ScopeMatcher;
+use crate::utils::CrusterError;
+
+/// Session rule compiled from config, it keeps the last token got by login
+struct SessionHandler {
+    rule: SessionRule,
+    scope: ScopeMatcher,
+    token_regex: Option<Regex>,
+    expired: Query,
+    header: HeaderName,
+    // Requests wait here while login is sent, so it is sent once for all of them
+    token: Mutex<Option<String>>,
+}
+
+impl SessionHandler {
+    fn new(rule: &SessionRule, config_scope: Option<&Scope>) -> Self {
+        // Rules are checked while config is parsed
+        SessionHandler {
+            rule: rule.clone(),
+            scope: ScopeMatcher::from_config(Some(&rule.get_scope(config_scope))),
+            token_regex: rule.token_regex.as_ref().map(|re| Regex::new(re).expect("Token regex is checked while config is parsed")),
+            expired: Query::from_str(&rule.expired).unwrap_or_else(|e| panic!("Expired query is checked while config is parsed: {}", e.explain(&rule.expired))),
+            header: HeaderName::from_str(&rule.header).expect("Session header is checked while config is parsed"),
+            token: Mutex::new(None),
+        }
+    }
+}
+
+// Path is dot separated keys of objects and indexes of arrays, i.e. 'data.tokens.0.value'
+fn find_json_value<'a>(value: &'a json::Value, path: &str) -> Option<&'a json::Value> {
+    let mut current = value;
+    for key in path.split('.').filter(|key| !key.is_empty()) {
+        current = match current {
+            json::Value::Object(object) => object.get(key)?,
+            json::Value::Array(array) => array.get(key.parse::<usize>().ok()?)?,
+            _ => return None
+        };
+    }
+
+    return Some(current);
+}
+
+impl SessionHandler {
+    fn make_header_value(&self, token: &str) -> Result<HeaderValue, CrusterError> {
+        let value = self.rule.value.replace("{token}", token);
+        return Ok(HeaderValue::from_str(&value)?);
+    }
+
+    fn extract_token(&self, response: &HyperResponseWrapper) -> Result<String, CrusterError> {
+        let token = if let Some(re) = self.token_regex.as_ref() {
+            re.captures(&response.to_string())
+                .and_then(|captures| captures.get(1).or(captures.get(0)))
+                .map(|token| token.as_str().to_string())
+        }
+        else {
+            let path = self.rule.token_json.as_deref().unwrap_or_default();
+            let body: json::Value = json::from_slice(&response.body)?;
+            find_json_value(&body, path).map(|value| {
+                match value {
+                    json::Value::String(token) => token.clone(),
+                    other => other.to_string()
+                }
+            })
+        };
+
+        return token.ok_or_else(|| {
+            CrusterError::UndefinedError(
+                format!("Could not find token in response to login '{}' with status {}", self.rule.login, response.status)
+            )
+        });
+    }
+
+    /// Sends login request of repeater as is, not through session rules
+    async fn login(&self, repeaters_path: &str) -> Result<String, CrusterError> {
+        if !Path::new(repeaters_path).is_file() {
+            return Err(CrusterError::UndefinedError(format!("Could not find login repeater '{}', project has no repeaters", self.rule.login)));
+        }
+
+        let number = self.rule.login.parse::<usize>().ok();
+        let mut found = None;
+        for (i, repeater) in RepeaterIterator::new(repeaters_path)?.enumerate() {
+            let repeater = repeater?;
+            let is_login = match number {
+                Some(number) => i + 1 == number,
+                None => repeater.name == self.rule.login
+            };
+
+            if is_login {
+                found = Some(repeater);
+                break;
+            }
+        }
+
+        let repeater = found
+            .ok_or_else(|| CrusterError::UndefinedError(format!("Could not find login repeater '{}'", self.rule.login)))?;
+
+        let client = reqwest::Client::builder()
+            .use_rustls_tls()
+            .redirect(reqwest::redirect::Policy::none())
+            .http1_only()
+            .build()?;
+
+        let response = client.execute(repeater.make_reqwest()?).await?;
+        let response = HyperResponseWrapper::from_reqwest(response).await?;
+        let token = self.extract_token(&response)?;
+        debug!("Session token is got with login '{}'", self.rule.login);
+
+        return Ok(token);
+    }
+}
+
+/// Session handling rules from config. Requests fitting rule get header with token, login macro is sent
+/// to get token when there is none yet or when response says it is expired
+pub(crate) struct Sessions {
+    handlers: Vec<SessionHandler>,
+    repeaters_path: String,
+    // For 'in scope' in queries of expired responses
+    scope: ScopeMatcher,
+}
+
+impl From<&Config> for Sessions {
+    fn from(config: &Config) -> Self {
+        let handlers = config.sessions
+            .iter()
+            .flatten()
+            .map(|rule| SessionHandler::new(rule, config.scope.as_ref()))
+            .collect();
+
+        let repeaters_path = config.project
+            .as_ref()
+            .map(|project| format!("{}/repeater.jsonl", project))
+            .unwrap_or_default();
+
+        return Sessions { handlers, repeaters_path, scope: ScopeMatcher::from_config(config.scope.as_ref()) };
+    }
+}
+
+impl Sessions {
+    pub(crate) fn is_empty(&self) -> bool {
+        return self.handlers.is_empty();
+    }
+
+    /// Returns session headers with tokens of every rule fitting URI, login is sent first if there is no token yet
+    pub(crate) async fn prepare(&self, uri: &str) -> Result<Vec<(HeaderName, HeaderValue)>, CrusterError> {
+        let mut headers = Vec::default();
+        for handler in self.handlers.iter().filter(|handler| handler.scope.fits(uri)) {
+            let mut token = handler.token.lock().await;
+            if token.is_none() {
+                *token = Some(handler.login(&self.repeaters_path).await?);
+            }
+
+            let value = handler.make_header_value(token.as_deref().unwrap_or_default())?;
+            headers.push((handler.header.clone(), value));
+        }
+
+        return Ok(headers);
+    }
+
+    async fn prepare_reqwest(&self, request: &mut reqwest::Request) -> Result<(), CrusterError> {
+        let uri = request.url().to_string();
+        for (name, value) in self.prepare(&uri).await? {
+            request.headers_mut().insert(name, value);
+        }
+
+        return Ok(());
+    }
+
+    /// Sends login again for every rule which finds exchange expired. Returns whether request should be
+    /// sent once more with new token
+    pub(crate) async fn refresh_expired(&self, request: &HyperRequestWrapper, response: &HyperResponseWrapper) -> Result<bool, CrusterError> {
+        if self.is_empty() {
+            return Ok(false);
+        }
+
+        let pair = RequestResponsePair {
+            request: Some(request.clone()),
+            response: Some(response.clone()),
+            index: 0,
+            timestamp: None,
+            seen: 1,
+            tags: Vec::default()
+        };
+
+        let in_scope = |uri: &str| self.scope.fits(uri);
+        let mut refreshed = false;
+        for handler in self.handlers.iter().filter(|handler| handler.scope.fits(&request.uri)) {
+            if !handler.expired.matches(&pair, &in_scope) {
+                continue;
+            }
+
+            let mut token = handler.token.lock().await;
+            // Other request could get new token already while this one waited for response
+            let sent_value = request.headers.get(&handler.header);
+            let current_value = match token.as_deref() {
+                Some(token) => Some(handler.make_header_value(token)?),
+                None => None
+            };
+
+            if current_value.is_none() || sent_value == current_value.as_ref() {
+                *token = Some(handler.login(&self.repeaters_path).await?);
+            }
+
+            refreshed = true;
+        }
+
+        return Ok(refreshed);
+    }
+
+    /// Sends repeater request with `send` after session headers are put into it, and once more with new token if response
+    /// says session is expired. `send` returns response with anything else caller needs, i.e. count of redirects
+    pub(crate) async fn send_with_session<T, E, F, R>(&self, request: reqwest::Request, mut send: F) -> Result<(HyperResponseWrapper, T), E>
+    where
+        F: FnMut(reqwest::Request) -> R,
+        R: Future<Output = Result<(HyperResponseWrapper, T), E>>,
+        E: From<CrusterError>
+    {
+        let mut request = request;
+        self.prepare_reqwest(&mut request).await?;
+
+        // Body of request could be a stream, which cannot be sent twice, then it is sent once as is
+        let retry = if self.is_empty() { None } else { request.try_clone() };
+        let sent = match retry.as_ref().and_then(|retry| retry.try_clone()) {
+            Some(sent) => Some(HyperRequestWrapper::from_reqwest(sent).await?),
+            None => None
+        };
+
+        let result = send(request).await?;
+        if let (Some(mut retry), Some(sent)) = (retry, sent) {
+            if self.refresh_expired(&sent, &result.0).await? {
+                self.prepare_reqwest(&mut retry).await?;
+                return send(retry).await;
+            }
+        }
+
+        return Ok(result);
+    }
+}
+
+/// Request of proxy is sent once more by Cruster itself, when it is sent with new token
+pub(crate) async fn resend(request: &HyperRequestWrapper) -> Result<HyperResponseWrapper, CrusterError> {
+    let client = reqwest::Client::builder()
+        .use_rustls_tls()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+
+    let method = reqwest::Method::from_bytes(request.method.as_bytes())?;
+    let url = reqwest::Url::parse(&request.uri)
+        .map_err(|err| CrusterError::CouldParseRequestPathError(err.to_string()))?;
+
+    let response = client.request(method, url)
+        .headers(request.headers.clone())
+        .body(request.body.clone())
+        .send()
+        .await?;
+
+    return HyperResponseWrapper::from_reqwest(response).await;
+}
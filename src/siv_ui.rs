@@ -8,6 +8,11 @@ mod sivuserdata;
 mod filter_view;
 mod req_res_spanned;
 mod clipboard;
+mod export_code_view;
+mod sitemap_view;
+mod params_view;
+mod comparer_view;
+mod scope_view;
 pub(super) mod error_view;
 
 #[cfg(feature = "termion")]
@@ -29,7 +34,7 @@ use cursive::views::{Dialog, LinearLayout, TextContent, TextView, StackView, };
 use log::debug;
 use std::rc::Rc;
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use crossbeam_channel::Receiver as CBReceiver;
 // use std::thread::{self, JoinHandle, sleep};
 
@@ -38,10 +43,17 @@ use crate::config::Config;
 use sivuserdata::SivUserData;
 use crate::utils::CrusterError;
 use status_bar::StatusBarContent;
-use crate::http_storage::HTTPStorage;
+use crate::noise::NoiseFilter;
+use crate::scope::ScopeMatcher;
+use crate::project::views::ProjectViews;
+use crate::query::Query;
+use std::str::FromStr;
+use crate::http_storage::{HTTPStorage, StoredRequest};
 use crate::siv_ui::http_table::HTTPTable;
 use self::sivuserdata::GetCrusterUserData;
 use crate::cruster_proxy::events::ProxyEvents;
+use crate::session::Sessions;
+use std::sync::Arc;
 
 impl GetCrusterUserData for Cursive {
     fn get_cruster_userdata(&mut self) -> &mut SivUserData {
@@ -58,6 +70,7 @@ enum BasicColumn {
     Path,
     StatusCode,
     ResponseLength,
+    Seen,
 }
 
 // Define the item type
@@ -69,6 +82,8 @@ pub(crate) struct ProxyDataForTable {
     pub(crate) path: String,
     pub(crate) status_code: String,
     pub(crate) response_length: usize,
+    // How many times the request was seen, duplicates are counted here
+    pub(crate) seen: usize,
 }
 
 impl TableViewItem<BasicColumn> for ProxyDataForTable {
@@ -85,6 +100,12 @@ impl TableViewItem<BasicColumn> for ProxyDataForTable {
             else {
                 self.response_length.to_string()
             },
+            BasicColumn::Seen => if self.seen > 1 {
+                self.seen.to_string()
+            }
+            else {
+                "".to_string()
+            },
         }
     }
 
@@ -96,11 +117,12 @@ impl TableViewItem<BasicColumn> for ProxyDataForTable {
             BasicColumn::Path => self.path.cmp(&other.path),
             BasicColumn::StatusCode => self.status_code.cmp(&other.status_code),
             BasicColumn::ResponseLength => self.response_length.cmp(&other.response_length),
+            BasicColumn::Seen => self.seen.cmp(&other.seen),
         }
     }
 }
 
-pub(super) fn bootstrap_ui(mut siv: Cursive, config: Config, rx: CBReceiver<ProxyEvents>) {
+pub(super) fn bootstrap_ui(mut siv: Cursive, config: Config, rx: CBReceiver<ProxyEvents>, sessions: Arc<Sessions>) {
     let help_message = Rc::new(help_view::make_help_message());
 
     siv.add_global_callback('q', |s| quit_popup::draw_popup(s));
@@ -111,6 +133,10 @@ pub(super) fn bootstrap_ui(mut siv: Cursive, config: Config, rx: CBReceiver<Prox
     siv.add_global_callback('F', |s| { filter_view::draw_filter(s) });
     siv.add_global_callback('r', |s| { repeater::draw_repeater_select(s) });
     siv.add_global_callback('R', |s| { repeater::create_and_draw_repeater(s) });
+    siv.add_global_callback('M', |s| { sitemap_view::draw_sitemap(s) });
+    siv.add_global_callback('P', |s| { params_view::draw_params_inventory(s) });
+    siv.add_global_callback('C', |s| { comparer_view::add_selected_pair(s) });
+    siv.add_global_callback('I', |s| { scope_view::draw_scope_editor(s) });
 
     // siv.set_autorefresh(true);
     siv.set_theme(cursive::theme::Theme {
@@ -141,22 +167,31 @@ pub(super) fn bootstrap_ui(mut siv: Cursive, config: Config, rx: CBReceiver<Prox
         StyledString::styled("Press '?' to get help", BaseColor::White.dark())
     );
 
+    let mut http_storage = HTTPStorage::default();
+    if let Some(noise) = config.noise.as_ref() {
+        http_storage.set_noise_filter(NoiseFilter::from(noise));
+    }
+
     siv.set_user_data(
         SivUserData {
             config,
             proxy_receiver: rx,
-            http_storage: HTTPStorage::default(),
+            http_storage,
             request_view_content: request_view_content.clone(),
             response_view_content: response_view_content.clone(),
             filter_content: "".to_string(),
+            filter_query: None,
+            views: ProjectViews::default(),
             active_http_table_name: "proxy-table",
             errors: Vec::new(),
             status: StatusBarContent::new(status_bar_message.clone(), status_bar_stats.clone()),
             data_storing_started: false,
-            include: None,
-            exclude: None,
+            scope: ScopeMatcher::default(),
             table_id_ref: HashMap::default(),
             repeater_state: vec![],
+            sitemap_expanded: HashSet::default(),
+            comparer: comparer_view::ComparerState::default(),
+            sessions,
         }
     );
 
@@ -269,32 +304,63 @@ pub(super) fn put_proxy_data_to_storage(siv: &mut Cursive) {
             ProxyEvents::RequestSent((req, hash)) => {
                 let fit_scope = rx.is_uri_in_socpe(&req.uri);
                 if !rx.is_scope_strict() || fit_scope {
-                    let table_record = rx.http_storage.put_request(req, hash);
-
-                    if fit_scope && rx.is_http_pair_match_filter(table_record.id) {
-                        let id = table_record.id;
-                        table.insert_item(table_record);
-                        let last_index = table.borrow_items().len() - 1;
-                        rx.table_id_ref.insert(id, last_index);
+                    match rx.http_storage.put_request(req, hash) {
+                        StoredRequest::New(table_record) => {
+                            let is_noise = rx.http_storage
+                                .get_by_id(table_record.id)
+                                .is_some_and(|pair| rx.http_storage.is_noise(pair));
+
+                            if fit_scope && !is_noise && rx.is_http_pair_match_filter(table_record.id) {
+                                let id = table_record.id;
+                                table.insert_item(table_record);
+                                let last_index = table.borrow_items().len() - 1;
+                                rx.table_id_ref.insert(id, last_index);
+                            }
+                        },
+                        StoredRequest::Duplicate(kept_id) => {
+                            let seen = rx.http_storage.get_by_id(kept_id).map(|pair| pair.seen);
+                            if let (Some(seen), Some(table_index)) = (seen, rx.table_id_ref.get(&kept_id)) {
+                                if let Some(table_record) = table.borrow_item_mut(table_index.to_owned()) {
+                                    table_record.seen = seen;
+                                }
+                            }
+                        },
+                        StoredRequest::Dropped => {}
                     }
                 }
             },
+            ProxyEvents::Annotated((tags, hash)) => {
+                rx.http_storage.annotate(tags, &hash);
+            },
             ProxyEvents::ResponseSent((res, hash)) => {
                 let table_id = rx.http_storage.put_response(res, &hash);
                 if let Some(id) = table_id {
-                    if let Some(pair) = rx.http_storage.get_by_id(id) {
-                        let response = pair.response.as_ref().unwrap();
-                        let table_index = rx.table_id_ref.get(&id);
-
-                        if table_index.is_none() {
-                            return;
+                    let table_index = match rx.table_id_ref.get(&id) {
+                        Some(table_index) => table_index.to_owned(),
+                        None => return
+                    };
+
+                    let pair = rx.http_storage.get_by_id(id);
+                    let is_noise = pair.is_none_or(|pair| rx.http_storage.is_noise(pair));
+
+                    // Pair is static content recognized by response, or it was not stored at all
+                    if is_noise {
+                        table.remove_item(table_index);
+                        rx.table_id_ref.remove(&id);
+                        for index in rx.table_id_ref.values_mut() {
+                            if *index > table_index {
+                                *index -= 1;
+                            }
                         }
 
-                        let possible_table_record = table.borrow_item_mut(table_index.unwrap().to_owned());
-                        if let Some(table_record) = possible_table_record {
-                            table_record.status_code = response.status.clone();
-                            table_record.response_length = response.get_length();
-                        }
+                        return;
+                    }
+
+                    let response = pair.unwrap().response.as_ref().unwrap();
+                    let possible_table_record = table.borrow_item_mut(table_index);
+                    if let Some(table_record) = possible_table_record {
+                        table_record.status_code = response.status.clone();
+                        table_record.response_length = response.get_length();
                     }
                 }
             },
@@ -341,12 +407,23 @@ fn draw_request_and_response(siv: &mut Cursive, item: usize) {
         let pair = user_data.http_storage.get_by_id(id);
 
         if let Some(pair) = pair {
+            // Matches of filter are highlighted
+            let filter_re = user_data.filter_query.as_ref().and_then(|query| query.highlight_regex());
+
             if let Some(request) = &pair.request {
-                let req_spanned = req_res_spanned::request_wrapper_to_spanned(request);
+                let mut req_spanned = req_res_spanned::request_wrapper_to_spanned(request);
+                if let Some(re) = filter_re.as_ref() {
+                    req_spanned = req_res_spanned::highlight_matches(req_spanned, re).0;
+                }
+
                 user_data.request_view_content.set_content(req_spanned);
     
                 if let Some(response) = &pair.response {
-                    let res_spanned = req_res_spanned::response_wrapper_to_spanned(response);
+                    let mut res_spanned = req_res_spanned::response_wrapper_to_spanned(response);
+                    if let Some(re) = filter_re.as_ref() {
+                        res_spanned = req_res_spanned::highlight_matches(res_spanned, re).0;
+                    }
+
                     user_data.response_view_content.set_content(res_spanned);
                 }
             }
@@ -383,10 +460,14 @@ fn store_cruster_state(siv: &mut Cursive) {
     let rs_path = format!("{}/repeater.jsonl", path_to_save);
 
     let http_storing_result = ud.http_storage.store(&http_path, None);
-    if let Err(err) = http_storing_result {
-        ud.push_error(err);
-        ud.status.set_message("Error when storing http data");
-    }
+    let merged = match http_storing_result {
+        Ok(merged) => merged,
+        Err(err) => {
+            ud.push_error(err);
+            ud.status.set_message("Error when storing http data");
+            0
+        }
+    };
 
     let rs_storing_result = ud.store_repeater_state(&rs_path);
     if let Err(err) = rs_storing_result {
@@ -396,6 +477,14 @@ fn store_cruster_state(siv: &mut Cursive) {
 
     ud.data_storing_started = false;
     ud.status.set_message("Storing completed");
+
+    // Pairs stored by other processes meanwhile are merged, and pairs of this one could get other IDs
+    if merged > 0 {
+        scope_view::refill_table(siv);
+        let ud = siv.get_cruster_userdata();
+        ud.update_status();
+        ud.status.set_message(format!("Storing completed, {} pairs stored by other processes are added", merged));
+    }
 }
 
 fn load_cruster_state(siv: &mut Cursive) {
@@ -412,11 +501,7 @@ fn load_cruster_state(siv: &mut Cursive) {
 
     let load_path = format!("{}/http.jsonl", ud.config.project.as_ref().unwrap());
     let result = if ud.is_scope_strict() {
-        ud.http_storage.load_with_strict_scope(
-            &load_path,
-            ud.include.as_ref(),
-            ud.exclude.as_ref()
-        )
+        ud.http_storage.load_with_strict_scope(&load_path, &ud.scope)
     }
     else {
         ud.http_storage.load(&load_path)
@@ -435,6 +520,28 @@ fn load_cruster_state(siv: &mut Cursive) {
         ud.status.set_message("Error while loading repeater state from file");
     }
 
+    match ProjectViews::load(ud.config.project.as_ref().unwrap()) {
+        Ok(views) => ud.views = views,
+        Err(err) => {
+            ud.push_error(err);
+            ud.status.set_message("Error while loading saved views from file");
+        }
+    }
+
+    // Table is shown as it was filtered with active view when project was left
+    let active = ud.views.active().map(|view| (view.name.clone(), view.query.clone()));
+    if let Some((name, content)) = active {
+        match Query::from_str(&content) {
+            Ok(query) => {
+                ud.filter_content = content;
+                filter_view::fill_table_with_query(siv, query);
+                siv.get_cruster_userdata().status.set_message(format!("View '{}' is applied", name));
+                return;
+            },
+            Err(e) => ud.push_error(e.into())
+        }
+    }
+
     fill_table_using_scope(siv);
 }
 
@@ -448,7 +555,7 @@ fn fill_table_using_scope(siv: &mut Cursive) {
         let req = pair.request.as_ref().unwrap();
 
         let in_scope = ud.is_uri_in_socpe(&req.uri);
-        if ! in_scope {
+        if ! in_scope || ud.http_storage.is_noise(pair) {
             continue;
         }
 
@@ -459,6 +566,7 @@ fn fill_table_using_scope(siv: &mut Cursive) {
             path: req.get_request_path(),
             status_code: "".to_string(),
             response_length: 0,
+            seen: pair.seen,
         };
 
         if let Some(res) = pair.response.as_ref() {
@@ -1,12 +1,16 @@
+use bstr::ByteSlice;
 use cursive::{Cursive, views::TextView};
 use cli_clipboard::{ClipboardContext, ClipboardProvider};
 
 use crate::utils::CrusterError;
+use crate::export_code::{self, CodeLang};
+use crate::cruster_proxy::request_response::HyperRequestWrapper;
 
 pub(super) enum CopySubject {
     FullScreenRequest,
     FullScreenResponse,
     FullScreenRequestAndResponse,
+    RequestAsCode(HyperRequestWrapper, CodeLang),
     Help
 }
 
@@ -106,6 +110,21 @@ impl CopyToClipboard for Cursive {
                     Some(Ok(()))
                 }
             },
+            CopySubject::RequestAsCode(request, lang) => {
+                let code = export_code::export_request(&request, lang);
+                if let Err(err) = ctx.set_contents(code.to_str_lossy().to_string()) {
+                    Some(
+                        Err(
+                            CrusterError::UndefinedError(
+                                format!("Could not set clipboard's content: {}", err.to_string())
+                            )
+                        )
+                    )
+                }
+                else {
+                    Some(Ok(()))
+                }
+            },
             CopySubject::Help => {
                 self.call_on_name("help-popup", |req: &mut TextView| {
                     let content = req.get_content();
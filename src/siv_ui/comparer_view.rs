@@ -0,0 +1,224 @@
+use cursive::{
+    Cursive,
+    views::{
+        Dialog,
+        OnEventView,
+        TextView
+    },
+    view::{
+        Nameable,
+        Resizable,
+        Scrollable
+    },
+    event::Key,
+    utils::markup::StyledString,
+    theme::{BaseColor, Effect, Style},
+};
+
+use super::{views_stack, http_table, sivuserdata::GetCrusterUserData};
+use crate::analysis::diff::{self, DiffMode, DiffSubject, DiffTag, Comparison};
+
+pub(super) struct ComparerItem {
+    label: String,
+    request: Option<DiffSubject>,
+    response: Option<DiffSubject>,
+}
+
+/// Two last items sent to comparer and the way they are compared
+pub(super) struct ComparerState {
+    items: Vec<ComparerItem>,
+    mode: DiffMode,
+    requests: bool,
+}
+
+impl Default for ComparerState {
+    fn default() -> Self {
+        ComparerState {
+            items: Vec::with_capacity(2),
+            mode: DiffMode::Lines,
+            requests: false,
+        }
+    }
+}
+
+fn push_item(siv: &mut Cursive, item: ComparerItem) {
+    let ud = siv.get_cruster_userdata();
+    let label = item.label.clone();
+    if ud.comparer.items.len() >= 2 {
+        ud.comparer.items.remove(0);
+    }
+
+    ud.comparer.items.push(item);
+    if ud.comparer.items.len() < 2 {
+        ud.status.set_message(format!("{} is sent to comparer, send one more to compare", label));
+        return;
+    }
+
+    draw_comparer(siv);
+}
+
+pub(super) fn add_selected_pair(siv: &mut Cursive) {
+    let id = match http_table::get_selected_id(siv) {
+        Some(id) => id,
+        None => return
+    };
+
+    let ud = siv.get_cruster_userdata();
+    let item = match ud.http_storage.get_by_id(id) {
+        Some(pair) => {
+            let title = pair.request
+                .as_ref()
+                .map(|req| format!("{} {}", &req.method, req.get_request_path()))
+                .unwrap_or_default();
+
+            ComparerItem {
+                label: format!("#{} {}", pair.index, title),
+                request: pair.request.as_ref().map(DiffSubject::from),
+                response: pair.response.as_ref().map(DiffSubject::from),
+            }
+        },
+        None => return
+    };
+
+    push_item(siv, item);
+}
+
+pub(super) fn add_repeater(siv: &mut Cursive, idx: usize) {
+    let ud = siv.get_cruster_userdata();
+    let item = match ud.repeater_state.get(idx) {
+        Some(state) => {
+            let response = state.response.get_content();
+            let response = if response.source().is_empty() {
+                None
+            }
+            else {
+                Some(DiffSubject::from_text(response.source()))
+            };
+
+            ComparerItem {
+                label: format!("Repeater #{}", idx),
+                request: Some(DiffSubject::from_text(&state.request)),
+                response,
+            }
+        },
+        None => return
+    };
+
+    push_item(siv, item);
+}
+
+fn comparison_to_spanned(comparison: &Comparison, mode: DiffMode) -> StyledString {
+    let mut result = StyledString::new();
+    for section in comparison.sections.iter() {
+        result.append(StyledString::styled(format!("@@ {} @@\n", section.name), BaseColor::Cyan.light()));
+        for (tag, chunk) in section.chunks.iter() {
+            // Cursive cannot draw NUL bytes
+            let chunk = chunk.replace('\0', "\\0");
+            let chunk = match mode {
+                DiffMode::Lines => {
+                    let prefix = match tag {
+                        DiffTag::Equal => " ",
+                        DiffTag::Removed => "-",
+                        DiffTag::Added => "+",
+                    };
+
+                    let mut line = format!("{}{}", prefix, chunk.trim_end_matches(['\r', '\n']));
+                    line.push('\n');
+                    line
+                },
+                DiffMode::Words => chunk
+            };
+
+            match tag {
+                DiffTag::Equal => result.append_plain(chunk),
+                DiffTag::Removed => result.append_styled(chunk, Style::from(BaseColor::Red.light()).combine(Effect::Strikethrough)),
+                DiffTag::Added => result.append_styled(chunk, BaseColor::Green.light()),
+            }
+        }
+
+        if mode == DiffMode::Words {
+            result.append_plain("\n");
+        }
+    }
+
+    return result;
+}
+
+fn make_comparison(siv: &mut Cursive) -> Option<(StyledString, String)> {
+    let ud = siv.get_cruster_userdata();
+    let state = &ud.comparer;
+    if state.items.len() < 2 {
+        return None;
+    }
+
+    let (left, right) = (&state.items[0], &state.items[1]);
+    let subjects = if state.requests {
+        (left.request.as_ref(), right.request.as_ref())
+    }
+    else {
+        (left.response.as_ref(), right.response.as_ref())
+    };
+
+    let (left_subject, right_subject) = match subjects {
+        (Some(l), Some(r)) => (l, r),
+        _ => {
+            let message = format!(
+                "There is no {} to compare, press 'r' to switch",
+                if state.requests { "request" } else { "response" }
+            );
+            return Some((StyledString::plain(message.clone()), message));
+        }
+    };
+
+    let comparison = diff::compare(left_subject, right_subject, state.mode);
+    return Some((comparison_to_spanned(&comparison, state.mode), comparison.stats.summary()));
+}
+
+fn redraw_comparer(siv: &mut Cursive) {
+    if let Some((content, summary)) = make_comparison(siv) {
+        siv.call_on_name("comparer-content", |tv: &mut TextView| { tv.set_content(content); });
+        siv.get_cruster_userdata().status.set_message(summary);
+    }
+}
+
+fn toggle_mode(siv: &mut Cursive) {
+    let state = &mut siv.get_cruster_userdata().comparer;
+    state.mode = match state.mode {
+        DiffMode::Lines => DiffMode::Words,
+        DiffMode::Words => DiffMode::Lines,
+    };
+
+    redraw_comparer(siv);
+}
+
+fn toggle_subjects(siv: &mut Cursive) {
+    let state = &mut siv.get_cruster_userdata().comparer;
+    state.requests = !state.requests;
+
+    redraw_comparer(siv);
+}
+
+pub(super) fn draw_comparer(siv: &mut Cursive) {
+    let (content, summary) = match make_comparison(siv) {
+        Some(comparison) => comparison,
+        None => {
+            siv.get_cruster_userdata().status.set_message("Send two items to comparer with <Shift> + c first");
+            return;
+        }
+    };
+
+    let ud = siv.get_cruster_userdata();
+    let title = format!("Comparer: {} <-> {}", &ud.comparer.items[0].label, &ud.comparer.items[1].label);
+
+    let with_events = OnEventView::new(TextView::new(content).with_name("comparer-content").scrollable())
+        .on_event(Key::Esc, |s: &mut Cursive| { views_stack::pop_layer(s); })
+        .on_event('w', |s: &mut Cursive| { toggle_mode(s); })
+        .on_event('r', |s: &mut Cursive| { toggle_subjects(s); });
+
+    let dialog = Dialog::around(with_events)
+        .title(title)
+        .full_screen();
+
+    views_stack::push_fullscreen_layer(siv, dialog);
+    siv.get_cruster_userdata().status.set_message(summary);
+}
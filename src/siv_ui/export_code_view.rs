@@ -0,0 +1,80 @@
+use cursive::{
+    Cursive,
+    views::{
+        Dialog,
+        OnEventView,
+        SelectView
+    },
+    event::Key,
+};
+
+use super::{
+    views_stack,
+    http_table,
+    sivuserdata::GetCrusterUserData,
+    clipboard::{CopyToClipboard, CopySubject},
+};
+use crate::{
+    export_code::CodeLang,
+    utils::CrusterError,
+    cruster_proxy::request_response::HyperRequestWrapper
+};
+
+pub(super) fn draw_export_code_select(siv: &mut Cursive, request: HyperRequestWrapper) {
+    let mut langs: SelectView<CodeLang> = SelectView::new();
+    for lang in CodeLang::all() {
+        langs.add_item(lang.to_string(), lang);
+    }
+
+    langs.set_on_submit(move |s: &mut Cursive, lang: &CodeLang| {
+        views_stack::pop_layer(s);
+        if let Err(err) = s.copy_to_clipboard(CopySubject::RequestAsCode(request.clone(), lang.to_owned())) {
+            s.get_cruster_userdata().push_error(err);
+            s.get_cruster_userdata().status.set_message("Copy to clibpoard failed");
+        }
+        else {
+            s.get_cruster_userdata().status.set_message(format!("Copied as {}!", lang));
+        }
+    });
+
+    let with_event = OnEventView::new(langs)
+        .on_event(Key::Esc, |s: &mut Cursive| { views_stack::pop_layer(s); });
+
+    let dialog = Dialog::around(with_event).title("Copy request as");
+    views_stack::push_layer(siv, dialog);
+}
+
+/// Export request of the pair selected in proxy table
+pub(super) fn export_selected_pair(siv: &mut Cursive) {
+    let possible_id = http_table::get_selected_id(siv);
+    let ud = siv.get_cruster_userdata();
+
+    let request = match possible_id.and_then(|id| ud.http_storage.get_by_id(id)) {
+        Some(pair) => pair.request.clone(),
+        None => None
+    };
+
+    match request {
+        Some(request) => draw_export_code_select(siv, request),
+        None => {
+            ud.push_error(CrusterError::EmptyRequest("Could not export request of selected pair".to_string()));
+            ud.status.set_message("Nothing to export");
+        }
+    }
+}
+
+pub(super) fn export_repeater_request(siv: &mut Cursive, idx: usize) {
+    let ud = siv.get_cruster_userdata();
+    let possible_request = match ud.repeater_state.get(idx) {
+        Some(state) => state.make_reqwest(),
+        None => Err(CrusterError::UndefinedError(format!("Could not find repeater state #{}", idx)))
+    };
+
+    match possible_request {
+        Ok(request) => draw_export_code_select(siv, HyperRequestWrapper::from(&request)),
+        Err(err) => {
+            ud.push_error(err);
+            ud.status.set_message("Could not export repeater's request");
+        }
+    }
+}
@@ -1,9 +1,9 @@
-use regex::Regex;
 use cursive::{
     Cursive,
     views::{
         TextView,
         EditView,
+        SelectView,
         Dialog,
         StackView,
         LinearLayout,
@@ -27,7 +27,9 @@ use cursive::{
 };
 
 use super::{sivuserdata::SivUserData, ProxyDataForTable, http_table::HTTPTable};
-use crate::http_storage::RequestResponsePair;
+use std::str::FromStr;
+use crate::query::Query;
+use crate::utils::CrusterError;
 
 pub(super) fn draw_filter(siv: &mut Cursive) {
     let ud: &mut SivUserData = siv.user_data().unwrap();
@@ -54,14 +56,32 @@ pub(super) fn draw_filter(siv: &mut Cursive) {
         }),
     };
 
+    // Empty value means no view, it clears filter
+    let active = ud.views.active().map(|view| view.name.clone());
+    let mut views = SelectView::<String>::new()
+        .popup()
+        .item("<no view>", String::new())
+        .on_submit(|s: &mut Cursive, name: &String| { select_view(s, name); });
+
+    for (i, view) in ud.views.iter().enumerate() {
+        views.add_item(view.name.clone(), view.name.clone());
+        if active.as_ref() == Some(&view.name) {
+            views.set_selection(i + 1);
+        }
+    }
+
     let with_theme = ThemedView::new(theme, editable);
     let layout = LinearLayout::horizontal()
         .child(txt)
         .child(with_theme)
+        .child(TextView::new("  "))
+        .child(views.with_name("filter-views"))
         // .min_height(3)
         .full_width();
 
-    let dialog = Dialog::around(layout).title(" Filter Regex ");
+    let dialog = Dialog::around(layout)
+        .title(" Filter Query ")
+        .button("Save as view", |s: &mut Cursive| { draw_view_name_dialog(s); });
     let with_events = OnEventView::new(dialog)
         .on_event('F', |_s: &mut Cursive| {})
         .on_event(event::Key::Esc, |s: &mut Cursive| { hide_filter(s, None) });
@@ -72,65 +92,143 @@ pub(super) fn draw_filter(siv: &mut Cursive) {
     siv.focus_name("filter-content").unwrap();
 }
 
-fn apply(siv: &mut Cursive, content: &str) {
+pub(super) fn apply(siv: &mut Cursive, content: &str) {
     if content.is_empty() {
+        siv.with_user_data(|ud: &mut SivUserData| { ud.filter_query = None; });
         super::fill_table_using_scope(siv);
+        mark_active_view(siv, content);
         hide_filter(siv, Some(content));
 
         return;
     }
 
-    let possible_re = Regex::new(content);
-    match possible_re {
-        Ok(re) => {
-            let table_name = siv.with_user_data(|ud: &mut SivUserData| { ud.active_http_table_name } ).unwrap();
-            let table_items = siv.call_on_name(table_name, |table: &mut HTTPTable| {
-                // TODO: ensure that popping one is the needed
-                table.take_items()
-            });
-            drop(table_items);
-
-            let ud: &mut SivUserData = siv.user_data().unwrap();
-            ud.table_id_ref.clear();
-
-            let mut items: Vec<ProxyDataForTable> = Vec::with_capacity(ud.http_storage.len());
-            for pair in ud.http_storage.into_iter() {
-                let req = pair.request.as_ref().unwrap();
-                let in_scope = ud.is_uri_in_socpe(&req.uri);
-                if ! in_scope {
-                    continue;
-                }
-
-                if is_pair_match_filter(pair, &re) {
-                    let mut table_record = ProxyDataForTable {
-                        id: pair.index,
-                        method: req.method.clone(),
-                        hostname: req.get_hostname(),
-                        path: req.get_request_path(),
-                        status_code: "".to_string(),
-                        response_length: 0,
-                    };
-
-                    if let Some(res) = pair.response.as_ref() {
-                        table_record.status_code = res.status.clone();
-                        table_record.response_length = res.get_length();
-                    }
-
-                    let id = table_record.id;
-                    items.push(table_record);
-                    ud.table_id_ref.insert(id, items.len() - 1);
-                }
-            }
-
-            siv.call_on_name(table_name, move |table: &mut HTTPTable| { table.set_items(items); });
+    match Query::parse_or_regex(content) {
+        Ok(query) => {
+            fill_table_with_query(siv, query);
+            mark_active_view(siv, content);
             hide_filter(siv, Some(content));
         },
         Err(e) => {
+            // Filter stays open, so query can be fixed, error with pointer to its position is in the errors list
             let ud: &mut SivUserData = siv.user_data().unwrap();
-            ud.push_error(e.into());
+            ud.status.set_message(e.to_string());
+            ud.push_error(CrusterError::UnacceptableFilter(e.explain(content)));
+        }
+    }
+}
 
-            hide_filter(siv, Some(content));
+/// Shows in table only pairs matching query, query becomes the active filter
+pub(super) fn fill_table_with_query(siv: &mut Cursive, query: Query) {
+    let table_name = siv.with_user_data(|ud: &mut SivUserData| { ud.active_http_table_name } ).unwrap();
+    let table_items = siv.call_on_name(table_name, |table: &mut HTTPTable| {
+        // TODO: ensure that popping one is the needed
+        table.take_items()
+    });
+    drop(table_items);
+
+    let ud: &mut SivUserData = siv.user_data().unwrap();
+    ud.table_id_ref.clear();
+
+    let mut items: Vec<ProxyDataForTable> = Vec::with_capacity(ud.http_storage.len());
+    let found = ud.http_storage.query(&query, &|uri| ud.is_uri_in_socpe(uri));
+    for pair in found {
+        let req = pair.request.as_ref().unwrap();
+        let in_scope = ud.is_uri_in_socpe(&req.uri);
+        if ! in_scope || ud.http_storage.is_noise(pair) {
+            continue;
+        }
+
+        let mut table_record = ProxyDataForTable {
+            id: pair.index,
+            method: req.method.clone(),
+            hostname: req.get_hostname(),
+            path: req.get_request_path(),
+            status_code: "".to_string(),
+            response_length: 0,
+            seen: pair.seen,
+        };
+
+        if let Some(res) = pair.response.as_ref() {
+            table_record.status_code = res.status.clone();
+            table_record.response_length = res.get_length();
         }
+
+        let id = table_record.id;
+        items.push(table_record);
+        ud.table_id_ref.insert(id, items.len() - 1);
+    }
+
+    ud.filter_query = Some(query);
+    siv.call_on_name(table_name, move |table: &mut HTTPTable| { table.set_items(items); });
+}
+
+/// View with the same query as applied filter becomes active, so it is restored with project
+fn mark_active_view(siv: &mut Cursive, content: &str) {
+    let ud: &mut SivUserData = siv.user_data().unwrap();
+    let name = ud.views
+        .iter()
+        .find(|view| !content.is_empty() && view.query == content)
+        .map(|view| view.name.clone());
+
+    if ud.views.active().map(|view| &view.name) != name.as_ref() {
+        ud.update_views(|views| views.set_active(name.as_deref()));
+    }
+}
+
+fn select_view(siv: &mut Cursive, name: &str) {
+    let ud: &mut SivUserData = siv.user_data().unwrap();
+    let query = ud.views
+        .get(name)
+        .map(|view| view.query.clone())
+        .unwrap_or_default();
+
+    siv.call_on_name("filter-content", |edit: &mut EditView| { edit.set_content(query.clone()); });
+    apply(siv, &query);
+}
+
+fn draw_view_name_dialog(siv: &mut Cursive) {
+    let content = siv
+        .call_on_name("filter-content", |edit: &mut EditView| { edit.get_content() })
+        .unwrap();
+
+    let ud: &mut SivUserData = siv.user_data().unwrap();
+    // Views are used by CLI too, so only valid queries are saved
+    if let Err(e) = Query::from_str(&content) {
+        ud.status.set_message(e.to_string());
+        return;
+    }
+
+    let editable = EditView::new()
+        .on_submit(move |s: &mut Cursive, name: &str| { save_view(s, name, &content); })
+        .with_name("view-name")
+        .min_width(30);
+
+    let dialog = Dialog::around(editable).title(" View Name ");
+    let with_events = OnEventView::new(dialog)
+        .on_event(event::Key::Esc, |s: &mut Cursive| {
+            s.call_on_name("views-stack", |sv: &mut StackView| { sv.pop_layer() });
+        });
+
+    ud.status.set_message("Press <Enter> to save view or <Esc> to go back");
+    siv.call_on_name("views-stack", |sv: &mut StackView| { sv.add_layer(with_events) });
+    siv.focus_name("view-name").unwrap();
+}
+
+fn save_view(siv: &mut Cursive, name: &str, query: &str) {
+    let name = name.trim();
+    if name.is_empty() {
+        return;
+    }
+
+    let ud: &mut SivUserData = siv.user_data().unwrap();
+    let is_new = ud.views.get(name).is_none();
+    ud.update_views(|views| views.save(name, query));
+    ud.status.set_message(format!("View '{}' is saved", name));
+
+    siv.call_on_name("views-stack", |sv: &mut StackView| { sv.pop_layer() });
+    if is_new {
+        let name = name.to_string();
+        siv.call_on_name("filter-views", move |select: &mut SelectView<String>| { select.add_item(name.clone(), name); });
     }
 }
 
@@ -144,10 +242,3 @@ fn hide_filter(siv: &mut Cursive, content: Option<&str>) {
 
     siv.call_on_name("views-stack", |sv: &mut StackView| { sv.pop_layer() });
 }
-
-pub(super) fn is_pair_match_filter(pair: &RequestResponsePair, re: &Regex) -> bool {
-    let req = pair.request.as_ref().unwrap();
-    let res = pair.response.as_ref();
-
-    return req.serach_with_re(&re) || (res.is_some() && res.unwrap().serach_with_re(&re));
-}
\ No newline at end of file
@@ -28,14 +28,28 @@ pub(super) fn make_help_message() -> SpannedString<Style> {
         SpannedString::styled("<Esc> - ", letters_style.clone()),
         SpannedString::styled("Close secondary view (i.e. help, errors, etc.)\n", descriptions_style.clone()),
 
+        SpannedString::styled("<Shift> + p - ", letters_style.clone()),
+        SpannedString::styled("Show inventory of endpoints with their parameters\n", descriptions_style.clone()),
+
         SpannedString::styled("<Shift> + r - ", letters_style.clone()),
         SpannedString::styled("Repeat request selected on table\n", descriptions_style.clone()),
 
+        SpannedString::styled("<Shift> + m - ", letters_style.clone()),
+        SpannedString::styled("Show site map of captured hosts and paths, <Enter> on node filters table\n", descriptions_style.clone()),
+
         SpannedString::styled("<Shift> + s - ", letters_style.clone()),
         SpannedString::styled("Store proxy data on drive, file path is configured on start\n", descriptions_style.clone()),
 
+        SpannedString::styled("<Shift> + c - ", letters_style.clone()),
+        SpannedString::styled("Send pair selected on table to comparer, comparer is shown when two items are sent", descriptions_style.clone()),
+        SpannedString::styled("\n    <On Repeater View> - ", BaseColor::Yellow.dark()),
+        SpannedString::styled("Send repeater to comparer\n", descriptions_style.clone()),
+
         SpannedString::styled("<Shift> + f - ", letters_style.clone()),
-        SpannedString::styled("Set filter for table\n", descriptions_style.clone()),
+        SpannedString::styled("Set filter query for table\n", descriptions_style.clone()),
+
+        SpannedString::styled("<Shift> + i - ", letters_style.clone()),
+        SpannedString::styled("Edit scope, applied scope is saved in project\n", descriptions_style.clone()),
 
         SpannedString::styled("e - ", letters_style.clone()),
         SpannedString::styled("Show error logs view\n", descriptions_style.clone()),
@@ -48,6 +62,10 @@ pub(super) fn make_help_message() -> SpannedString<Style> {
         SpannedString::styled("\n    <On Repeater View> - ", BaseColor::Yellow.dark()),
         SpannedString::styled("Edit request\n", descriptions_style.clone()),
 
+        SpannedString::styled("n / N - ", letters_style.clone()),
+        SpannedString::styled("\n    <On FullScreen Request/Response> - ", BaseColor::Yellow.dark()),
+        SpannedString::styled("Jump to next / previous match of filter\n", descriptions_style.clone()),
+
         SpannedString::styled("p - ", letters_style.clone()),
         SpannedString::styled("\n    <On Repeater View> - ", BaseColor::Yellow.dark()),
         SpannedString::styled("Show parameters\n", descriptions_style.clone()),
@@ -56,12 +74,24 @@ pub(super) fn make_help_message() -> SpannedString<Style> {
         SpannedString::styled("\n    <On Proxy Table> - ", BaseColor::Yellow.dark()),
         SpannedString::styled("Show active repeaters", descriptions_style.clone()),
         SpannedString::styled("\n    <On FullScreen Request/Response> - ", BaseColor::Yellow.dark()),
-        SpannedString::styled("Copy request content to clipboard\n", descriptions_style.clone()),
+        SpannedString::styled("Copy request content to clipboard", descriptions_style.clone()),
+        SpannedString::styled("\n    <On Comparer View> - ", BaseColor::Yellow.dark()),
+        SpannedString::styled("Switch between comparing responses and requests\n", descriptions_style.clone()),
 
         SpannedString::styled("s - ", letters_style.clone()),
         SpannedString::styled("\n    <On FullScreen Request/Response> - ", BaseColor::Yellow.dark()),
         SpannedString::styled("Copy response content to clipboard\n", descriptions_style.clone()),
 
+        SpannedString::styled("x - ", letters_style.clone()),
+        SpannedString::styled("\n    <On FullScreen Request/Response> - ", BaseColor::Yellow.dark()),
+        SpannedString::styled("Copy request as curl, Python, raw HTTP or PowerShell code", descriptions_style.clone()),
+        SpannedString::styled("\n    <On Repeater View> - ", BaseColor::Yellow.dark()),
+        SpannedString::styled("Copy request as curl, Python, raw HTTP or PowerShell code\n", descriptions_style.clone()),
+
+        SpannedString::styled("w - ", letters_style.clone()),
+        SpannedString::styled("\n    <On Comparer View> - ", BaseColor::Yellow.dark()),
+        SpannedString::styled("Switch between line and word difference\n", descriptions_style.clone()),
+
         SpannedString::styled("t - ", letters_style.clone()),
         SpannedString::styled("Show fullscreen HTTP proxy table\n", descriptions_style.clone()),
 
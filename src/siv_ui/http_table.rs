@@ -5,7 +5,10 @@ use cursive::{
         OnEventView,
         StackView,
         TextView,
-        LinearLayout
+        LinearLayout,
+        ScrollView,
+        ResizedView,
+        NamedView
     },
     view::{
         Nameable,
@@ -17,7 +20,7 @@ use cursive::{
     }, utils::span::SpannedString,
 };
 use cursive_table_view::TableView;
-use std::cmp::Ordering;
+use std::{cmp::Ordering, cell::Cell, rc::Rc};
 
 use super::{
     BasicColumn,
@@ -26,6 +29,7 @@ use super::{
     draw_request_and_response,
     req_res_spanned,
     views_stack,
+    export_code_view,
     clipboard::{CopyToClipboard, CopySubject}, sivuserdata::GetCrusterUserData
 };
 use crate::utils::CrusterError;
@@ -34,6 +38,15 @@ use crate::utils::CrusterError;
 
 pub(super) type HTTPTable = TableView<ProxyDataForTable, BasicColumn>;
 
+// Matches of filter in fullscreen request and response, to jump between them
+struct FilterMatches {
+    request_text: String,
+    response_text: String,
+    // Whether match is in response and its offset in text
+    positions: Vec<(bool, usize)>,
+    current: Cell<Option<usize>>,
+}
+
 pub(super) fn new_table() -> HTTPTable {
     let table: HTTPTable = HTTPTable::new()
         .on_submit(|siv: &mut Cursive, _: usize, __: usize| { draw_fullscreen_request_and_response(siv); })
@@ -67,6 +80,11 @@ pub(super) fn new_table() -> HTTPTable {
             BasicColumn::ResponseLength,
             "Length",
             |c| {c.width(12)}
+        )
+        .column(
+            BasicColumn::Seen,
+            "Seen",
+            |c| {c.width(6)}
         );
 
     return table;
@@ -163,13 +181,45 @@ fn draw_fullscreen_request_and_response(siv: &mut Cursive) {
                         SpannedString::new()
                     };
 
+                    let filter_re = ud.filter_query.as_ref().and_then(|query| query.highlight_regex());
+                    let (req_spanned, res_spanned, positions) = match filter_re.as_ref() {
+                        Some(re) => {
+                            let (req_spanned, req_matches) = req_res_spanned::highlight_matches(req_spanned, re);
+                            let (res_spanned, res_matches) = req_res_spanned::highlight_matches(res_spanned, re);
+                            let positions: Vec<(bool, usize)> = req_matches
+                                .into_iter()
+                                .map(|offset| (false, offset))
+                                .chain(res_matches.into_iter().map(|offset| (true, offset)))
+                                .collect();
+
+                            (req_spanned, res_spanned, positions)
+                        },
+                        None => (req_spanned, res_spanned, Vec::default())
+                    };
+
+                    let matches = Rc::new(
+                        FilterMatches {
+                            request_text: req_spanned.source().to_string(),
+                            response_text: res_spanned.source().to_string(),
+                            positions,
+                            current: Cell::new(None)
+                        }
+                    );
+                    let (next_matches, previous_matches) = (matches.clone(), matches.clone());
+
+                    if !matches.positions.is_empty() {
+                        ud.status.set_message(format!("{} matches of filter, press 'n'/'N' to jump to next/previous", matches.positions.len()));
+                    }
+
                     let request_view = TextView::new(req_spanned)
                         .with_name("request-fs-content")
-                        .scrollable();
+                        .scrollable()
+                        .with_name("request-fs-scroll");
                     let response_view = TextView::new(res_spanned)
                         .with_name("response-fs-content")
                         .full_screen()
-                        .scrollable();
+                        .scrollable()
+                        .with_name("response-fs-scroll");
                     
                     let layout = LinearLayout::horizontal()
                         .child(Dialog::around(request_view).title("Request").with_name("request-fs"))
@@ -212,6 +262,15 @@ fn draw_fullscreen_request_and_response(siv: &mut Cursive) {
                             else {
                                 s.get_cruster_userdata().status.set_message("Copied!");
                             }
+                        })
+                        .on_event('x', |s: &mut Cursive| {
+                            export_code_view::export_selected_pair(s);
+                        })
+                        .on_event('n', move |s: &mut Cursive| {
+                            jump_to_match(s, &next_matches, true);
+                        })
+                        .on_event('N', move |s: &mut Cursive| {
+                            jump_to_match(s, &previous_matches, false);
                         });
                     
                     views_stack::push_fullscreen_layer(siv, layout_with_event);
@@ -231,6 +290,40 @@ fn draw_fullscreen_request_and_response(siv: &mut Cursive) {
     }
 }
 
+fn jump_to_match(siv: &mut Cursive, matches: &FilterMatches, forward: bool) {
+    let count = matches.positions.len();
+    if count == 0 {
+        siv.get_cruster_userdata().status.set_message("Nothing matches filter here");
+        return;
+    }
+
+    let next = match (matches.current.get(), forward) {
+        (None, true) => 0,
+        (None, false) => count - 1,
+        (Some(current), true) => (current + 1) % count,
+        (Some(current), false) => (current + count - 1) % count,
+    };
+    matches.current.set(Some(next));
+
+    let (in_response, offset) = matches.positions[next];
+    if in_response {
+        siv.call_on_name("response-fs-scroll", |scroll: &mut ScrollView<ResizedView<NamedView<TextView>>>| {
+            let row = req_res_spanned::row_of_offset(&matches.response_text, offset, scroll.content_viewport().width());
+            let _ = scroll.set_offset((0, row));
+        });
+        let _ = siv.focus_name("response-fs");
+    }
+    else {
+        siv.call_on_name("request-fs-scroll", |scroll: &mut ScrollView<NamedView<TextView>>| {
+            let row = req_res_spanned::row_of_offset(&matches.request_text, offset, scroll.content_viewport().width());
+            let _ = scroll.set_offset((0, row));
+        });
+        let _ = siv.focus_name("request-fs");
+    }
+
+    siv.get_cruster_userdata().status.set_message(format!("Match {} of {}", next + 1, count));
+}
+
 /// `id` in terms of `HTTPStorage`
 pub(super) fn get_selected_id(siv: &mut Cursive) -> Option<usize> {
     let ud: &mut SivUserData = siv.user_data().unwrap();
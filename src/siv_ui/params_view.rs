@@ -0,0 +1,73 @@
+use std::rc::Rc;
+use cursive::{
+    Cursive,
+    views::{
+        Dialog,
+        OnEventView,
+        SelectView,
+        TextView
+    },
+    view::{
+        Resizable,
+        Scrollable
+    },
+    event::Key,
+};
+
+use super::{views_stack, sivuserdata::GetCrusterUserData};
+use crate::analysis::params::ParamsInventory;
+
+fn draw_endpoint_params(siv: &mut Cursive, inventory: &ParamsInventory, key: &(String, String)) {
+    let endpoint = match inventory.endpoints.get(key) {
+        Some(endpoint) => endpoint,
+        None => return
+    };
+
+    let content = ParamsInventory::endpoint_to_lines(endpoint).join("\n");
+    let with_events = OnEventView::new(TextView::new(content).scrollable())
+        .on_event(Key::Esc, |s: &mut Cursive| { views_stack::pop_layer(s); });
+
+    let dialog = Dialog::around(with_events)
+        .title(endpoint.title())
+        .full_screen();
+
+    views_stack::push_fullscreen_layer(siv, dialog);
+}
+
+pub(super) fn draw_params_inventory(siv: &mut Cursive) {
+    let ud = siv.get_cruster_userdata();
+    let in_scope = ud.http_storage
+        .into_iter()
+        .filter(|pair| {
+            pair.request
+                .as_ref()
+                .map(|req| ud.is_uri_in_socpe(&req.uri))
+                .unwrap_or(false)
+        });
+
+    let inventory = Rc::new(ParamsInventory::from_pairs(in_scope));
+    if inventory.endpoints.is_empty() {
+        ud.status.set_message("There are no endpoints to show");
+        return;
+    }
+
+    let mut endpoints: SelectView<(String, String)> = SelectView::new();
+    for (key, endpoint) in inventory.endpoints.iter() {
+        let label = format!("{} ({} params, {} requests)", endpoint.title(), endpoint.params.len(), endpoint.count);
+        endpoints.add_item(label, key.clone());
+    }
+
+    endpoints.set_on_submit(move |s: &mut Cursive, key: &(String, String)| {
+        draw_endpoint_params(s, &inventory, key);
+    });
+
+    let with_events = OnEventView::new(endpoints.scrollable())
+        .on_event(Key::Esc, |s: &mut Cursive| { views_stack::pop_layer(s); });
+
+    let dialog = Dialog::around(with_events)
+        .title("Endpoints")
+        .full_screen();
+
+    views_stack::push_fullscreen_layer(siv, dialog);
+    siv.get_cruster_userdata().status.set_message("Press <Enter> to show parameters of endpoint");
+}
@@ -29,7 +29,7 @@ use serde::{Serialize, Deserialize};
 
 use crate::utils::CrusterError;
 use super::{sivuserdata::SivUserData, http_table};
-use super::{views_stack, req_res_spanned::response_wrapper_to_spanned, sivuserdata::GetCrusterUserData};
+use super::{views_stack, export_code_view, comparer_view, req_res_spanned::response_wrapper_to_spanned, sivuserdata::GetCrusterUserData};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub(crate) struct RepeaterParameters {
@@ -160,7 +160,9 @@ fn draw_static_repeater(siv: &mut Cursive, idx: usize) {
         .on_event(event::Key::Esc, |s: &mut Cursive| { views_stack::pop_layer(s); })
         .on_event('p', move |s: &mut Cursive| { draw_repeater_parameters(s, idx.clone()); })
         .on_event(event::Key::Enter, move |s: &mut Cursive| { send_request(s, idx); })
-        .on_event('i', move |s: &mut Cursive| { draw_editable_repeater(s, idx); });
+        .on_event('i', move |s: &mut Cursive| { draw_editable_repeater(s, idx); })
+        .on_event('x', move |s: &mut Cursive| { export_code_view::export_repeater_request(s, idx); })
+        .on_event('C', move |s: &mut Cursive| { comparer_view::add_repeater(s, idx); });
 
     let dialog = Dialog::around(layout_with_quit).title("Repeater").full_screen();
     views_stack::push_fullscreen_layer(siv, dialog);
@@ -278,10 +280,11 @@ fn send_request(siv: &mut Cursive, idx: usize) {
             repeater_state.response.set_content("");
             repeater_state.saved_headers = request.headers().clone();
             let need_redirect = repeater_state.parameters.redirects;
+            let sessions = ud.sessions.clone();
 
             ud.status.set_message("Sending...");
             // request_executor::send_hyper_request(siv, request, Instant::now(), idx);
-            request_executor::send_request_detached(request, idx, need_redirect, siv.cb_sink().clone());
+            request_executor::send_request_detached(request, idx, need_redirect, siv.cb_sink().clone(), sessions);
         },
         Err(err) => {
             ud.status.set_message("Error when trying to repeat request");
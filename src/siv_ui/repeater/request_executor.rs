@@ -1,5 +1,5 @@
 use reqwest;
-use std::{thread, str::FromStr};
+use std::{thread, str::FromStr, sync::Arc};
 use tokio::runtime::Runtime;
 use cursive::{Cursive, utils::span::SpannedString, theme::Style};
 
@@ -7,6 +7,7 @@ use crate::{
     utils::CrusterError,
     siv_ui::req_res_spanned,
     cruster_proxy::request_response::{HyperResponseWrapper, HyperRequestWrapper},
+    session::Sessions,
 };
 use http::{HeaderMap, HeaderValue};
 
@@ -94,7 +95,7 @@ fn forge_request_for_redirect(client: &reqwest::Client, rsp_hdrs: &HeaderMap<Hea
     return Ok(request);
 }
 
-async fn send_reqwest(req: reqwest::Request, state_index: usize, redirects: bool, sink: cursive::CbSink) -> Result<SpannedString<Style>, CrusterError> {
+async fn send_reqwest(req: reqwest::Request, state_index: usize, redirects: bool, sink: cursive::CbSink) -> Result<HyperResponseWrapper, CrusterError> {
     let client = reqwest::ClientBuilder::new()
         .http1_only()
         .use_rustls_tls()
@@ -134,17 +135,30 @@ async fn send_reqwest(req: reqwest::Request, state_index: usize, redirects: bool
         }
         else {
             let wrapper = HyperResponseWrapper::from_reqwest(rsp).await?;
-            let styled_text = req_res_spanned::response_to_spanned_full(&wrapper);
-            return Ok(styled_text);
+            return Ok(wrapper);
         }
     }
 }
 
-pub(super) fn send_request_detached(req: reqwest::Request, state_index: usize, redirects: bool, sink: cursive::CbSink) {
+async fn send_with_session(req: reqwest::Request, state_index: usize, redirects: bool, sink: cursive::CbSink, sessions: &Sessions) -> Result<SpannedString<Style>, CrusterError> {
+    let send = |request: reqwest::Request| {
+        let sink = sink.clone();
+        async move {
+            let wrapper = send_reqwest(request, state_index, redirects, sink).await?;
+            return Ok::<_, CrusterError>((wrapper, ()));
+        }
+    };
+
+    let (wrapper, _) = sessions.send_with_session(req, send).await?;
+    let styled_text = req_res_spanned::response_to_spanned_full(&wrapper);
+    return Ok(styled_text);
+}
+
+pub(super) fn send_request_detached(req: reqwest::Request, state_index: usize, redirects: bool, sink: cursive::CbSink, sessions: Arc<Sessions>) {
     let _thrd = thread::spawn(
         move || {
             let runtime = Runtime::new().unwrap();
-            match runtime.block_on(send_reqwest(req, state_index, redirects, sink.clone())) {
+            match runtime.block_on(send_with_session(req, state_index, redirects, sink.clone(), &sessions)) {
                 Ok(response_text) => send_ready_event(response_text, state_index, sink),
                 Err(e) => send_error_event(e, state_index, sink)
             }
@@ -1,7 +1,8 @@
 use http::HeaderMap;
+use regex::Regex;
 use bstr::ByteSlice;
-use std::{ffi::CString, borrow::Cow};
-use cursive::{utils::{span::SpannedString, markup::StyledString}, theme::{Style, BaseColor, Effect}};
+use std::{ffi::CString, borrow::Cow, cmp::min};
+use cursive::{utils::{span::SpannedString, markup::StyledString, lines::spans::LinesIterator}, theme::{Style, BaseColor, Color, ColorStyle, Effect}};
 
 use crate::cruster_proxy::request_response::{HyperRequestWrapper, HyperResponseWrapper};
 
@@ -191,3 +192,55 @@ pub(super) fn response_wrapper_to_spanned(res: &HyperResponseWrapper) -> Spanned
 pub(super) fn response_to_spanned_full(res: &HyperResponseWrapper) -> SpannedString<Style> {
     return response_to_spanned_with_length_limit(res, 0);
 }
+
+/// Highlights matches of regex keeping the rest of styles. Returns byte offsets of matches in the text
+pub(super) fn highlight_matches(spanned: SpannedString<Style>, re: &Regex) -> (SpannedString<Style>, Vec<usize>) {
+    let text: String = spanned.spans().map(|span| span.content).collect();
+    let matches: Vec<(usize, usize)> = re
+        .find_iter(&text)
+        .filter(|m| !m.as_str().is_empty())
+        .map(|m| (m.start(), m.end()))
+        .collect();
+
+    if matches.is_empty() {
+        return (spanned, Vec::default());
+    }
+
+    let highlight = Style::from(ColorStyle::new(Color::Dark(BaseColor::Black), Color::Light(BaseColor::Yellow)));
+    let mut result: SpannedString<Style> = SpannedString::new();
+    let mut offset = 0_usize;
+    let mut current = 0_usize;
+
+    for span in spanned.spans() {
+        let end = offset + span.content.len();
+        let mut pos = offset;
+
+        while pos < end {
+            while current < matches.len() && matches[current].1 <= pos {
+                current += 1;
+            }
+
+            let (next, inside) = match matches.get(current) {
+                Some((start, finish)) if *start <= pos => (min(*finish, end), true),
+                Some((start, _)) => (min(*start, end), false),
+                None => (end, false)
+            };
+
+            let style = if inside { span.attr.combine(highlight) } else { *span.attr };
+            result.append(SpannedString::single_span(&text[pos..next], style));
+            pos = next;
+        }
+
+        offset = end;
+    }
+
+    return (result, matches.into_iter().map(|(start, _)| start).collect());
+}
+
+/// Number of the row where text at offset is shown, when text is wrapped to width
+pub(super) fn row_of_offset(text: &str, offset: usize, width: usize) -> usize {
+    let prefix = &text[..offset];
+    let rows = LinesIterator::new(&SpannedString::<Style>::plain(prefix), width.max(1)).count();
+
+    return if prefix.is_empty() || prefix.ends_with('\n') { rows } else { rows.saturating_sub(1) };
+}
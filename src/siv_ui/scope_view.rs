@@ -0,0 +1,296 @@
+use std::str::FromStr;
+use regex::Regex;
+use cursive::{
+    Cursive,
+    views::{
+        TextView,
+        EditView,
+        SelectView,
+        Checkbox,
+        Dialog,
+        LinearLayout,
+        OnEventView
+    },
+    theme::BaseColor,
+    utils::markup::StyledString,
+    view::{
+        Nameable,
+        Resizable,
+        Scrollable
+    },
+    event::Key,
+};
+
+use super::{views_stack, filter_view, sivuserdata::GetCrusterUserData};
+use crate::config::Scope;
+use crate::project;
+use crate::scope::{ScopeMatcher, ScopeRule};
+
+#[derive(Clone)]
+enum EntryKind {
+    Rule(ScopeRule),
+    Regex(String),
+}
+
+#[derive(Clone)]
+struct ScopeItem {
+    include: bool,
+    kind: EntryKind,
+}
+
+type ScopeEntries = SelectView<ScopeItem>;
+
+impl ScopeItem {
+    fn label(&self) -> String {
+        let sign = if self.include { "+" } else { "-" };
+        return match &self.kind {
+            EntryKind::Rule(rule) => format!("{} rule  {}", sign, rule),
+            EntryKind::Regex(re) => format!("{} regex {}", sign, re),
+        };
+    }
+}
+
+fn scope_to_items(scope: Option<&Scope>) -> Vec<ScopeItem> {
+    let mut items: Vec<ScopeItem> = Vec::default();
+    let scope = match scope {
+        Some(scope) => scope,
+        None => return items
+    };
+
+    for (include, rules, regexes) in [(true, &scope.include_rules, &scope.include), (false, &scope.exclude_rules, &scope.exclude)] {
+        for rule in rules.iter().flatten() {
+            items.push(ScopeItem { include, kind: EntryKind::Rule(rule.clone()) });
+        }
+
+        for re in regexes.iter().flatten() {
+            items.push(ScopeItem { include, kind: EntryKind::Regex(re.clone()) });
+        }
+    }
+
+    return items;
+}
+
+fn items_to_scope(items: &[ScopeItem], strict: bool) -> Scope {
+    let mut scope = Scope { strict, ..Scope::default() };
+    for item in items {
+        match (&item.kind, item.include) {
+            (EntryKind::Rule(rule), true) => scope.include_rules.get_or_insert_with(Vec::new).push(rule.clone()),
+            (EntryKind::Rule(rule), false) => scope.exclude_rules.get_or_insert_with(Vec::new).push(rule.clone()),
+            (EntryKind::Regex(re), true) => scope.include.get_or_insert_with(Vec::new).push(re.clone()),
+            (EntryKind::Regex(re), false) => scope.exclude.get_or_insert_with(Vec::new).push(re.clone()),
+        }
+    }
+
+    return scope;
+}
+
+pub(super) fn draw_scope_editor(siv: &mut Cursive) {
+    let ud = siv.get_cruster_userdata();
+    let mut entries: ScopeEntries = SelectView::new();
+    for item in scope_to_items(ud.config.scope.as_ref()) {
+        entries.add_item(item.label(), item);
+    }
+
+    let strict = if ud.is_scope_strict() {
+        Checkbox::new().checked().with_name("scope-strict")
+    }
+    else {
+        Checkbox::new().with_name("scope-strict")
+    };
+
+    let layout = LinearLayout::vertical()
+        .child(entries.with_name("scope-entries").scrollable().min_height(5))
+        .child(TextView::new(" "))
+        .child(
+            LinearLayout::horizontal()
+                .child(strict)
+                .child(TextView::new(" Strict: drop out-of-scope pairs from storage"))
+        );
+
+    let dialog = Dialog::around(layout)
+        .title(" Scope ")
+        .button("Include", |s: &mut Cursive| { draw_entry_dialog(s, true); })
+        .button("Exclude", |s: &mut Cursive| { draw_entry_dialog(s, false); })
+        .button("Remove", |s: &mut Cursive| { remove_selected(s); })
+        .button("Apply", |s: &mut Cursive| { apply(s); })
+        .min_width(60);
+
+    let with_events = OnEventView::new(dialog)
+        .on_event('I', |_s: &mut Cursive| {})
+        .on_event(Key::Del, |s: &mut Cursive| { remove_selected(s); })
+        .on_event(Key::Esc, |s: &mut Cursive| { views_stack::pop_layer(s); });
+
+    views_stack::push_layer(siv, with_events);
+    siv.get_cruster_userdata().status.set_message("<Del> to remove selected entry, 'Apply' to use and save scope, <Esc> to go back");
+}
+
+fn draw_entry_dialog(siv: &mut Cursive, include: bool) {
+    let editable = EditView::new()
+        .on_submit(move |s: &mut Cursive, txt: &str| { add_entry(s, include, txt, false); })
+        .with_name("scope-entry")
+        .min_width(50);
+
+    let title = if include { " Include Into Scope " } else { " Exclude From Scope " };
+    let dialog = Dialog::around(editable)
+        .title(title)
+        .button("Add as rule", move |s: &mut Cursive| {
+            let txt = s.call_on_name("scope-entry", |edit: &mut EditView| { edit.get_content() }).unwrap();
+            add_entry(s, include, &txt, false);
+        })
+        .button("Add as regex", move |s: &mut Cursive| {
+            let txt = s.call_on_name("scope-entry", |edit: &mut EditView| { edit.get_content() }).unwrap();
+            add_entry(s, include, &txt, true);
+        });
+
+    let with_events = OnEventView::new(dialog)
+        .on_event(Key::Esc, |s: &mut Cursive| { views_stack::pop_layer(s); });
+
+    views_stack::push_layer(siv, with_events);
+    siv.get_cruster_userdata().status.set_message("<Enter> to add as rule, i.e. *.example.com/api, or use buttons");
+    siv.focus_name("scope-entry").unwrap();
+}
+
+fn add_entry(siv: &mut Cursive, include: bool, txt: &str, is_regex: bool) {
+    let txt = txt.trim();
+    if txt.is_empty() {
+        return;
+    }
+
+    let kind = if is_regex {
+        Regex::new(txt)
+            .map(|_| EntryKind::Regex(txt.to_string()))
+            .map_err(|e| e.to_string())
+    }
+    else {
+        ScopeRule::from_str(txt)
+            .map(EntryKind::Rule)
+            .map_err(|e| e.to_string())
+    };
+
+    let item = match kind {
+        Ok(kind) => ScopeItem { include, kind },
+        Err(e) => {
+            // Dialog stays open, so entry can be fixed
+            siv.get_cruster_userdata().status.set_message(e);
+            return;
+        }
+    };
+
+    views_stack::pop_layer(siv);
+    siv.call_on_name("scope-entries", move |entries: &mut ScopeEntries| {
+        entries.add_item(item.label(), item);
+        let _ = entries.set_selection(entries.len() - 1);
+    });
+}
+
+fn remove_selected(siv: &mut Cursive) {
+    siv.call_on_name("scope-entries", |entries: &mut ScopeEntries| {
+        if let Some(id) = entries.selected_id() {
+            let _ = entries.remove_item(id);
+        }
+    });
+}
+
+fn apply(siv: &mut Cursive) {
+    let items: Vec<ScopeItem> = siv
+        .call_on_name("scope-entries", |entries: &mut ScopeEntries| {
+            entries.iter().map(|(_, item)| item.clone()).collect()
+        })
+        .unwrap();
+
+    let strict = siv
+        .call_on_name("scope-strict", |cb: &mut Checkbox| { cb.is_checked() })
+        .unwrap();
+
+    let scope = items_to_scope(&items, strict);
+    let ud = siv.get_cruster_userdata();
+    // Entries are checked when they are added, so it is not expected to fail
+    match ScopeMatcher::try_from(&scope) {
+        Ok(matcher) => ud.scope = matcher,
+        Err(err) => {
+            ud.status.set_message(err.to_string());
+            return;
+        }
+    }
+
+    if let Some(project) = ud.config.project.clone() {
+        if let Err(err) = project::scope::store(&project, &scope) {
+            ud.push_error(err);
+        }
+    }
+
+    ud.config.scope = Some(scope);
+    views_stack::pop_layer(siv);
+    refill_table(siv);
+
+    if strict {
+        popup_purge(siv);
+    }
+    else {
+        siv.get_cruster_userdata().status.set_message("Scope is applied");
+    }
+}
+
+/// Table is refilled with the new scope, active filter is kept
+pub(super) fn refill_table(siv: &mut Cursive) {
+    match siv.get_cruster_userdata().filter_query.clone() {
+        Some(query) => filter_view::fill_table_with_query(siv, query),
+        None => super::fill_table_using_scope(siv)
+    }
+}
+
+/// Only completed pairs are removed, the ones waiting for response are left as is
+fn out_of_scope_ids(siv: &mut Cursive) -> Vec<usize> {
+    let ud = siv.get_cruster_userdata();
+    return ud.http_storage
+        .into_iter()
+        .filter(|pair| pair.response.is_some())
+        .filter(|pair| {
+            pair.request
+                .as_ref()
+                .map(|req| !ud.is_uri_in_socpe(&req.uri))
+                .unwrap_or(false)
+        })
+        .map(|pair| pair.index)
+        .collect();
+}
+
+fn popup_purge(siv: &mut Cursive) {
+    let ids = out_of_scope_ids(siv);
+    if ids.is_empty() {
+        siv.get_cruster_userdata().status.set_message("Scope is applied");
+        return;
+    }
+
+    let styled_text = StyledString::styled(
+        format!("\nRemove {} out-of-scope pairs from storage?", ids.len()),
+        BaseColor::Yellow.light()
+    );
+
+    let dialog = Dialog::around(TextView::new(styled_text))
+        .title("Strict Scope")
+        .button("No", |s: &mut Cursive| {
+            views_stack::pop_layer(s);
+            s.get_cruster_userdata().status.set_message("Scope is applied");
+        })
+        .button("Yes", move |s: &mut Cursive| {
+            views_stack::pop_layer(s);
+            purge(s, &ids);
+        })
+        .h_align(cursive::align::HAlign::Center);
+
+    views_stack::push_layer(siv, dialog);
+}
+
+fn purge(siv: &mut Cursive, ids: &[usize]) {
+    let ud = siv.get_cruster_userdata();
+    for id in ids {
+        if let Err(err) = ud.http_storage.remove_by_id(*id, false) {
+            ud.push_error(err);
+        }
+    }
+
+    ud.update_status();
+    ud.status.set_message(format!("Scope is applied, {} pairs are removed", ids.len()));
+    refill_table(siv);
+}
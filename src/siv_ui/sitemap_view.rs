@@ -0,0 +1,143 @@
+use std::rc::Rc;
+use std::collections::HashSet;
+use cursive::{
+    Cursive,
+    views::{
+        Dialog,
+        OnEventView,
+        SelectView
+    },
+    view::{
+        Nameable,
+        Resizable,
+        Scrollable
+    },
+    event::Key,
+};
+
+use super::{views_stack, filter_view, sivuserdata::GetCrusterUserData};
+use crate::analysis::sitemap::{SiteMap, SiteMapNode};
+
+type SiteMapTree = SelectView<String>;
+
+fn node_to_items(node: &SiteMapNode, depth: usize, expanded: &HashSet<String>, items: &mut Vec<(String, String)>) {
+    let is_expanded = expanded.contains(&node.prefix);
+    let marker = if node.children.is_empty() {
+        "  "
+    }
+    else if is_expanded {
+        "▾ "
+    }
+    else {
+        "▸ "
+    };
+
+    let label = format!("{}{}{} {}", "  ".repeat(depth), marker, &node.name, node.summary());
+    items.push((label, node.prefix.clone()));
+
+    if is_expanded {
+        for child in node.children.values() {
+            node_to_items(child, depth + 1, expanded, items);
+        }
+    }
+}
+
+fn make_items(sitemap: &SiteMap, expanded: &HashSet<String>) -> Vec<(String, String)> {
+    let mut items: Vec<(String, String)> = Vec::with_capacity(sitemap.hosts.len() * 4);
+    for host in sitemap.hosts.values() {
+        node_to_items(host, 0, expanded, &mut items);
+    }
+
+    return items;
+}
+
+fn redraw_tree(siv: &mut Cursive, sitemap: &SiteMap, selected_prefix: &str) {
+    let items = make_items(sitemap, &siv.get_cruster_userdata().sitemap_expanded);
+    siv.call_on_name("sitemap-tree", |tree: &mut SiteMapTree| {
+        tree.clear();
+        let mut selected: usize = 0;
+        for (i, (label, prefix)) in items.into_iter().enumerate() {
+            if prefix == selected_prefix {
+                selected = i;
+            }
+
+            tree.add_item(label, prefix);
+        }
+
+        // Callback is not needed, because tree is just redrawn
+        let _ = tree.set_selection(selected);
+    });
+}
+
+/// `expand`: `Some(true)` to expand, `Some(false)` to collapse, `None` to toggle
+fn change_node_state(siv: &mut Cursive, sitemap: &SiteMap, expand: Option<bool>) {
+    let selected = siv.call_on_name("sitemap-tree", |tree: &mut SiteMapTree| {
+        tree.selection().map(|s| s.as_ref().clone())
+    });
+
+    let prefix = match selected.flatten() {
+        Some(prefix) => prefix,
+        None => return
+    };
+
+    let has_children = sitemap
+        .find(&prefix)
+        .map(|node| !node.children.is_empty())
+        .unwrap_or(false);
+
+    let ud = siv.get_cruster_userdata();
+    let is_expanded = ud.sitemap_expanded.contains(&prefix);
+    match (expand.unwrap_or(!is_expanded), has_children) {
+        (true, true) => { ud.sitemap_expanded.insert(prefix.clone()); },
+        (false, _) => { ud.sitemap_expanded.remove(&prefix); },
+        _ => return
+    }
+
+    redraw_tree(siv, sitemap, &prefix);
+}
+
+fn filter_by_node(siv: &mut Cursive, prefix: &str) {
+    // Filter is matched against request's first line, i.e. 'GET https://example.com/api?q=1 HTTP/1.1'
+    let filter = format!(r"^\S+ {}([/?# ]|$)", regex::escape(prefix));
+    filter_view::apply(siv, &filter);
+    siv.get_cruster_userdata().status.set_message(format!("Showing {}", prefix));
+}
+
+pub(super) fn draw_sitemap(siv: &mut Cursive) {
+    let ud = siv.get_cruster_userdata();
+    let in_scope = ud.http_storage
+        .into_iter()
+        .filter(|pair| {
+            pair.request
+                .as_ref()
+                .map(|req| ud.is_uri_in_socpe(&req.uri))
+                .unwrap_or(false)
+        });
+
+    let sitemap = Rc::new(SiteMap::from_pairs(in_scope));
+    if sitemap.hosts.is_empty() {
+        ud.status.set_message("Site map is empty");
+        return;
+    }
+
+    let mut tree: SiteMapTree = SelectView::new();
+    for (label, prefix) in make_items(&sitemap, &ud.sitemap_expanded) {
+        tree.add_item(label, prefix);
+    }
+
+    tree.set_on_submit(|s: &mut Cursive, prefix: &String| { filter_by_node(s, prefix); });
+
+    let (toggle_map, expand_map, collapse_map) = (sitemap.clone(), sitemap.clone(), sitemap);
+    let with_events = OnEventView::new(tree.with_name("sitemap-tree").scrollable())
+        .on_event(Key::Esc, |s: &mut Cursive| { views_stack::pop_layer(s); })
+        .on_event(' ', move |s: &mut Cursive| { change_node_state(s, &toggle_map, None); })
+        .on_pre_event(Key::Right, move |s: &mut Cursive| { change_node_state(s, &expand_map, Some(true)); })
+        .on_pre_event(Key::Left, move |s: &mut Cursive| { change_node_state(s, &collapse_map, Some(false)); });
+
+    let dialog = Dialog::around(with_events)
+        .title("Site Map")
+        .full_screen();
+
+    views_stack::push_fullscreen_layer(siv, dialog);
+    siv.get_cruster_userdata().status.set_message("<Space>/<Right>/<Left> to expand or collapse, <Enter> to filter table");
+}
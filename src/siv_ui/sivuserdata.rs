@@ -1,22 +1,24 @@
-use std::fs;
-use regex::Regex;
 use serde_json as json;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use crossbeam_channel::Receiver;
-use std::io::{Write, BufReader, BufRead};
 use cursive::{views::TextContent, Cursive};
 
 use super::repeater;
 use super::status_bar;
 use super::repeater::RepeaterState;
 use super::repeater::RepeaterStateSerializable;
+use super::comparer_view::ComparerState;
 use crate::{
     config::Config,
     utils::CrusterError,
     http_storage::HTTPStorage,
-    scope
+    project::{files::{self, FileLock, RecordReader}, views::ProjectViews},
+    query::Query,
+    scope::ScopeMatcher
 };
 use crate::cruster_proxy::events::ProxyEvents;
+use crate::session::Sessions;
+use std::sync::Arc;
 
 pub(super) struct SivUserData {
     pub(super) config: Config,
@@ -25,14 +27,20 @@ pub(super) struct SivUserData {
     pub(super) request_view_content: TextContent,
     pub(super) response_view_content: TextContent,
     pub(super) filter_content: String,
+    // Parsed filter_content, None if filter is empty
+    pub(super) filter_query: Option<Query>,
+    pub(super) views: ProjectViews,
     pub(super) active_http_table_name: &'static str,
     pub(super) errors: Vec<CrusterError>,
     pub(super) status: status_bar::StatusBarContent,
     pub(super) data_storing_started: bool,
-    pub(super) include: Option<Vec<Regex>>,
-    pub(super) exclude: Option<Vec<Regex>>,
+    pub(super) scope: ScopeMatcher,
     pub(super) table_id_ref: HashMap<usize, usize>,
     pub(super) repeater_state: Vec<repeater::RepeaterState>,
+    pub(super) sitemap_expanded: HashSet<String>,
+    pub(super) comparer: ComparerState,
+    // Shared with proxy, so repeaters use the same tokens
+    pub(super) sessions: Arc<Sessions>,
 }
 
 pub(super) trait GetCrusterUserData {
@@ -62,20 +70,7 @@ impl SivUserData {
     }
 
     pub(super) fn is_uri_in_socpe(&self, uri: &str) -> bool {
-        match (self.include.as_ref(), self.exclude.as_ref()) {
-            (None, None) => {
-                return true;
-            },
-            (Some(included), None) => {
-                return scope::fit_included(uri, included.as_slice());
-            },
-            (None, Some(excluded)) => {
-                return scope::fit_excluded(uri, &excluded);
-            },
-            (Some(inc), Some(exc)) => {
-                return scope::fit(uri, &inc, &exc);
-            }
-        }
+        return self.scope.fits(uri);
     }
 
     pub(super) fn is_scope_strict(&self) -> bool {
@@ -88,73 +83,69 @@ impl SivUserData {
     }
 
     pub(super) fn store_repeater_state(&self, pth: &str) -> Result<(), CrusterError> {
-        let mut fout = fs::OpenOptions::new().create(true).truncate(true).write(true).open(pth)?;
-        for rs in self.repeater_state.iter() {
-            let serializable = RepeaterStateSerializable::from(rs);
-            let jsn = json::to_string(&serializable)?;
-            let _bytes_written = fout.write(jsn.as_bytes())?;
-            let _one_byte_written = fout.write("\n".as_bytes())?;
-        }
+        let lock = FileLock::exclusive(pth)?;
+        files::rewrite(&lock, pth, |fout| {
+            for rs in self.repeater_state.iter() {
+                let serializable = RepeaterStateSerializable::from(rs);
+                let jsn = json::to_string(&serializable)?;
+                fout.write_all(jsn.as_bytes())?;
+                fout.write_all("\n".as_bytes())?;
+            }
 
-        Ok(())
+            Ok(())
+        })
     }
 
     pub(super) fn load_repeater_state(&mut self, pth: &str) -> Result<(), CrusterError> {
-        match std::fs::File::open(pth) {
-            Ok(fin) => {
-                let reader = BufReader::new(fin);
-                for read_result in reader.lines() {
-                    if let Ok(line) = read_result {
-                        if line.is_empty() {
-                            continue;
-                        }
-                        let rs: RepeaterStateSerializable = json::from_str(&line)?;
-                        let rss = RepeaterState::try_from(rs)?;
-                        self.repeater_state.push(rss);
-                    }
-                }
-            },
-            Err(e) => {
-                return Err(e.into());
-            }
+        for read_result in RecordReader::open(pth)? {
+            let rs: RepeaterStateSerializable = json::from_str(&read_result?)?;
+            let rss = RepeaterState::try_from(rs)?;
+            self.repeater_state.push(rss);
         }
 
         Ok(())
     }
 
+    /// Changes saved views. Views of project are reread before change, so the ones saved with CLI meanwhile are kept
+    pub(super) fn update_views<F>(&mut self, change: F)
+    where
+        F: FnOnce(&mut ProjectViews)
+    {
+        let project = match self.config.project.as_ref() {
+            Some(project) => project.clone(),
+            None => {
+                change(&mut self.views);
+                return;
+            }
+        };
+
+        let result = ProjectViews::lock(&project).and_then(|lock| {
+            let mut views = ProjectViews::load(&project)?;
+            change(&mut views);
+            views.store(&lock, &project)?;
+            Ok(views)
+        });
+
+        match result {
+            Ok(views) => self.views = views,
+            Err(err) => self.push_error(err)
+        }
+    }
+
     pub(super) fn is_http_pair_match_filter(&mut self, id: usize) -> bool {
         let pair = self.http_storage.get_by_id(id);
         if pair.is_none() {
             return false;
         }
 
-        if self.filter_content.is_empty() {
-            return true;
-        }
-
-        let re = Regex::new(&self.filter_content);
-        if re.is_err() {
-            return false;
-        }
-
-        return super::filter_view::is_pair_match_filter(pair.unwrap(), re.as_ref().unwrap());
+        return match self.filter_query.as_ref() {
+            Some(query) => query.matches(pair.unwrap(), &|uri| self.is_uri_in_socpe(uri)),
+            None => true
+        };
     }
 }
 
 pub(super) fn make_scope(siv: &mut Cursive) {
     let ud: &mut SivUserData = siv.user_data().unwrap();
-
-    if let Some(scope) = ud.config.scope.as_mut() {
-        if let Some(included) = scope.include.as_mut() {
-            let compiled = scope::make_re_list(included.as_slice());
-            ud.include = Some(compiled);
-        }
-    }
-
-    if let Some(scope) = ud.config.scope.as_mut() {
-        if let Some(excluded) = scope.exclude.as_mut() {
-            let compiled = scope::make_re_list(excluded.as_slice());
-            ud.exclude = Some(compiled);
-        }
-    }
-}
\ No newline at end of file
+    ud.scope = ScopeMatcher::from_config(ud.config.scope.as_ref());
+}
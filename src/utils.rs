@@ -88,6 +88,8 @@ pub(crate) enum CrusterError {
     SystemTimeError(String),
     CrusterConfigErrror(String),
     CrusterCLIError(String),
+    EncryptionError(String),
+    ScriptError(String),
 }
 
 impl From<io::Error> for CrusterError {
@@ -339,6 +341,12 @@ impl fmt::Display for CrusterError {
             CrusterError::CrusterCLIError(s) => {
                 write!(f, "{}", s)
             },
+            CrusterError::EncryptionError(s) => {
+                write!(f, "{}", s)
+            },
+            CrusterError::ScriptError(s) => {
+                write!(f, "{}", s)
+            },
             _ => { write!(f, "{:?}", self) }
         }
     }